@@ -14,6 +14,8 @@ pub struct SendRecord {
     pub status: SendStatus,
     #[serde(default)]
     pub error_message: Option<String>,
+    #[serde(default)]
+    pub speed_mbps: Option<f64>, // Ultima viteză raportată de transfer (lipsește în istoricul vechi)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
@@ -24,7 +26,8 @@ pub enum SendStatus {
     Cancelled,
 }
 
-fn send_history_path() -> PathBuf {
+// Istoricul JSON al versiunilor vechi, importat o singură dată în `HistoryStore`
+pub fn send_history_path() -> PathBuf {
     dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join(".photo_transfer_send_history.json")
@@ -39,25 +42,3 @@ pub fn load_send_history() -> Result<Vec<SendRecord>, String> {
     let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
     serde_json::from_str(&content).map_err(|e| e.to_string())
 }
-
-pub fn save_send_history(history: &[SendRecord]) -> Result<(), String> {
-    let path = send_history_path();
-    // Keep only last 500 records
-    let to_save: Vec<_> = history.iter().rev().take(500).cloned().collect();
-    let content = serde_json::to_string_pretty(&to_save).map_err(|e| e.to_string())?;
-    std::fs::write(&path, content).map_err(|e| e.to_string())
-}
-
-pub fn add_send_record(record: SendRecord) -> Result<(), String> {
-    let mut history = load_send_history().unwrap_or_default();
-    history.push(record);
-    save_send_history(&history)
-}
-
-pub fn clear_send_history() -> Result<(), String> {
-    let path = send_history_path();
-    if path.exists() {
-        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
-    }
-    Ok(())
-}
@@ -62,12 +62,16 @@ impl ServiceDiscovery {
                                     .map(|v| v.val_str().to_string())
                                     .unwrap_or_else(|| info.get_fullname().to_string());
 
+                                // Receiver-ele vechi nu au cheie
+                                let key = info.get_properties().get("key").map(|v| v.val_str().to_string());
+
                                 if let Some(addr) = info.get_addresses().iter().next() {
                                     let service = DiscoveredService {
                                         name: name.clone(),
                                         role: role.clone(),
                                         host: addr.to_string(),
                                         port: info.get_port(),
+                                        key,
                                     };
                                     println!("mDNS Discovery: Adding service {} ({}) at {}:{}", name, role, addr, info.get_port());
                                     on_service_found(service);
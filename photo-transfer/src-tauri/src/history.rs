@@ -0,0 +1,283 @@
+use crate::config::{load_send_history, send_history_path, SendRecord, SendStatus};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sends (
+        id            INTEGER PRIMARY KEY,
+        timestamp     TEXT NOT NULL,
+        target_name   TEXT NOT NULL,
+        target_role   TEXT NOT NULL,
+        file_count    INTEGER NOT NULL,
+        total_size    INTEGER NOT NULL,
+        status        TEXT NOT NULL,
+        error_message TEXT
+    );
+    CREATE INDEX IF NOT EXISTS sends_by_time ON sends (timestamp);
+
+    CREATE TABLE IF NOT EXISTS sent_files (
+        path     TEXT NOT NULL,
+        size     INTEGER NOT NULL,
+        modified INTEGER NOT NULL,
+        receiver TEXT NOT NULL,
+        sent_at  TEXT NOT NULL,
+        PRIMARY KEY (path, size, modified, receiver)
+    );
+
+    CREATE TABLE IF NOT EXISTS imports (
+        file        TEXT PRIMARY KEY,
+        imported_at TEXT NOT NULL,
+        records     INTEGER NOT NULL
+    );
+";
+
+// Modificările schemei după prima versiune, aplicate o singură dată în ordine (PRAGMA user_version)
+// `sent_files.receiver` e cheia publică a receiver-ului; rândurile mai vechi rămân cu numele lui
+const MIGRATIONS: &[&str] = &[
+    "ALTER TABLE sends ADD COLUMN speed_mbps REAL",
+    "ALTER TABLE sent_files ADD COLUMN receiver_name TEXT NOT NULL DEFAULT '';
+     UPDATE sent_files SET receiver_name = receiver;",
+];
+
+/// Filtrele istoricului; câmpurile lipsă nu filtrează
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SendFilter {
+    pub target_name: Option<String>, // Fără diferență între litere mari și mici
+    pub target_role: Option<String>,
+    pub status: Option<SendStatus>,
+    pub from: Option<DateTime<Utc>>, // Inclusiv
+    pub to: Option<DateTime<Utc>>,   // Exclusiv
+}
+
+/// Un fișier de pe disc, recunoscut după cale, mărime și data modificării. Un card formatat
+/// refolosește numele (IMG_0001), dar nu și mărimea și data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileKey {
+    pub path: String,
+    pub size: u64,
+    pub modified: i64, // Milisecunde de la epoch
+}
+
+impl FileKey {
+    pub fn read(path: &str) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        Ok(Self { path: path.to_string(), size: metadata.len(), modified })
+    }
+}
+
+/// Istoricul trimiterilor, într-o bază SQLite (fără limită de înregistrări)
+#[derive(Clone)]
+pub struct HistoryStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl HistoryStore {
+    pub fn path() -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".photo_transfer.db")
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("Nu pot deschide istoricul {}: {}", path.display(), e))?;
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+        Self::init(conn)
+    }
+
+    /// Istoricul de pe disc sau, dacă nu poate fi deschis (fișier corupt, disc plin), unul în memorie:
+    /// aplicația pornește oricum, doar că istoricul nu se păstrează după închidere
+    pub fn open_or_in_memory(path: &Path) -> Self {
+        Self::open(path).unwrap_or_else(|e| {
+            eprintln!("{} - istoricul se păstrează doar până la închiderea aplicației", e);
+            Connection::open_in_memory()
+                .map_err(|e| e.to_string())
+                .and_then(Self::init)
+                .expect("SQLite în memorie")
+        })
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA).map_err(|e| format!("Eroare creare tabele istoric: {}", e))?;
+        migrate(&conn).map_err(|e| format!("Eroare actualizare tabele istoric: {}", e))?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut Connection) -> rusqlite::Result<R>) -> Result<R, String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        f(&mut conn).map_err(|e| format!("Eroare istoric: {}", e))
+    }
+
+    pub fn add(&self, record: &SendRecord) -> Result<(), String> {
+        self.with(|conn| insert_send(conn, record))
+    }
+
+    /// Trimiterile, în ordinea în care au avut loc
+    pub fn sends(&self, filter: &SendFilter) -> Result<Vec<SendRecord>, String> {
+        self.with(|conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT timestamp, target_name, target_role, file_count, total_size, status, error_message, speed_mbps
+                 FROM sends
+                 WHERE (?1 IS NULL OR target_name = ?1 COLLATE NOCASE)
+                   AND (?2 IS NULL OR target_role = ?2)
+                   AND (?3 IS NULL OR status = ?3)
+                   AND (?4 IS NULL OR timestamp >= ?4)
+                   AND (?5 IS NULL OR timestamp < ?5)
+                 ORDER BY timestamp, id",
+            )?;
+            let rows = stmt.query_map(
+                params![
+                    filter.target_name.as_deref().map(str::trim),
+                    filter.target_role,
+                    filter.status.as_ref().map(status_text),
+                    filter.from.as_ref().map(timestamp_text),
+                    filter.to.as_ref().map(timestamp_text),
+                ],
+                send_from_row,
+            )?;
+            rows.collect()
+        })
+    }
+
+    /// Fișierul a fost confirmat de receiver-ul cu cheia publică `receiver_id`
+    /// (numele, la receiver-ele necriptate); `receiver_name` e doar pentru afișare
+    pub fn mark_sent(&self, file: &FileKey, receiver_id: &str, receiver_name: &str) -> Result<(), String> {
+        self.with(|conn| {
+            conn.prepare_cached(
+                "INSERT OR REPLACE INTO sent_files (path, size, modified, receiver, receiver_name, sent_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?
+            .execute(params![
+                file.path,
+                file.size as i64,
+                file.modified,
+                receiver_id,
+                receiver_name,
+                timestamp_text(&Utc::now())
+            ])
+            .map(|_| ())
+        })
+    }
+
+    /// Id-urile receiver-elor (vezi `mark_sent`) care au primit deja fiecare fișier, în ordinea din `files`
+    pub fn sent_to(&self, files: &[FileKey]) -> Result<Vec<Vec<String>>, String> {
+        self.with(|conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT receiver FROM sent_files WHERE path = ?1 AND size = ?2 AND modified = ?3 ORDER BY sent_at",
+            )?;
+            files
+                .iter()
+                .map(|f| stmt.query_map(params![f.path, f.size as i64, f.modified], |row| row.get(0))?.collect())
+                .collect()
+        })
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        self.with(|conn| conn.execute("DELETE FROM sends", [])).map(|_| ())
+    }
+
+    /// Importă o singură dată fișierul JSON al versiunilor vechi (fișierul rămâne pe disc ca backup)
+    pub fn import_legacy(&self) {
+        let file = send_history_path();
+        let imported = self.with(|conn| {
+            conn.query_row("SELECT 1 FROM imports WHERE file = ?1", [file.to_string_lossy()], |_| Ok(()))
+                .optional()
+        });
+        if !file.exists() || !matches!(imported, Ok(None)) {
+            return;
+        }
+
+        let result = load_send_history().and_then(|mut records| {
+            // Fișierul vechi era inversat la fiecare salvare
+            records.sort_by_key(|r| r.timestamp);
+            self.with(|conn| {
+                let tx = conn.transaction()?;
+                for record in &records {
+                    insert_send(&tx, record)?;
+                }
+                tx.execute(
+                    "INSERT INTO imports (file, imported_at, records) VALUES (?1, ?2, ?3)",
+                    params![file.to_string_lossy(), timestamp_text(&Utc::now()), records.len() as i64],
+                )?;
+                tx.commit()?;
+                Ok(records.len())
+            })
+        });
+        match result {
+            Ok(count) => println!("Istoric importat din {}: {} înregistrări", file.display(), count),
+            Err(e) => eprintln!("Nu pot importa istoricul din {}: {}", file.display(), e),
+        }
+    }
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        conn.execute_batch(migration)?;
+        conn.pragma_update(None, "user_version", index + 1)?;
+    }
+    Ok(())
+}
+
+fn insert_send(conn: &Connection, record: &SendRecord) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO sends
+             (timestamp, target_name, target_role, file_count, total_size, status, error_message, speed_mbps)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            timestamp_text(&record.timestamp),
+            record.target_name,
+            record.target_role,
+            record.file_count as i64,
+            record.total_size as i64,
+            status_text(&record.status),
+            record.error_message,
+            record.speed_mbps,
+        ],
+    )
+    .map(|_| ())
+}
+
+fn send_from_row(row: &Row) -> rusqlite::Result<SendRecord> {
+    let timestamp: String = row.get(0)?;
+    Ok(SendRecord {
+        timestamp: DateTime::parse_from_rfc3339(&timestamp)
+            .map(|t| t.with_timezone(&Utc))
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?,
+        target_name: row.get(1)?,
+        target_role: row.get(2)?,
+        file_count: row.get::<_, i64>(3)? as usize,
+        total_size: row.get::<_, i64>(4)? as u64,
+        status: status_from_text(&row.get::<_, String>(5)?),
+        error_message: row.get(6)?,
+        speed_mbps: row.get(7)?,
+    })
+}
+
+// Format fix (UTC, microsecunde), ca ordinea textului să fie ordinea cronologică
+fn timestamp_text(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn status_text(status: &SendStatus) -> &'static str {
+    match status {
+        SendStatus::Success => "Success",
+        SendStatus::Error => "Error",
+        SendStatus::Cancelled => "Cancelled",
+    }
+}
+
+fn status_from_text(text: &str) -> SendStatus {
+    match text {
+        "Error" => SendStatus::Error,
+        "Cancelled" => SendStatus::Cancelled,
+        _ => SendStatus::Success,
+    }
+}
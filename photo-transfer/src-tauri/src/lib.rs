@@ -1,15 +1,20 @@
 mod config;
 mod discovery;
+mod history;
+mod stats;
 mod transfer;
 
-use config::{SendRecord, SendStatus, add_send_record, load_send_history, clear_send_history};
+use config::SendRecord;
 use discovery::ServiceDiscovery;
+use history::{FileKey, HistoryStore, SendFilter};
+use stats::SendStats;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use tauri::State;
+use transfer_protocol::ReceiverInfo;
 
 // Callback-uri pentru discovery (trebuie să fie Sync pentru a fi partajate între thread-uri)
 type ServiceFoundCallback = Arc<dyn Fn(DiscoveredService) + Send + Sync>;
@@ -23,6 +28,7 @@ pub struct AppState {
     // Callback-uri pentru recrearea discovery-ului
     pub on_service_found: ServiceFoundCallback,
     pub on_service_removed: ServiceRemovedCallback,
+    pub history: HistoryStore,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +37,8 @@ pub struct DiscoveredService {
     pub role: String, // "tagger" sau "editor"
     pub host: String,
     pub port: u16,
+    #[serde(default)]
+    pub key: Option<String>, // Cheia publică anunțată prin mDNS (lipsește la receiver-ele vechi)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +48,14 @@ pub struct FileInfo {
     pub size: u64,
 }
 
+/// Un fișier din selecție, cu receiver-ele care l-au primit deja
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpandedFile {
+    pub path: String,
+    pub size: u64,
+    pub sent_to: Vec<String>, // Receiver-ele care l-au primit deja: cheia publică sau, la cele vechi, numele
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransferProgress {
     pub file_name: String,
@@ -50,6 +66,14 @@ pub struct TransferProgress {
     pub speed_mbps: f64,
 }
 
+/// Progresul calculării hash-urilor la verificarea duplicatelor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChecksumProgress {
+    pub current: usize,
+    pub total: usize,
+    pub file_name: String,
+}
+
 // Comenzi Tauri
 
 #[tauri::command]
@@ -97,24 +121,30 @@ async fn send_files(
     // Reset flag și trimite fișierele
     state.is_transfer_cancelled.store(false, Ordering::Relaxed);
     let is_cancelled = Arc::clone(&state.is_transfer_cancelled);
-    transfer::send_files_to_receiver(&service, &photographer_name, &files, is_cancelled, window).await
+    let options = load_send_options();
+    let history = state.history.clone();
+    transfer::send_files_to_receiver(service, photographer_name, files, options, history, is_cancelled, window).await
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn send_files_to_host(
     state: State<'_, AppState>,
     target_host: String,
     target_port: u16,
+    target_name: String,
+    target_role: String,
     photographer_name: String,
     file_paths: Vec<String>,
     window: tauri::Window,
 ) -> Result<(), String> {
     // Create a temporary service for the specified host
     let service = DiscoveredService {
-        name: format!("{}:{}", target_host, target_port),
-        role: "direct".to_string(),
+        name: target_name,
+        role: target_role, // Salvat în istoric
         host: target_host,
         port: target_port,
+        key: None,
     };
 
     // Pregătește fișierele
@@ -138,7 +168,9 @@ async fn send_files_to_host(
     // Reset flag și trimite fișierele
     state.is_transfer_cancelled.store(false, Ordering::Relaxed);
     let is_cancelled = Arc::clone(&state.is_transfer_cancelled);
-    transfer::send_files_to_receiver(&service, &photographer_name, &files, is_cancelled, window).await
+    let options = load_send_options();
+    let history = state.history.clone();
+    transfer::send_files_to_receiver(service, photographer_name, files, options, history, is_cancelled, window).await
 }
 
 fn get_media_extensions_list() -> Vec<&'static str> {
@@ -162,9 +194,9 @@ async fn get_media_extensions() -> Vec<String> {
 }
 
 /// Expand paths - if a path is a folder, recursively find all media files inside
-/// Returns a flat list of file paths (no folders)
+/// Returns a flat list of files (no folders), each with the receivers it was already sent to
 #[tauri::command]
-async fn expand_paths(paths: Vec<String>) -> Result<Vec<String>, String> {
+async fn expand_paths(state: State<'_, AppState>, paths: Vec<String>) -> Result<Vec<ExpandedFile>, String> {
     use std::collections::HashSet;
 
     let extensions: HashSet<&str> = get_media_extensions_list().into_iter().collect();
@@ -208,25 +240,153 @@ async fn expand_paths(paths: Vec<String>) -> Result<Vec<String>, String> {
         collect_files(path, &extensions, &mut result);
     }
 
-    Ok(result)
+    // Fișierele care nu pot fi citite nu pot fi nici trimise
+    let keys: Vec<FileKey> = result.iter().filter_map(|p| FileKey::read(p).ok()).collect();
+    let sent_to = state.history.sent_to(&keys)?;
+    Ok(keys
+        .into_iter()
+        .zip(sent_to)
+        .map(|(key, sent_to)| ExpandedFile { path: key.path, size: key.size, sent_to })
+        .collect())
+}
+
+fn config_path() -> Result<PathBuf, String> {
+    Ok(dirs::home_dir()
+        .ok_or("Nu s-a găsit directorul home")?
+        .join(".photo_transfer_sender_tauri.json"))
+}
+
+fn read_config_file() -> serde_json::Value {
+    config_path()
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_else(|| serde_json::json!({}))
+}
+
+/// Setările de transfer din config: "parallel_connections" (1 = o singură conexiune),
+/// "event_passphrase" (parola evenimentului cerută de receiver) și "legacy_receivers"
+/// (receiver-ele vechi către care utilizatorul a permis transferul necriptat)
+fn load_send_options() -> transfer::SendOptions {
+    let config = read_config_file();
+    transfer::SendOptions {
+        parallel_connections: config
+            .get("parallel_connections")
+            .and_then(|n| n.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(transfer::DEFAULT_PARALLEL_CONNECTIONS),
+        event_key: config
+            .get("event_passphrase")
+            .and_then(|p| p.as_str())
+            .filter(|p| !p.trim().is_empty())
+            .map(transfer_protocol::event_key),
+        legacy_receivers: config
+            .get("legacy_receivers")
+            .and_then(|r| serde_json::from_value(r.clone()).ok())
+            .unwrap_or_default(),
+    }
 }
 
 #[tauri::command]
 async fn save_config(name: String) -> Result<(), String> {
-    let config_path = dirs::home_dir()
-        .ok_or("Nu s-a găsit directorul home")?
-        .join(".photo_transfer_sender_tauri.json");
+    // Păstrează celelalte setări din fișier (ex: parallel_connections)
+    let mut config = read_config_file();
+    if !config.is_object() {
+        config = serde_json::json!({});
+    }
+    config["name"] = serde_json::json!(name);
+    std::fs::write(config_path()?, serde_json::to_string_pretty(&config).unwrap())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn save_event_passphrase(passphrase: String) -> Result<(), String> {
+    let mut config = read_config_file();
+    if !config.is_object() {
+        config = serde_json::json!({});
+    }
+    config["event_passphrase"] = serde_json::json!(passphrase.trim());
+    std::fs::write(config_path()?, serde_json::to_string_pretty(&config).unwrap())
+        .map_err(|e| e.to_string())
+}
+
+/// Permite (sau retrage) transferul necriptat către un receiver vechi, confirmat explicit în UI
+#[tauri::command]
+async fn set_legacy_receiver(target_name: String, allowed: bool) -> Result<(), String> {
+    let mut config = read_config_file();
+    if !config.is_object() {
+        config = serde_json::json!({});
+    }
+    let mut receivers: Vec<String> = config
+        .get("legacy_receivers")
+        .and_then(|r| serde_json::from_value(r.clone()).ok())
+        .unwrap_or_default();
+    receivers.retain(|name| *name != target_name);
+    if allowed {
+        receivers.push(target_name);
+    }
+    config["legacy_receivers"] = serde_json::json!(receivers);
+    std::fs::write(config_path()?, serde_json::to_string_pretty(&config).unwrap())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn load_event_passphrase() -> Result<String, String> {
+    Ok(read_config_file()
+        .get("event_passphrase")
+        .and_then(|p| p.as_str())
+        .unwrap_or_default()
+        .to_string())
+}
+
+// "ask" = fereastra de duplicate, "receiver" = decide politica receiver-ului,
+// altfel acțiunea aplicată automat tuturor duplicatelor
+const DUPLICATE_POLICIES: &[&str] = &["ask", "receiver", "skip", "overwrite", "keep_both"];
+
+#[tauri::command]
+async fn save_duplicate_policy(policy: String) -> Result<(), String> {
+    if !DUPLICATE_POLICIES.contains(&policy.as_str()) {
+        return Err(format!("Politică de duplicate necunoscută: {}", policy));
+    }
+    let mut config = read_config_file();
+    if !config.is_object() {
+        config = serde_json::json!({});
+    }
+    config["duplicate_policy"] = serde_json::json!(policy);
+    std::fs::write(config_path()?, serde_json::to_string_pretty(&config).unwrap())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn load_duplicate_policy() -> Result<String, String> {
+    Ok(read_config_file()
+        .get("duplicate_policy")
+        .and_then(|p| p.as_str())
+        .filter(|p| DUPLICATE_POLICIES.contains(p))
+        .unwrap_or("ask")
+        .to_string())
+}
 
-    let config = serde_json::json!({ "name": name });
-    std::fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap())
+/// "Doar fișierele noi": la trimitere se sar fișierele primite deja de receiver-ul ales
+#[tauri::command]
+async fn save_only_new(enabled: bool) -> Result<(), String> {
+    let mut config = read_config_file();
+    if !config.is_object() {
+        config = serde_json::json!({});
+    }
+    config["only_new"] = serde_json::json!(enabled);
+    std::fs::write(config_path()?, serde_json::to_string_pretty(&config).unwrap())
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn load_only_new() -> Result<bool, String> {
+    Ok(read_config_file().get("only_new").and_then(|v| v.as_bool()).unwrap_or(false))
+}
+
 #[tauri::command]
 async fn load_config() -> Result<Option<String>, String> {
-    let config_path = dirs::home_dir()
-        .ok_or("Nu s-a găsit directorul home")?
-        .join(".photo_transfer_sender_tauri.json");
+    let config_path = config_path()?;
 
     if !config_path.exists() {
         return Ok(None);
@@ -253,30 +413,45 @@ async fn add_manual_service(
             role,
             host: ip,
             port,
+            key: None,
         },
     );
     Ok(())
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ReceiverInfo {
-    pub name: String,
-    pub role: String,
+#[tauri::command]
+async fn pair_with_receiver(
+    target_host: String,
+    target_port: u16,
+    photographer_name: String,
+    pin: String,
+) -> Result<String, String> {
+    let service = DiscoveredService {
+        name: format!("{}:{}", target_host, target_port),
+        role: "direct".to_string(),
+        host: target_host,
+        port: target_port,
+        key: None,
+    };
+
+    transfer::pair_with_receiver(service, photographer_name, pin).await
 }
 
 #[tauri::command]
 async fn check_duplicates_before_send(
     target_host: String,
     target_port: u16,
+    target_name: String,
     photographer_name: String,
     file_paths: Vec<String>,
     window: tauri::Window,
 ) -> Result<transfer::DuplicateCheckResult, String> {
     let service = DiscoveredService {
-        name: format!("{}:{}", target_host, target_port),
+        name: target_name,
         role: "direct".to_string(),
         host: target_host,
         port: target_port,
+        key: None,
     };
 
     let files: Vec<FileInfo> = file_paths
@@ -296,25 +471,30 @@ async fn check_duplicates_before_send(
         return Err("Nu s-au găsit fișiere valide".to_string());
     }
 
-    // Verificare duplicate doar după nume (instant, fără checksum)
-    transfer::check_duplicates(&service, &photographer_name, &files, Some(&window))
+    // Fiecare fișier e citit o dată pentru hash-ul BLAKE3 (receiver-ul recunoaște și copiile redenumite);
+    // progresul apare în UI ca "checksum-progress"
+    transfer::check_duplicates(service, photographer_name, files, load_send_options(), window).await
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn send_files_with_selection(
     state: State<'_, AppState>,
     target_host: String,
     target_port: u16,
+    target_name: String,
+    target_role: String,
     photographer_name: String,
     file_paths: Vec<String>,
-    files_to_send: Vec<String>,
+    selection: transfer::Selection,
     window: tauri::Window,
 ) -> Result<(), String> {
     let service = DiscoveredService {
-        name: format!("{}:{}", target_host, target_port),
-        role: "direct".to_string(),
+        name: target_name,
+        role: target_role, // Salvat în istoric
         host: target_host,
         port: target_port,
+        key: None,
     };
 
     let files: Vec<FileInfo> = file_paths
@@ -338,10 +518,12 @@ async fn send_files_with_selection(
     state.is_transfer_cancelled.store(false, Ordering::Relaxed);
     let is_cancelled = Arc::clone(&state.is_transfer_cancelled);
     transfer::send_files_with_selection(
-        &service,
-        &photographer_name,
-        &files,
-        Some(files_to_send),
+        service,
+        photographer_name,
+        files,
+        Some(selection),
+        load_send_options(),
+        state.history.clone(),
         is_cancelled,
         window,
     )
@@ -387,7 +569,7 @@ async fn restart_discovery(state: State<'_, AppState>) -> Result<(), String> {
     }
 
     // Așteaptă puțin pentru cleanup
-    std::thread::sleep(std::time::Duration::from_millis(200));
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
 
     // Creează un nou discovery
     let on_found = Arc::clone(&state.on_service_found);
@@ -418,85 +600,70 @@ async fn refresh_discovery(state: State<'_, AppState>) -> Result<(), String> {
 
 #[tauri::command]
 async fn get_receiver_info(ip: String, port: u16) -> Result<ReceiverInfo, String> {
-    use std::io::{Read, Write};
+    use transfer_protocol::{
+        client_handshake, read_json, write_frame, ClientHandshake, Hello, HelloIntent, MAX_HEADER_LEN,
+    };
     use std::net::TcpStream;
     use std::time::Duration;
 
-    // Connect to receiver
-    let addr = format!("{}:{}", ip, port);
-    let mut stream = TcpStream::connect_timeout(
-        &addr.parse().map_err(|e: std::net::AddrParseError| e.to_string())?,
-        Duration::from_secs(5),
-    )
-    .map_err(|e| format!("Nu s-a putut conecta la {}: {}", addr, e))?;
-
-    stream.set_read_timeout(Some(Duration::from_secs(5))).ok();
-    stream.set_write_timeout(Some(Duration::from_secs(5))).ok();
-
-    // Send INFO request (header_len = 0)
-    stream
-        .write_all(&0u32.to_be_bytes())
-        .map_err(|e| format!("Eroare trimitere request: {}", e))?;
-
-    // Read response length
-    let mut len_buf = [0u8; 4];
-    stream
-        .read_exact(&mut len_buf)
-        .map_err(|e| format!("Eroare citire răspuns: {}", e))?;
-    let response_len = u32::from_be_bytes(len_buf) as usize;
+    // Conectarea și handshake-ul sunt blocante (cu timeout de 5s)
+    transfer::run_blocking(move || {
+        let addr = format!("{}:{}", ip, port);
+        let connect = || -> Result<TcpStream, String> {
+            let stream = TcpStream::connect_timeout(
+                &addr.parse().map_err(|e: std::net::AddrParseError| e.to_string())?,
+                Duration::from_secs(5),
+            )
+            .map_err(|e| format!("Nu s-a putut conecta la {}: {}", addr, e))?;
+
+            stream.set_read_timeout(Some(Duration::from_secs(5))).ok();
+            stream.set_write_timeout(Some(Duration::from_secs(5))).ok();
+            Ok(stream)
+        };
+
+        // Receiver-ele noi răspund la handshake cu nume + rol
+        let mut stream = connect()?;
+        if let ClientHandshake::Accepted(reply) = client_handshake(&mut stream, &Hello::new(HelloIntent::Info))? {
+            return Ok(ReceiverInfo {
+                name: reply.name,
+                role: reply.role,
+            });
+        }
 
-    // Read response
-    let mut response_buf = vec![0u8; response_len];
-    stream
-        .read_exact(&mut response_buf)
-        .map_err(|e| format!("Eroare citire date: {}", e))?;
+        // Receiver vechi: reconectare și INFO request (header_len = 0)
+        let mut stream = connect()?;
+        write_frame(&mut stream, &[]).map_err(|e| format!("Eroare trimitere request: {}", e))?;
 
-    // Parse JSON
-    let info: ReceiverInfo =
-        serde_json::from_slice(&response_buf).map_err(|e| format!("Eroare parsare: {}", e))?;
+        let info: ReceiverInfo =
+            read_json(&mut stream, MAX_HEADER_LEN).map_err(|e| format!("Eroare citire răspuns: {}", e))?;
 
-    Ok(info)
+        Ok(info)
+    })
+    .await
 }
 
 // ========== ISTORIC TRIMITERI ==========
 
 #[tauri::command]
-async fn get_send_history() -> Result<Vec<SendRecord>, String> {
-    load_send_history()
+async fn get_send_history(state: State<'_, AppState>) -> Result<Vec<SendRecord>, String> {
+    state.history.sends(&SendFilter::default())
 }
 
+/// Istoricul filtrat după destinatar, rol, status și interval de timp
 #[tauri::command]
-async fn add_to_send_history(
-    target_name: String,
-    target_role: String,
-    file_count: usize,
-    total_size: u64,
-    status: String,
-    error_message: Option<String>,
-) -> Result<(), String> {
-    let send_status = match status.as_str() {
-        "success" => SendStatus::Success,
-        "error" => SendStatus::Error,
-        "cancelled" => SendStatus::Cancelled,
-        _ => SendStatus::Success,
-    };
-
-    let record = SendRecord {
-        timestamp: chrono::Utc::now(),
-        target_name,
-        target_role,
-        file_count,
-        total_size,
-        status: send_status,
-        error_message,
-    };
+async fn query_send_history(state: State<'_, AppState>, filter: SendFilter) -> Result<Vec<SendRecord>, String> {
+    state.history.sends(&filter)
+}
 
-    add_send_record(record)
+/// Volum, viteză, erori și orele aglomerate pe receiver, zi și rol, din istoricul trimiterilor
+#[tauri::command]
+async fn get_send_stats(state: State<'_, AppState>, filter: Option<SendFilter>) -> Result<SendStats, String> {
+    Ok(SendStats::build(&state.history.sends(&filter.unwrap_or_default())?))
 }
 
 #[tauri::command]
-async fn clear_history() -> Result<(), String> {
-    clear_send_history()
+async fn clear_history(state: State<'_, AppState>) -> Result<(), String> {
+    state.history.clear()
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -529,12 +696,16 @@ pub fn run() {
         move |fullname| on_removed_clone(fullname),
     );
 
+    let history = HistoryStore::open_or_in_memory(&HistoryStore::path());
+    history.import_legacy();
+
     let app_state = AppState {
         discovery: Arc::new(Mutex::new(Some(discovery))),
         discovered_services,
         is_transfer_cancelled: Arc::new(AtomicBool::new(false)),
         on_service_found,
         on_service_removed,
+        history,
     };
 
     tauri::Builder::default()
@@ -551,16 +722,25 @@ pub fn run() {
             expand_paths,
             save_config,
             load_config,
+            save_event_passphrase,
+            load_event_passphrase,
+            set_legacy_receiver,
+            save_duplicate_policy,
+            load_duplicate_policy,
+            save_only_new,
+            load_only_new,
             add_manual_service,
             get_receiver_info,
             check_duplicates_before_send,
+            pair_with_receiver,
             send_files_with_selection,
             cancel_transfer,
             restart_connection,
             restart_discovery,
             refresh_discovery,
             get_send_history,
-            add_to_send_history,
+            query_send_history,
+            get_send_stats,
             clear_history,
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,114 @@
+use crate::config::{SendRecord, SendStatus};
+use chrono::{Local, Timelike};
+use serde::Serialize;
+use std::collections::HashMap;
+use transfer_protocol::{Accumulator, HourLoads, Outcome, Summary};
+
+/// Volumul, viteza și rata de eșec ale unui grup de trimiteri
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Throughput {
+    pub sends: usize,
+    pub files: usize,
+    pub bytes: u64,
+    pub average_mbps: Option<f64>, // MB trimiși / timpul total, doar trimiterile cu viteza salvată
+    pub peak_mbps: Option<f64>,    // Cea mai rapidă trimitere
+    pub failed: usize,
+    pub cancelled: usize,
+    pub failure_rate: f64, // 0..1
+    pub cancel_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupThroughput {
+    pub name: String,
+    #[serde(flatten)]
+    pub throughput: Throughput,
+}
+
+/// Trimiterile dintr-o oră din zi (ora locală)
+#[derive(Debug, Clone, Serialize)]
+pub struct HourLoad {
+    pub hour: u32,
+    pub sends: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SendStats {
+    pub totals: Throughput,
+    pub targets: Vec<GroupThroughput>, // Pe receiver - cel mai lent se vede după average_mbps
+    pub days: Vec<GroupThroughput>,    // Data calendaristică
+    pub roles: Vec<GroupThroughput>,   // "tagger", "editor"
+    pub busiest_hours: Vec<HourLoad>,  // Cele mai încărcate primele, doar orele cu trimiteri
+}
+
+impl From<Summary> for Throughput {
+    fn from(s: Summary) -> Self {
+        Self {
+            sends: s.count,
+            files: s.files,
+            bytes: s.bytes,
+            average_mbps: s.average_mbps,
+            peak_mbps: s.peak_mbps,
+            failed: s.failed,
+            cancelled: s.interrupted,
+            failure_rate: s.failure_rate,
+            cancel_rate: s.interrupted_rate,
+        }
+    }
+}
+
+fn add(acc: &mut Accumulator, record: &SendRecord) {
+    let outcome = match record.status {
+        SendStatus::Success => Outcome::Complete,
+        SendStatus::Error => Outcome::Failed,
+        SendStatus::Cancelled => Outcome::Interrupted,
+    };
+    acc.add(record.file_count, record.total_size, outcome, record.speed_mbps);
+}
+
+impl SendStats {
+    pub fn build(sends: &[SendRecord]) -> Self {
+        let mut totals = Accumulator::default();
+        let mut targets: HashMap<String, Accumulator> = HashMap::new();
+        let mut days: HashMap<String, Accumulator> = HashMap::new();
+        let mut roles: HashMap<String, Accumulator> = HashMap::new();
+        let mut hours = HourLoads::default();
+
+        for record in sends {
+            let local = record.timestamp.with_timezone(&Local);
+            add(&mut totals, record);
+            add(targets.entry(record.target_name.clone()).or_default(), record);
+            add(days.entry(local.format("%Y-%m-%d").to_string()).or_default(), record);
+            add(roles.entry(record.target_role.clone()).or_default(), record);
+            hours.add(local.hour(), record.total_size);
+        }
+
+        let mut targets = groups(targets);
+        targets.sort_by(|a, b| b.throughput.bytes.cmp(&a.throughput.bytes).then(a.name.cmp(&b.name)));
+        let mut days = groups(days);
+        days.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut roles = groups(roles);
+        roles.sort_by(|a, b| a.name.cmp(&b.name));
+        let busiest_hours = hours
+            .busiest()
+            .into_iter()
+            .map(|h| HourLoad { hour: h.hour, sends: h.count, bytes: h.bytes })
+            .collect();
+
+        Self {
+            totals: totals.finish().into(),
+            targets,
+            days,
+            roles,
+            busiest_hours,
+        }
+    }
+}
+
+fn groups(groups: HashMap<String, Accumulator>) -> Vec<GroupThroughput> {
+    groups
+        .into_iter()
+        .map(|(name, acc)| GroupThroughput { name, throughput: acc.finish().into() })
+        .collect()
+}
@@ -1,17 +1,50 @@
-use crate::{DiscoveredService, FileInfo, TransferProgress};
+use crate::config::{SendRecord, SendStatus};
+use crate::history::{FileKey, HistoryStore};
+use crate::{ChecksumProgress, DiscoveredService, FileInfo, TransferProgress};
 use serde::{Deserialize, Serialize};
-use std::io::{Read, Write};
-use std::net::TcpStream;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tauri::Emitter;
+use transfer_protocol::{
+    check_plaintext, client_handshake, hash_prefix, open_channel, read_json, secure_connect, write_checksum,
+    write_json, AckResponse, Channel, ClientHandshake, DuplicateAction, FileAck, FileAttributes, FileMetadata,
+    FileStatus, Hello, HelloIntent, KeyStore, Negotiated, SecureMode, SendDecision, TransferHeader, TransferSession,
+    CAP_CHECKSUM, CAP_ENCRYPTION, CAP_FILE_RESULT, CAP_PARALLEL, CAP_RESUME, MAX_CHECKSUM_RETRIES, MAX_HEADER_LEN,
+};
+
+pub use transfer_protocol::{DuplicateInfo, PartialFileInfo};
 
 const CHUNK_SIZE: usize = 4 * 1024 * 1024; // 4 MB chunks pentru viteză maximă
 const TCP_TIMEOUT_SECS: u64 = 30;
+const CONNECT_TIMEOUT_SECS: u64 = 5;
 const MAX_CONNECT_RETRIES: u32 = 3;
 const RETRY_DELAY_MS: u64 = 500;
 
+// Transfer pe mai multe conexiuni (fișierele mici / linkurile rapide nu mai sunt limitate
+// de un singur stream și de confirmarea după fiecare fișier)
+pub const DEFAULT_PARALLEL_CONNECTIONS: usize = 4;
+const MAX_PARALLEL_CONNECTIONS: usize = 16;
+const TRANSFER_ABORTED: &str = "Transfer oprit - altă conexiune a eșuat";
+
+/// Setările senderului aplicate fiecărui transfer (din fișierul de config)
+#[derive(Debug, Clone)]
+pub struct SendOptions {
+    pub parallel_connections: usize,
+    pub event_key: Option<String>, // Derivat din parola evenimentului (None = fără parolă)
+    pub legacy_receivers: Vec<String>, // Receiver-e vechi, fără criptare, către care s-a permis trimiterea în clar
+}
+
+impl SendOptions {
+    fn allows_legacy(&self, target_name: &str) -> bool {
+        self.legacy_receivers.iter().any(|name| name == target_name)
+    }
+}
+
 /// Deschide un fișier pentru citire, cu suport pentru sharing pe Windows
 fn open_file_for_read(path: &str) -> std::io::Result<std::fs::File> {
     #[cfg(target_os = "windows")]
@@ -30,38 +63,12 @@ fn open_file_for_read(path: &str) -> std::io::Result<std::fs::File> {
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct TransferHeader {
-    photographer: String,
-    files: Vec<FileMetadata>,
-    is_folder_transfer: bool,
-    folder_name: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-struct FileMetadata {
-    name: String,
-    size: u64,
-    checksum: String, // Păstrat pentru compatibilitate, dar gol
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DuplicateInfo {
-    pub file_name: String,
-    pub existing_path: String,
-    pub existing_size: u64,
-    pub new_size: u64,
-    pub same_checksum: bool,
-}
-
-#[derive(Serialize, Deserialize)]
-struct AckResponse {
-    status: String,
-    folder: Option<String>,
-    #[serde(default)]
-    duplicates: Vec<DuplicateInfo>,
+/// Alegerea utilizatorului după verificarea duplicatelor
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Selection {
+    pub files: Vec<String>, // Numele fișierelor de trimis
     #[serde(default)]
-    resume_folder: Option<String>,
+    pub actions: HashMap<String, DuplicateAction>, // Pentru duplicate; lipsă = decide receiver-ul
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -69,14 +76,32 @@ pub struct DuplicateCheckResult {
     pub duplicates: Vec<DuplicateInfo>,
     pub resume_folder: Option<String>,
     pub target_folder: String,
+    pub partial_files: Vec<PartialFileInfo>,
+}
+
+/// I/O-ul de rețea și de fișiere e încă `std::io` blocant (protocolul nu e portat pe tokio): rulează
+/// pe pool-ul de thread-uri blocante al lui tokio, ca un transfer lung să nu țină ocupat un worker
+/// al runtime-ului async. Fiecare conexiune de trimitere ține un thread din pool cât durează.
+pub async fn run_blocking<T: Send + 'static>(
+    task: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|e| format!("Transfer oprit neașteptat: {}", e))?
 }
 
 /// Conectare cu retry logic
 fn connect_with_retry(addr: &str) -> Result<TcpStream, String> {
+    let socket_addr = addr
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| format!("Adresă invalidă: {}", addr))?;
     let mut last_error = String::new();
 
     for attempt in 1..=MAX_CONNECT_RETRIES {
-        match TcpStream::connect(addr) {
+        // Fără timeout, un receiver oprit poate bloca încercarea minute întregi
+        match TcpStream::connect_timeout(&socket_addr, Duration::from_secs(CONNECT_TIMEOUT_SECS)) {
             Ok(stream) => {
                 if attempt > 1 {
                     println!("Conectat la {} la încercarea {}", addr, attempt);
@@ -96,16 +121,9 @@ fn connect_with_retry(addr: &str) -> Result<TcpStream, String> {
     Err(format!("Nu m-am putut conecta la {} după {} încercări: {}", addr, MAX_CONNECT_RETRIES, last_error))
 }
 
-/// Verifică duplicatele înainte de transfer - doar după nume (INSTANT)
-pub fn check_duplicates(
-    service: &DiscoveredService,
-    photographer_name: &str,
-    files: &[FileInfo],
-    _window: Option<&tauri::Window>,
-) -> Result<DuplicateCheckResult, String> {
-    // Conectare la receiver cu retry
-    let addr = format!("{}:{}", service.host, service.port);
-    let mut stream = connect_with_retry(&addr)?;
+/// Conectare cu retry + setări TCP
+fn connect(addr: &str) -> Result<TcpStream, String> {
+    let stream = connect_with_retry(addr)?;
 
     stream
         .set_nodelay(true)
@@ -119,70 +137,179 @@ pub fn check_duplicates(
         .set_write_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECS)))
         .map_err(|e| format!("Eroare setare write timeout: {}", e))?;
 
-    // Construiește metadata FĂRĂ checksum
+    Ok(stream)
+}
+
+/// Cheia senderului și receiver-ele asociate prin PIN
+fn keys_path() -> Result<PathBuf, String> {
+    Ok(dirs::home_dir()
+        .ok_or("Nu s-a găsit directorul home")?
+        .join(".photo_transfer_keys.json"))
+}
+
+/// Conectare + handshake versionat. Cu receiver-ele noi, transferul trece prin canalul criptat
+/// (receiver-ul trebuie asociat). Un receiver vechi închide conexiunea la vederea preambulului -
+/// atunci folosim protocolul vechi, necriptat, doar dacă utilizatorul l-a permis pentru acest receiver.
+fn connect_with_handshake(
+    addr: &str,
+    photographer_name: &str,
+    target_name: &str,
+    options: &SendOptions,
+) -> Result<(Channel<TcpStream>, Negotiated), String> {
+    let mut stream = connect(addr)?;
+    let mut keys = KeyStore::load_or_create(&keys_path()?)?;
+    let allow_legacy = options.allows_legacy(target_name);
+
+    match client_handshake(&mut stream, &Hello::new(HelloIntent::Transfer).with_name(photographer_name))? {
+        ClientHandshake::Accepted(reply) => {
+            println!("Protocol v{} negociat cu {}, capabilități: {:?}", reply.version, addr, reply.capabilities);
+            let negotiated = reply.negotiated();
+            let channel = open_channel(stream, &mut keys, &negotiated, target_name, allow_legacy)?;
+            if !channel.is_encrypted() {
+                println!("ATENȚIE: receiver-ul {} nu suportă criptarea, transfer necriptat (permis)", addr);
+            }
+            Ok((channel, negotiated))
+        }
+        ClientHandshake::Legacy => {
+            check_plaintext(&keys, target_name, allow_legacy)?;
+            println!("Receiver-ul {} folosește protocolul vechi, reconectare fără handshake (necriptat)", addr);
+            Ok((Channel::Plain(connect(addr)?), Negotiated::legacy()))
+        }
+    }
+}
+
+/// Asociază senderul cu un receiver folosind PIN-ul afișat pe receiver.
+/// Returnează numele receiver-ului asociat.
+pub async fn pair_with_receiver(
+    service: DiscoveredService,
+    photographer_name: String,
+    pin: String,
+) -> Result<String, String> {
+    run_blocking(move || pair_blocking(&service, &photographer_name, &pin)).await
+}
+
+fn pair_blocking(service: &DiscoveredService, photographer_name: &str, pin: &str) -> Result<String, String> {
+    let addr = format!("{}:{}", service.host, service.port);
+    let mut stream = connect(&addr)?;
+
+    let negotiated = match client_handshake(&mut stream, &Hello::new(HelloIntent::Pair).with_name(photographer_name))? {
+        ClientHandshake::Accepted(reply) => reply.negotiated(),
+        ClientHandshake::Legacy => return Err("Receiver-ul folosește o versiune veche, fără asociere".to_string()),
+    };
+    if !negotiated.has(CAP_ENCRYPTION) {
+        return Err("Receiver-ul nu suportă criptarea".to_string());
+    }
+
+    let path = keys_path()?;
+    let mut keys = KeyStore::load_or_create(&path)?;
+    let (_, reply) = secure_connect(stream, &mut keys, SecureMode::Pin(pin), &negotiated)?;
+    keys.save(&path)?;
+
+    println!("Asociat cu {} ({})", reply.name, addr);
+    Ok(reply.name)
+}
+
+/// Verifică fișierele parțiale raportate de receiver. Dacă începutul fișierului local are
+/// același hash, transferul continuă de la acel offset cu hasher-ul deja inițializat.
+fn find_resume_points(
+    files: &[&FileInfo],
+    partial_files: &[PartialFileInfo],
+) -> HashMap<String, (u64, blake3::Hasher)> {
+    let mut points = HashMap::new();
+
+    for partial in partial_files {
+        let Some(file) = files.iter().find(|f| f.name == partial.file_name) else {
+            continue;
+        };
+        if partial.bytes_received == 0 || partial.bytes_received > file.size {
+            continue;
+        }
+
+        let Ok(hasher) = open_file_for_read(&file.path).and_then(|h| hash_prefix(h, partial.bytes_received)) else {
+            continue;
+        };
+
+        if hasher.finalize().to_hex().as_str() == partial.prefix_checksum {
+            points.insert(file.name.clone(), (partial.bytes_received, hasher));
+        } else {
+            println!("Fișierul parțial {} diferă pe receiver, se retrimite complet", file.name);
+        }
+    }
+
+    points
+}
+
+/// Verifică duplicatele înainte de transfer. Hash-ul fiecărui fișier îi permite receiver-ului să
+/// deosebească copiile identice (chiar redenumite) de fișierele care doar au același nume.
+pub async fn check_duplicates(
+    service: DiscoveredService,
+    photographer_name: String,
+    files: Vec<FileInfo>,
+    options: SendOptions,
+    window: tauri::Window,
+) -> Result<DuplicateCheckResult, String> {
+    run_blocking(move || check_duplicates_blocking(&service, &photographer_name, &files, &options, &window)).await
+}
+
+fn check_duplicates_blocking(
+    service: &DiscoveredService,
+    photographer_name: &str,
+    files: &[FileInfo],
+    options: &SendOptions,
+    window: &tauri::Window,
+) -> Result<DuplicateCheckResult, String> {
+    // Hash-urile se calculează înainte de conectare - receiver-ul nu așteaptă header-ul la nesfârșit.
+    // Un fișier care nu poate fi citit rămâne fără hash (comparat doar după nume).
     let file_metadata: Vec<FileMetadata> = files
         .iter()
-        .map(|f| FileMetadata {
-            name: f.name.clone(),
-            size: f.size,
-            checksum: String::new(),
+        .enumerate()
+        .map(|(index, f)| {
+            let _ = window.emit(
+                "checksum-progress",
+                ChecksumProgress { current: index + 1, total: files.len(), file_name: f.name.clone() },
+            );
+            FileMetadata {
+                name: f.name.clone(),
+                relative_path: String::new(),
+                size: f.size,
+                checksum: open_file_for_read(&f.path)
+                    .and_then(|file| hash_prefix(file, f.size))
+                    .map(|hasher| hasher.finalize().to_hex().to_string())
+                    .unwrap_or_default(),
+                attributes: FileAttributes::default(), // Doar verificare de duplicate
+            }
         })
         .collect();
 
+    // Conectare la receiver cu retry
+    let addr = format!("{}:{}", service.host, service.port);
+    let (mut stream, _) = connect_with_handshake(&addr, photographer_name, &service.name, options)?;
+
     // Trimite header-ul
     let header = TransferHeader {
         photographer: photographer_name.to_string(),
         files: file_metadata,
         is_folder_transfer: false,
         folder_name: None,
+        sender_role: None,
+        session: None,
+        event_key: options.event_key.clone(),
     };
 
-    let header_json = serde_json::to_string(&header).map_err(|e| e.to_string())?;
-    let header_bytes = header_json.as_bytes();
-
-    stream
-        .write_all(&(header_bytes.len() as u32).to_be_bytes())
-        .map_err(|e| format!("Eroare trimitere lungime header: {}", e))?;
-
-    stream
-        .write_all(header_bytes)
-        .map_err(|e| format!("Eroare trimitere header: {}", e))?;
+    write_json(&mut stream, &header).map_err(|e| format!("Eroare trimitere header: {}", e))?;
 
     // Așteaptă ACK cu informații despre duplicate
-    let mut ack_len_buf = [0u8; 4];
-    stream
-        .read_exact(&mut ack_len_buf)
-        .map_err(|e| format!("Eroare citire lungime ACK: {}", e))?;
-    let ack_len = u32::from_be_bytes(ack_len_buf) as usize;
-
-    let mut ack_buf = vec![0u8; ack_len];
-    stream
-        .read_exact(&mut ack_buf)
-        .map_err(|e| format!("Eroare citire ACK: {}", e))?;
-
     let ack: AckResponse =
-        serde_json::from_slice(&ack_buf).map_err(|e| format!("Eroare parsare ACK: {}", e))?;
+        read_json(&mut stream, MAX_HEADER_LEN).map_err(|e| format!("Eroare citire ACK: {}", e))?;
 
-    if ack.status != "ready" {
-        return Err(format!("Receiver nu e gata: {}", ack.status));
+    if !ack.is_ready() {
+        return Err(ack.refusal());
     }
 
     // Trimite lista goală pentru a închide conexiunea (doar verificare duplicate)
-    let empty_list: Vec<String> = Vec::new();
-    let decision_json = serde_json::to_string(&empty_list).map_err(|e| e.to_string())?;
-    let decision_bytes = decision_json.as_bytes();
-
-    // Trimite decizia și așteaptă să fie procesată
-    stream
-        .write_all(&(decision_bytes.len() as u32).to_be_bytes())
-        .map_err(|e| format!("Eroare trimitere decizie len: {}", e))?;
-    stream
-        .write_all(decision_bytes)
+    write_json(&mut stream, &SendDecision::new(Vec::new(), HashMap::new(), HashMap::new()))
         .map_err(|e| format!("Eroare trimitere decizie: {}", e))?;
 
-    // Flush pentru a te asigura că datele sunt trimise
-    stream.flush().ok();
-
     // Închide conexiunea graceful
     drop(stream);
 
@@ -190,192 +317,478 @@ pub fn check_duplicates(
         duplicates: ack.duplicates,
         resume_folder: ack.resume_folder,
         target_folder: ack.folder.unwrap_or_default(),
+        partial_files: ack.partial_files,
     })
 }
 
 pub async fn send_files_to_receiver(
-    service: &DiscoveredService,
-    photographer_name: &str,
-    files: &[FileInfo],
+    service: DiscoveredService,
+    photographer_name: String,
+    files: Vec<FileInfo>,
+    options: SendOptions,
+    history: HistoryStore,
     is_cancelled: Arc<AtomicBool>,
     window: tauri::Window,
 ) -> Result<(), String> {
-    send_files_with_selection(service, photographer_name, files, None, is_cancelled, window).await
+    send_files_with_selection(service, photographer_name, files, None, options, history, is_cancelled, window).await
+}
+
+/// Starea comună a conexiunilor unui transfer: progres combinat și oprire
+struct SendContext<'a> {
+    photographer_name: &'a str,
+    receiver_id: &'a str, // Cheia publică a receiver-ului (numele la cele necriptate), pentru indexul trimiselor
+    target_name: &'a str,
+    history: &'a HistoryStore,
+    event_key: Option<&'a str>,
+    is_cancelled: &'a AtomicBool,
+    window: &'a tauri::Window,
+    total_files: usize,
+    total_bytes: u64,
+    actions: &'a HashMap<String, DuplicateAction>,
+    start_time: Instant,
+    bytes_sent: AtomicU64,
+    bytes_resumed: AtomicU64, // Bytes aflați deja pe receiver (nu intră în calculul vitezei)
+    files_completed: AtomicUsize,
+    aborted: AtomicBool, // O conexiune a eșuat sau transferul a fost anulat - oprește și restul
+}
+
+impl SendContext<'_> {
+    fn speed_mbps(&self) -> f64 {
+        let elapsed = self.start_time.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            let transferred = self.bytes_sent.load(Ordering::Relaxed) - self.bytes_resumed.load(Ordering::Relaxed);
+            (transferred as f64 / elapsed) / (1024.0 * 1024.0)
+        } else {
+            0.0
+        }
+    }
+
+    // Bytes trimiși efectiv (fără cei reluați) / durata; None dacă nu s-a trimis nimic
+    fn measured_speed(&self) -> Option<f64> {
+        let speed = self.speed_mbps();
+        (speed.is_finite() && speed > 0.0).then_some(speed)
+    }
+
+    // Anularea e raportată UI-ului o singură dată, chiar dacă rulează mai multe conexiuni
+    fn check_cancelled(&self) -> Result<(), String> {
+        if self.is_cancelled.load(Ordering::Relaxed) {
+            if !self.aborted.swap(true, Ordering::Relaxed) {
+                let _ = self.window.emit("transfer-cancelled", ());
+            }
+            return Err("Transfer anulat de utilizator".to_string());
+        }
+        if self.aborted.load(Ordering::Relaxed) {
+            return Err(TRANSFER_ABORTED.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Ce s-a trimis efectiv, pentru înregistrarea din istoric
+#[derive(Default)]
+struct SendReport {
+    file_count: usize,
+    total_size: u64,
+    speed_mbps: Option<f64>,
 }
 
-/// Trimite fișierele selectate - FĂRĂ checksum (transfer direct, rapid)
+/// Împarte fișierele în `count` grupuri cu dimensiuni totale apropiate (cele mai mari primele)
+fn split_by_size<'a>(files: &[&'a FileInfo], count: usize) -> Vec<Vec<&'a FileInfo>> {
+    let mut sorted = files.to_vec();
+    sorted.sort_by_key(|f| std::cmp::Reverse(f.size));
+
+    let mut buckets: Vec<(u64, Vec<&FileInfo>)> = vec![(0, Vec::new()); count];
+    for file in sorted {
+        if let Some(bucket) = buckets.iter_mut().min_by_key(|(size, _)| *size) {
+            bucket.0 += file.size;
+            bucket.1.push(file);
+        }
+    }
+
+    buckets.into_iter().map(|(_, files)| files).filter(|files| !files.is_empty()).collect()
+}
+
+/// Trimite fișierele selectate - checksum-ul e calculat streaming, în timpul trimiterii.
+/// Dacă receiver-ul acceptă, fișierele sunt împărțite pe mai multe conexiuni paralele.
+#[allow(clippy::too_many_arguments)]
 pub async fn send_files_with_selection(
+    service: DiscoveredService,
+    photographer_name: String,
+    files: Vec<FileInfo>,
+    selection: Option<Selection>,
+    options: SendOptions,
+    history: HistoryStore,
+    is_cancelled: Arc<AtomicBool>,
+    window: tauri::Window,
+) -> Result<(), String> {
+    run_blocking(move || {
+        send_selection_blocking(
+            &service,
+            &photographer_name,
+            &files,
+            selection,
+            &options,
+            &history,
+            &is_cancelled,
+            &window,
+        )
+    })
+    .await
+}
+
+/// Trimiterea și înregistrarea ei în istoric, cu viteza măsurată aici. Înregistrarea e scrisă
+/// înainte ca UI-ul să afle rezultatul, deci trimiterile simultane nu își pot încurca vitezele.
+#[allow(clippy::too_many_arguments)]
+fn send_selection_blocking(
     service: &DiscoveredService,
     photographer_name: &str,
     files: &[FileInfo],
-    files_to_send: Option<Vec<String>>,
-    is_cancelled: Arc<AtomicBool>,
-    window: tauri::Window,
+    selection: Option<Selection>,
+    options: &SendOptions,
+    history: &HistoryStore,
+    is_cancelled: &AtomicBool,
+    window: &tauri::Window,
 ) -> Result<(), String> {
-    // Determină ce fișiere să trimită
-    let files_filtered: Vec<&FileInfo> = if let Some(ref selected) = files_to_send {
-        files.iter().filter(|f| selected.contains(&f.name)).collect()
-    } else {
-        files.iter().collect()
+    let mut report = SendReport::default();
+    let result = send_selection(
+        service,
+        photographer_name,
+        files,
+        selection,
+        options,
+        history,
+        is_cancelled,
+        window,
+        &mut report,
+    );
+
+    let cancelled = result.is_err() && is_cancelled.load(Ordering::Relaxed);
+    let record = SendRecord {
+        timestamp: chrono::Utc::now(),
+        target_name: service.name.clone(),
+        target_role: service.role.clone(),
+        file_count: report.file_count,
+        total_size: report.total_size,
+        status: match result {
+            Ok(()) => SendStatus::Success,
+            Err(_) if cancelled => SendStatus::Cancelled,
+            Err(_) => SendStatus::Error,
+        },
+        error_message: result.as_ref().err().filter(|_| !cancelled).cloned(),
+        speed_mbps: report.speed_mbps,
     };
+    if let Err(e) = history.add(&record) {
+        eprintln!("{}", e);
+    }
+
+    if result.is_ok() {
+        let _ = window.emit("transfer-complete", report.file_count);
+    }
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+fn send_selection(
+    service: &DiscoveredService,
+    photographer_name: &str,
+    files: &[FileInfo],
+    selection: Option<Selection>,
+    options: &SendOptions,
+    history: &HistoryStore,
+    is_cancelled: &AtomicBool,
+    window: &tauri::Window,
+    report: &mut SendReport,
+) -> Result<(), String> {
+    // Determină ce fișiere să trimită
+    let selection = selection.unwrap_or_else(|| Selection {
+        files: files.iter().map(|f| f.name.clone()).collect(),
+        actions: HashMap::new(),
+    });
+    let files_filtered: Vec<&FileInfo> = files
+        .iter()
+        .filter(|f| selection.files.contains(&f.name))
+        .filter(|f| selection.actions.get(&f.name) != Some(&DuplicateAction::Skip))
+        .collect();
 
+    report.file_count = files_filtered.len();
+    report.total_size = files_filtered.iter().map(|f| f.size).sum();
     if files_filtered.is_empty() {
-        let _ = window.emit("transfer-complete", 0);
         return Ok(());
     }
 
     // Conectare la receiver cu retry
     let addr = format!("{}:{}", service.host, service.port);
-    let mut stream = connect_with_retry(&addr)?;
+    let (stream, negotiated) = connect_with_handshake(&addr, photographer_name, &service.name, options)?;
+
+    // Numele mDNS se poate schimba sau repeta; cheia verificată la handshake nu
+    let receiver_id = stream.remote_key().unwrap_or(&service.name).to_string();
+
+    let ctx = SendContext {
+        photographer_name,
+        receiver_id: &receiver_id,
+        target_name: &service.name,
+        history,
+        event_key: options.event_key.as_deref(),
+        is_cancelled,
+        window,
+        total_files: files_filtered.len(),
+        total_bytes: files_filtered.iter().map(|f| f.size).sum(),
+        actions: &selection.actions,
+        start_time: Instant::now(),
+        bytes_sent: AtomicU64::new(0),
+        bytes_resumed: AtomicU64::new(0),
+        files_completed: AtomicUsize::new(0),
+        aborted: AtomicBool::new(false),
+    };
 
-    stream
-        .set_nodelay(true)
-        .map_err(|e| format!("Eroare setare TCP nodelay: {}", e))?;
+    // Viteza se măsoară și când trimiterea se oprește la jumătate
+    let result = (|| -> Result<(), String> {
+        let buckets = if negotiated.has(CAP_PARALLEL) {
+            split_by_size(&files_filtered, options.parallel_connections.clamp(1, MAX_PARALLEL_CONNECTIONS))
+        } else {
+            vec![files_filtered.clone()]
+        };
+
+        if buckets.len() <= 1 {
+            println!(
+                "Trimit {} fișiere către {} (protocol v{})",
+                files_filtered.len(),
+                addr,
+                negotiated.version
+            );
+            send_over_connection(stream, &negotiated, &files_filtered, None, &ctx)?;
+        } else {
+            println!(
+                "Trimit {} fișiere către {} pe {} conexiuni (protocol v{})",
+                files_filtered.len(),
+                addr,
+                buckets.len(),
+                negotiated.version
+            );
+
+            // Prima conexiune există deja; restul se deschid înainte de a trimite ceva
+            let mut connections = vec![(stream, negotiated)];
+            for _ in 1..buckets.len() {
+                connections.push(connect_with_handshake(&addr, photographer_name, &service.name, options)?);
+            }
 
-    // Set timeout pentru detectarea deconectărilor
-    stream
-        .set_read_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECS)))
-        .map_err(|e| format!("Eroare setare read timeout: {}", e))?;
-    stream
-        .set_write_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECS)))
-        .map_err(|e| format!("Eroare setare write timeout: {}", e))?;
+            let session = TransferSession {
+                id: uuid::Uuid::new_v4().to_string(),
+                connections: buckets.len() as u32,
+                total_files: ctx.total_files,
+                total_bytes: ctx.total_bytes,
+            };
 
+            let results: Vec<Result<(), String>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = connections
+                    .into_iter()
+                    .zip(&buckets)
+                    .map(|((stream, negotiated), bucket)| {
+                        let ctx = &ctx;
+                        let session = session.clone();
+                        scope.spawn(move || {
+                            let result = send_over_connection(stream, &negotiated, bucket, Some(session), ctx);
+                            if result.is_err() {
+                                ctx.aborted.store(true, Ordering::Relaxed);
+                            }
+                            result
+                        })
+                    })
+                    .collect();
+
+                handles
+                    .into_iter()
+                    .map(|h| h.join().unwrap_or_else(|_| Err("Conexiune de trimitere oprită neașteptat".to_string())))
+                    .collect()
+            });
+
+            // Prima eroare reală (nu oprirea provocată de ea pe celelalte conexiuni)
+            let mut errors = results.into_iter().filter_map(Result::err).collect::<Vec<_>>();
+            if !errors.is_empty() {
+                let index = errors.iter().position(|e| e != TRANSFER_ABORTED).unwrap_or(0);
+                return Err(errors.swap_remove(index));
+            }
+        }
+        Ok(())
+    })();
+    report.speed_mbps = ctx.measured_speed();
+    result
+}
+
+/// Un transfer complet (header, ACK, decizie, fișiere) pe o singură conexiune.
+/// La transferurile paralele, `session` leagă conexiunea de celelalte pe receiver.
+fn send_over_connection(
+    mut stream: Channel<TcpStream>,
+    negotiated: &Negotiated,
+    files: &[&FileInfo],
+    session: Option<TransferSession>,
+    ctx: &SendContext,
+) -> Result<(), String> {
     // Construiește metadata FĂRĂ checksum
-    let file_metadata: Vec<FileMetadata> = files_filtered
+    let file_metadata: Vec<FileMetadata> = files
         .iter()
         .map(|f| FileMetadata {
             name: f.name.clone(),
+            relative_path: String::new(),
             size: f.size,
-            checksum: String::new(),
+            checksum: String::new(), // Hash-ul se trimite după datele fișierului (calculat streaming)
+            attributes: FileAttributes::read(std::path::Path::new(&f.path)), // Data originală a pozei
         })
         .collect();
 
     // Trimite header-ul
     let header = TransferHeader {
-        photographer: photographer_name.to_string(),
+        photographer: ctx.photographer_name.to_string(),
         files: file_metadata,
         is_folder_transfer: false,
         folder_name: None,
+        sender_role: None,
+        session,
+        event_key: ctx.event_key.map(String::from),
     };
 
-    let header_json = serde_json::to_string(&header).map_err(|e| e.to_string())?;
-    let header_bytes = header_json.as_bytes();
-
-    stream
-        .write_all(&(header_bytes.len() as u32).to_be_bytes())
-        .map_err(|e| format!("Eroare trimitere lungime header: {}", e))?;
-
-    stream
-        .write_all(header_bytes)
-        .map_err(|e| format!("Eroare trimitere header: {}", e))?;
+    write_json(&mut stream, &header).map_err(|e| format!("Eroare trimitere header: {}", e))?;
 
     // Așteaptă ACK
-    let mut ack_len_buf = [0u8; 4];
-    stream
-        .read_exact(&mut ack_len_buf)
-        .map_err(|e| format!("Eroare citire lungime ACK: {}", e))?;
-    let ack_len = u32::from_be_bytes(ack_len_buf) as usize;
-
-    let mut ack_buf = vec![0u8; ack_len];
-    stream
-        .read_exact(&mut ack_buf)
-        .map_err(|e| format!("Eroare citire ACK: {}", e))?;
-
     let ack: AckResponse =
-        serde_json::from_slice(&ack_buf).map_err(|e| format!("Eroare parsare ACK: {}", e))?;
+        read_json(&mut stream, MAX_HEADER_LEN).map_err(|e| format!("Eroare citire ACK: {}", e))?;
 
-    if ack.status != "ready" {
-        return Err(format!("Receiver nu e gata: {}", ack.status));
+    if !ack.is_ready() {
+        return Err(ack.refusal());
     }
 
-    // Trimite lista de fișiere de transferat
-    let selected_names: Vec<String> = files_filtered.iter().map(|f| f.name.clone()).collect();
-    let decision_json = serde_json::to_string(&selected_names).map_err(|e| e.to_string())?;
-    let decision_bytes = decision_json.as_bytes();
-
-    stream
-        .write_all(&(decision_bytes.len() as u32).to_be_bytes())
-        .map_err(|e| format!("Eroare trimitere decizie len: {}", e))?;
-    stream
-        .write_all(decision_bytes)
-        .map_err(|e| format!("Eroare trimitere decizie: {}", e))?;
+    // Fișierele întrerupte anterior se reiau de unde au rămas (verificat prin hash)
+    let use_checksum = negotiated.has(CAP_CHECKSUM);
+    let use_file_result = negotiated.has(CAP_FILE_RESULT);
+    let mut resume_points = if use_checksum && negotiated.has(CAP_RESUME) {
+        find_resume_points(files, &ack.partial_files)
+    } else {
+        HashMap::new()
+    };
 
-    let total_bytes: u64 = files_filtered.iter().map(|f| f.size).sum();
+    // Trimite lista de fișiere de transferat (și offset-urile / acțiunile pentru duplicate, doar dacă
+    // există - receiver-ele vechi înțeleg doar lista simplă)
+    let selected_names: Vec<String> = files.iter().map(|f| f.name.clone()).collect();
+    let actions = files.iter().filter_map(|f| Some((f.name.clone(), *ctx.actions.get(&f.name)?))).collect();
+    let decision = SendDecision::new(
+        selected_names,
+        resume_points.iter().map(|(name, (offset, _))| (name.clone(), *offset)).collect(),
+        actions,
+    );
+    write_json(&mut stream, &decision).map_err(|e| format!("Eroare trimitere decizie: {}", e))?;
+
+    // Trimite fișierele - hash-ul e calculat în timpul citirii, fără o trecere separată
+    for file in files {
+        let mut attempt: u32 = 1;
+
+        // Fișierele sărite de receiver ca duplicate nu se marchează drept trimise
+        let skipped = loop {
+            // Verifică dacă transferul a fost anulat
+            ctx.check_cancelled()?;
+
+            let mut file_handle =
+                open_file_for_read(&file.path).map_err(|e| format!("Nu pot deschide {}: {}", file.name, e))?;
+
+            // Reluarea se aplică doar la prima încercare; după un checksum greșit se trimite tot
+            let (resume_offset, mut hasher) = match resume_points.remove(&file.name) {
+                Some(point) if attempt == 1 => point,
+                _ => (0, blake3::Hasher::new()),
+            };
+            if resume_offset > 0 {
+                file_handle
+                    .seek(SeekFrom::Start(resume_offset))
+                    .map_err(|e| format!("Eroare poziționare {}: {}", file.name, e))?;
+            }
 
-    // Trimite fișierele - direct, fără checksum
-    let mut total_sent: u64 = 0;
-    let start_time = Instant::now();
+            let mut buffer = vec![0u8; CHUNK_SIZE];
+            let mut file_sent: u64 = resume_offset;
+            ctx.bytes_sent.fetch_add(resume_offset, Ordering::Relaxed);
+            ctx.bytes_resumed.fetch_add(resume_offset, Ordering::Relaxed);
 
-    for (index, file) in files_filtered.iter().enumerate() {
-        // Verifică dacă transferul a fost anulat
-        if is_cancelled.load(Ordering::Relaxed) {
-            let _ = window.emit("transfer-cancelled", ());
-            return Err("Transfer anulat de utilizator".to_string());
-        }
+            while file_sent < file.size {
+                let bytes_read = file_handle
+                    .read(&mut buffer)
+                    .map_err(|e| format!("Eroare citire {}: {}", file.name, e))?;
 
-        let mut file_handle =
-            open_file_for_read(&file.path).map_err(|e| format!("Nu pot deschide {}: {}", file.name, e))?;
+                if bytes_read == 0 {
+                    break;
+                }
 
-        let mut buffer = vec![0u8; CHUNK_SIZE];
-        let mut file_sent: u64 = 0;
+                hasher.update(&buffer[..bytes_read]);
 
-        while file_sent < file.size {
-            let bytes_read = file_handle
-                .read(&mut buffer)
-                .map_err(|e| format!("Eroare citire {}: {}", file.name, e))?;
+                stream
+                    .write_all(&buffer[..bytes_read])
+                    .map_err(|e| format!("Eroare trimitere {}: {}", file.name, e))?;
 
-            if bytes_read == 0 {
-                break;
-            }
+                file_sent += bytes_read as u64;
+                let total_sent = ctx.bytes_sent.fetch_add(bytes_read as u64, Ordering::Relaxed) + bytes_read as u64;
 
-            stream
-                .write_all(&buffer[..bytes_read])
-                .map_err(|e| format!("Eroare trimitere {}: {}", file.name, e))?;
+                // Verifică dacă transferul a fost anulat după fiecare chunk
+                ctx.check_cancelled()?;
 
-            file_sent += bytes_read as u64;
-            total_sent += bytes_read as u64;
+                // Trimite progress la UI
+                let progress = TransferProgress {
+                    file_name: file.name.clone(),
+                    file_index: ctx.files_completed.load(Ordering::Relaxed),
+                    total_files: ctx.total_files,
+                    bytes_sent: total_sent,
+                    total_bytes: ctx.total_bytes,
+                    speed_mbps: ctx.speed_mbps(),
+                };
 
-            // Verifică dacă transferul a fost anulat după fiecare chunk
-            if is_cancelled.load(Ordering::Relaxed) {
-                let _ = window.emit("transfer-cancelled", ());
-                return Err("Transfer anulat de utilizator".to_string());
+                let _ = ctx.window.emit("transfer-progress", &progress);
             }
 
-            // Calculează viteza
-            let elapsed = start_time.elapsed().as_secs_f64();
-            let speed_mbps = if elapsed > 0.0 {
-                (total_sent as f64 / elapsed) / (1024.0 * 1024.0)
-            } else {
-                0.0
-            };
-
-            // Trimite progress la UI
-            let progress = TransferProgress {
-                file_name: file.name.clone(),
-                file_index: index,
-                total_files: files_filtered.len(),
-                bytes_sent: total_sent,
-                total_bytes,
-                speed_mbps,
-            };
+            // Trimite hash-ul fișierului, dacă receiver-ul l-a acceptat
+            if use_checksum {
+                write_checksum(&mut stream, &hasher.finalize())
+                    .map_err(|e| format!("Eroare trimitere checksum: {}", e))?;
+            }
 
-            let _ = window.emit("transfer-progress", &progress);
+            // Așteaptă confirmare pentru fișier
+            let response = FileAck::read_from(&mut stream, use_file_result)
+                .map_err(|e| format!("Eroare citire confirmare: {}", e))?;
+
+            match response.status {
+                // Receiver-ele noi confirmă și câți bytes au ajuns pe disc
+                FileStatus::Ok if use_file_result && response.bytes_written != file.size => {
+                    return Err(format!(
+                        "Receiver-ul a salvat {} din {} bytes pentru {}",
+                        response.bytes_written, file.size, file.name
+                    ));
+                }
+                FileStatus::Ok => break response.skipped,
+                FileStatus::ChecksumMismatch if attempt < MAX_CHECKSUM_RETRIES => {
+                    println!(
+                        "Checksum diferit pentru {} (încercarea {}/{}), retrimit...",
+                        file.name, attempt, MAX_CHECKSUM_RETRIES
+                    );
+                    ctx.bytes_sent.fetch_sub(file_sent, Ordering::Relaxed);
+                    ctx.bytes_resumed.fetch_sub(resume_offset, Ordering::Relaxed);
+                    attempt += 1;
+                }
+                FileStatus::ChecksumMismatch => {
+                    return Err(format!(
+                        "Checksum diferit pentru {} după {} încercări",
+                        file.name, MAX_CHECKSUM_RETRIES
+                    ));
+                }
+                FileStatus::Failed => return Err(response.reason(&file.name)),
+            }
+        };
+
+        // Ținut minte pentru "doar fișierele noi" la următoarea trimitere către acest receiver
+        if !skipped {
+            let marked = FileKey::read(&file.path)
+                .map_err(|e| e.to_string())
+                .and_then(|key| ctx.history.mark_sent(&key, ctx.receiver_id, ctx.target_name));
+            if let Err(e) = marked {
+                eprintln!("Nu pot marca {} ca trimis: {}", file.name, e);
+            }
         }
 
-        // Așteaptă confirmare pentru fișier (OK simplu, fără verificare checksum)
-        let mut response = [0u8; 32];
-        let n = stream
-            .read(&mut response)
-            .map_err(|e| format!("Eroare citire confirmare: {}", e))?;
-
-        let response_str = String::from_utf8_lossy(&response[..n]);
-        if !response_str.contains("OK") {
-            return Err(format!("Eroare la fișierul {}: {}", file.name, response_str));
-        }
+        ctx.files_completed.fetch_add(1, Ordering::Relaxed);
     }
 
-    // Emite eveniment de finalizare
-    let _ = window.emit("transfer-complete", files_filtered.len());
-
     Ok(())
 }
@@ -11,6 +11,7 @@ interface DiscoveredService {
   role: string;
   host: string;
   port: number;
+  key?: string | null; // Cheia publică anunțată prin mDNS (lipsește la receiver-ele vechi)
 }
 
 interface TransferProgress {
@@ -28,12 +29,38 @@ interface DuplicateInfo {
   existing_size: number;
   new_size: number;
   same_checksum: boolean;
+  existing_name?: string; // Copia existentă are alt nume (fișier redenumit)
+}
+
+// Ce face receiver-ul cu un fișier care există deja
+type DuplicateAction = "skip" | "overwrite" | "keep_both";
+
+// "ask" = fereastra de duplicate, "receiver" = decide politica receiver-ului
+type DuplicatePolicy = "ask" | "receiver" | DuplicateAction;
+
+interface Selection {
+  files: string[];
+  actions: Record<string, DuplicateAction>;
+}
+
+interface PartialFileInfo {
+  file_name: string;
+  bytes_received: number;
+  prefix_checksum: string;
 }
 
 interface DuplicateCheckResult {
   duplicates: DuplicateInfo[];
   resume_folder: string | null;
   target_folder: string;
+  partial_files: PartialFileInfo[];
+}
+
+// Rezultatul expand_paths: fișierul și receiver-ele care l-au primit deja
+interface ExpandedFile {
+  path: string;
+  size: number;
+  sent_to: string[]; // Cheile publice ale receiver-elor (numele, la cele vechi)
 }
 
 interface ChecksumProgress {
@@ -62,16 +89,11 @@ let pendingDuplicateCheck: {
   result: DuplicateCheckResult;
 } | null = null;
 
-// Current transfer info for history
-let currentTransfer: {
-  targetName: string;
-  targetRole: string;
-  fileCount: number;
-  totalSize: number;
-} | null = null;
-
 // DOM Elements
 let photographerNameInput: HTMLInputElement;
+let eventPassphraseInput: HTMLInputElement;
+let duplicatePolicySelect: HTMLSelectElement;
+let sendScopeSelect: HTMLSelectElement;
 let dropTagger: HTMLElement;
 let dropEditor: HTMLElement;
 let taggerStatus: HTMLElement;
@@ -155,6 +177,9 @@ async function checkForUpdates() {
 
 function initElements() {
   photographerNameInput = document.getElementById("photographer-name") as HTMLInputElement;
+  eventPassphraseInput = document.getElementById("event-passphrase") as HTMLInputElement;
+  duplicatePolicySelect = document.getElementById("duplicate-policy") as HTMLSelectElement;
+  sendScopeSelect = document.getElementById("send-scope") as HTMLSelectElement;
   dropTagger = document.getElementById("drop-tagger")!;
   dropEditor = document.getElementById("drop-editor")!;
   taggerStatus = document.getElementById("tagger-status")!;
@@ -178,6 +203,9 @@ async function loadConfig() {
     if (name) {
       photographerNameInput.value = name;
     }
+    eventPassphraseInput.value = await invoke<string>("load_event_passphrase");
+    duplicatePolicySelect.value = await invoke<string>("load_duplicate_policy");
+    sendScopeSelect.value = (await invoke<boolean>("load_only_new")) ? "new" : "all";
   } catch (e) {
     console.error("Error loading config:", e);
   }
@@ -213,6 +241,30 @@ function setupEventListeners() {
     }
   });
 
+  eventPassphraseInput.addEventListener("change", async () => {
+    try {
+      await invoke("save_event_passphrase", { passphrase: eventPassphraseInput.value });
+    } catch (e) {
+      console.error("Error saving passphrase:", e);
+    }
+  });
+
+  duplicatePolicySelect.addEventListener("change", async () => {
+    try {
+      await invoke("save_duplicate_policy", { policy: duplicatePolicySelect.value });
+    } catch (e) {
+      console.error("Error saving duplicate policy:", e);
+    }
+  });
+
+  sendScopeSelect.addEventListener("change", async () => {
+    try {
+      await invoke("save_only_new", { enabled: sendScopeSelect.value === "new" });
+    } catch (e) {
+      console.error("Error saving send scope:", e);
+    }
+  });
+
   // Drop zones click handlers (for files)
   dropTagger.addEventListener("click", (e) => {
     // Don't trigger if clicking on the folder button
@@ -337,12 +389,6 @@ async function setupTauriListeners() {
     progressStats.textContent = `${p.file_index + 1} / ${p.total_files} fișiere`;
     progressFile.textContent = p.file_name;
     progressSpeed.textContent = `${p.speed_mbps.toFixed(1)} MB/s`;
-
-    // Update current transfer info with actual values
-    if (currentTransfer) {
-      currentTransfer.fileCount = p.total_files;
-      currentTransfer.totalSize = p.total_bytes;
-    }
   });
 
   // Transfer complete
@@ -351,18 +397,6 @@ async function setupTauriListeners() {
     progressSection.classList.remove("active");
     showToast(`Transfer complet: ${event.payload} fișiere`, "success");
     enableDropZones();
-
-    // Save to history
-    if (currentTransfer) {
-      await saveToHistory(
-        currentTransfer.targetName,
-        currentTransfer.targetRole,
-        event.payload,
-        currentTransfer.totalSize,
-        "success"
-      );
-      currentTransfer = null;
-    }
   });
 
   // Transfer cancelled (from backend)
@@ -371,18 +405,6 @@ async function setupTauriListeners() {
     progressSection.classList.remove("active");
     showToast("Transfer anulat", "error");
     enableDropZones();
-
-    // Save to history
-    if (currentTransfer) {
-      await saveToHistory(
-        currentTransfer.targetName,
-        currentTransfer.targetRole,
-        currentTransfer.fileCount,
-        currentTransfer.totalSize,
-        "cancelled"
-      );
-      currentTransfer = null;
-    }
   });
 
   // Cancel button
@@ -397,18 +419,6 @@ async function setupTauriListeners() {
       progressSection.classList.remove("active");
       showToast("Transfer anulat", "error");
       enableDropZones();
-
-      // Save to history
-      if (currentTransfer) {
-        await saveToHistory(
-          currentTransfer.targetName,
-          currentTransfer.targetRole,
-          currentTransfer.fileCount,
-          currentTransfer.totalSize,
-          "cancelled"
-        );
-        currentTransfer = null;
-      }
     }
   });
 }
@@ -597,22 +607,43 @@ async function sendFilesToReceiver(receiver: DiscoveredService, paths: string[])
 
   try {
     // First expand paths (folders -> list of files inside)
-    const expandedPaths = await invoke<string[]>("expand_paths", { paths });
+    const expanded = await invoke<ExpandedFile[]>("expand_paths", { paths });
 
-    if (expandedPaths.length === 0) {
+    if (expanded.length === 0) {
       progressSection.classList.remove("active");
       showToast("Nu s-au găsit fișiere media în selecție", "error");
       return;
     }
 
-    // Update status - verificare duplicate (instant, fără checksum)
+    // Fișierele confirmate deja de acest receiver la o trimitere anterioară (după cheie; numele e doar afișat)
+    const receiverId = receiver.key ?? receiver.name;
+    const alreadySent = expanded.filter(f => f.sent_to.includes(receiverId)).length;
+    const onlyNew = sendScopeSelect.value === "new";
+    const expandedPaths = expanded
+      .filter(f => !onlyNew || !f.sent_to.includes(receiverId))
+      .map(f => f.path);
+
+    if (expandedPaths.length === 0) {
+      progressSection.classList.remove("active");
+      showToast(`Toate fișierele au fost trimise deja la ${receiver.name}`, "success");
+      return;
+    }
+
+    // Update status - verificare duplicate (fișierele sunt citite pentru hash)
     progressTitle.textContent = "Se verifică duplicate...";
-    progressFile.textContent = `${expandedPaths.length} fișiere găsite.`;
+    let foundText = `${expanded.length} fișiere găsite.`;
+    if (alreadySent > 0) {
+      foundText += onlyNew
+        ? ` ${alreadySent} trimise deja la ${receiver.name} sunt sărite.`
+        : ` ${alreadySent} au fost trimise deja la ${receiver.name}.`;
+    }
+    progressFile.textContent = foundText;
 
-    // Now check for duplicates with expanded paths (by name only - instant)
+    // Now check for duplicates with expanded paths (by content hash and by name)
     const result = await invoke<DuplicateCheckResult>("check_duplicates_before_send", {
       targetHost: receiver.host,
       targetPort: receiver.port,
+      targetName: receiver.name,
       photographerName: name,
       filePaths: expandedPaths,
       window: null, // Tauri will use current window
@@ -620,26 +651,110 @@ async function sendFilesToReceiver(receiver: DiscoveredService, paths: string[])
 
     progressSection.classList.remove("active");
 
-    if (result.duplicates.length > 0) {
+    const policy = duplicatePolicySelect.value as DuplicatePolicy;
+    if (result.duplicates.length === 0 || policy === "receiver") {
+      // Fără duplicate sau decide receiver-ul - se trimite tot
+      await startTransfer(receiver, expandedPaths, name);
+    } else if (policy === "ask") {
       // Show duplicate dialog
       pendingDuplicateCheck = { receiver, paths: expandedPaths, result };
       showDuplicateModal(expandedPaths, result);
     } else {
-      // No duplicates, proceed with transfer
-      await startTransfer(receiver, expandedPaths, name);
+      // Trimitere nesupravegheată: aceeași acțiune pentru toate duplicatele, fără fereastră
+      const selection = selectionWithPolicy(expandedPaths, result, policy);
+      if (selection.files.length === 0) {
+        showToast("Toate fișierele există deja pe receiver", "success");
+        return;
+      }
+      await startTransferWithSelection(receiver, expandedPaths, name, selection);
     }
   } catch (e) {
     console.error("Error:", e);
     // Reset toate state-urile la eroare
     isTransferring = false;
     pendingDuplicateCheck = null;
-    currentTransfer = null;
     progressSection.classList.remove("active");
     enableDropZones();
+
+    // Receiver nou: asociere cu PIN-ul afișat pe el, apoi reluăm trimiterea
+    if (String(e).includes("pairing_required")) {
+      if (await pairWithReceiver(receiver, name)) {
+        await sendFilesToReceiver(receiver, paths);
+      }
+      return;
+    }
+    // Receiver vechi, fără criptare: fișierele pleacă în clar doar cu acordul explicit pentru el
+    if (String(e).includes("legacy_receiver")) {
+      if (await allowLegacyReceiver(receiver)) {
+        await sendFilesToReceiver(receiver, paths);
+      }
+      return;
+    }
     showToast(`Eroare: ${e}`, "error");
   }
 }
 
+async function allowLegacyReceiver(receiver: DiscoveredService): Promise<boolean> {
+  const allowed = confirm(
+    `${receiver.name} folosește o versiune veche, fără criptare.\n\n` +
+    "Fișierele vor circula necriptat prin rețea. Permiți transferul necriptat către acest receiver?"
+  );
+  if (allowed) {
+    await invoke("set_legacy_receiver", { targetName: receiver.name, allowed: true });
+  }
+  return allowed;
+}
+
+function pairWithReceiver(receiver: DiscoveredService, name: string): Promise<boolean> {
+  const modal = document.getElementById("pairing-modal")!;
+  const input = document.getElementById("pairing-pin") as HTMLInputElement;
+  const btnConfirm = document.getElementById("pairing-confirm")!;
+  const btnCancel = document.getElementById("pairing-cancel")!;
+
+  document.getElementById("pairing-target")!.textContent = receiver.name;
+  input.value = "";
+  modal.style.display = "flex";
+  input.focus();
+
+  return new Promise((resolve) => {
+    const close = (paired: boolean) => {
+      modal.style.display = "none";
+      btnConfirm.onclick = null;
+      btnCancel.onclick = null;
+      resolve(paired);
+    };
+
+    btnConfirm.onclick = async () => {
+      try {
+        const receiverName = await invoke<string>("pair_with_receiver", {
+          targetHost: receiver.host,
+          targetPort: receiver.port,
+          photographerName: name,
+          pin: input.value.trim(),
+        });
+        showToast(`Asociat cu ${receiverName}`, "success");
+        close(true);
+      } catch (err) {
+        showToast(`${err}`, "error");
+        close(false);
+      }
+    };
+    btnCancel.onclick = () => close(false);
+  });
+}
+
+function fileNameOf(path: string): string {
+  return path.split("/").pop() || path.split("\\").pop() || path;
+}
+
+function selectionWithPolicy(paths: string[], result: DuplicateCheckResult, action: DuplicateAction): Selection {
+  const duplicateNames = new Set(result.duplicates.map(d => d.file_name));
+  const actions: Record<string, DuplicateAction> = {};
+  duplicateNames.forEach(name => actions[name] = action);
+  const files = paths.map(fileNameOf).filter(name => actions[name] !== "skip");
+  return { files, actions };
+}
+
 function showDuplicateModal(allPaths: string[], result: DuplicateCheckResult) {
   const modal = document.getElementById("duplicate-modal")!;
   const list = document.getElementById("duplicate-list")!;
@@ -649,7 +764,10 @@ function showDuplicateModal(allPaths: string[], result: DuplicateCheckResult) {
   // Set info text
   if (result.resume_folder) {
     title.textContent = "Transfer întrerupt detectat";
-    info.textContent = `Se reia transferul în folderul existent. ${result.duplicates.length} fișiere există deja.`;
+    const partialText = result.partial_files.length > 0
+      ? ` ${result.partial_files.length} fișiere incomplete vor continua de unde au rămas.`
+      : "";
+    info.textContent = `Se reia transferul în folderul existent. ${result.duplicates.length} fișiere există deja.${partialText}`;
   } else {
     title.textContent = "Fișiere duplicate detectate";
     info.textContent = `${result.duplicates.length} din ${allPaths.length} fișiere există deja pe receiver.`;
@@ -660,7 +778,7 @@ function showDuplicateModal(allPaths: string[], result: DuplicateCheckResult) {
   const duplicateNames = new Set(result.duplicates.map(d => d.file_name));
 
   allPaths.forEach((path) => {
-    const fileName = path.split("/").pop() || path.split("\\").pop() || path;
+    const fileName = fileNameOf(path);
     const duplicate = result.duplicates.find(d => d.file_name === fileName);
     const isDuplicate = duplicateNames.has(fileName);
 
@@ -685,7 +803,10 @@ function showDuplicateModal(allPaths: string[], result: DuplicateCheckResult) {
 
     if (duplicate) {
       if (duplicate.same_checksum) {
-        itemDetails.textContent = `Identic cu cel existent în ${duplicate.existing_path.split("/").pop() || "folder"}`;
+        const folder = duplicate.existing_path.split("/").pop() || "folder";
+        itemDetails.textContent = duplicate.existing_name
+          ? `Identic cu ${duplicate.existing_name} din ${folder}`
+          : `Identic cu cel existent în ${folder}`;
       } else {
         const sizeDiff = duplicate.new_size - duplicate.existing_size;
         const sizeDiffStr = sizeDiff > 0 ? `+${formatSize(sizeDiff)}` : formatSize(sizeDiff);
@@ -704,6 +825,15 @@ function showDuplicateModal(allPaths: string[], result: DuplicateCheckResult) {
 
     item.appendChild(checkbox);
     item.appendChild(itemInfo);
+    if (isDuplicate) {
+      // Duplicatul bifat: suprascrie sau păstrează ambele (nebifat = sărit)
+      const action = document.createElement("select");
+      action.className = "duplicate-item-action";
+      action.dataset.fileName = fileName;
+      action.add(new Option("Suprascrie", "overwrite"));
+      action.add(new Option("Păstrează ambele", "keep_both"));
+      item.appendChild(action);
+    }
     item.appendChild(badge);
     list.appendChild(item);
   });
@@ -762,18 +892,11 @@ function setupDuplicateModal() {
   btnConfirm.addEventListener("click", async () => {
     if (!pendingDuplicateCheck) return;
 
-    const checkboxes = document.querySelectorAll("#duplicate-list input[type=checkbox]:checked") as NodeListOf<HTMLInputElement>;
-    const selectedPaths: string[] = [];
-    const selectedNames: string[] = [];
-
-    checkboxes.forEach(cb => {
-      if (cb.dataset.path) selectedPaths.push(cb.dataset.path);
-      if (cb.dataset.fileName) selectedNames.push(cb.dataset.fileName);
-    });
+    const selection = modalSelection(true);
 
     modal.style.display = "none";
 
-    if (selectedPaths.length === 0) {
+    if (selection.files.length === 0) {
       showToast("Nu ai selectat niciun fișier", "error");
       pendingDuplicateCheck = null;
       return;
@@ -783,7 +906,7 @@ function setupDuplicateModal() {
     const name = photographerNameInput.value.trim();
     pendingDuplicateCheck = null;
 
-    await startTransferWithSelection(receiver, paths, name, selectedNames);
+    await startTransferWithSelection(receiver, paths, name, selection);
   });
 
   // Buton "Trimite TOATE" - trimite toate fișierele, inclusiv duplicate
@@ -796,26 +919,32 @@ function setupDuplicateModal() {
     const name = photographerNameInput.value.trim();
     pendingDuplicateCheck = null;
 
-    // Trimite fără filtrare - folosim startTransfer normal care trimite TOATE
-    await startTransfer(receiver, paths, name);
+    // Trimite fără filtrare, cu acțiunea aleasă pentru fiecare duplicat
+    await startTransferWithSelection(receiver, paths, name, modalSelection(false));
   });
 }
 
+// Fișierele de trimis din fereastra de duplicate și acțiunea aleasă pentru fiecare duplicat
+function modalSelection(onlyChecked: boolean): Selection {
+  const checkboxes = document.querySelectorAll("#duplicate-list input[type=checkbox]") as NodeListOf<HTMLInputElement>;
+  const files: string[] = [];
+  checkboxes.forEach(cb => {
+    if (cb.dataset.fileName && (cb.checked || !onlyChecked)) files.push(cb.dataset.fileName);
+  });
+
+  const actions: Record<string, DuplicateAction> = {};
+  const selects = document.querySelectorAll("#duplicate-list select.duplicate-item-action") as NodeListOf<HTMLSelectElement>;
+  selects.forEach(select => {
+    const name = select.dataset.fileName;
+    if (name && files.includes(name)) actions[name] = select.value as DuplicateAction;
+  });
+  return { files, actions };
+}
+
 async function startTransfer(receiver: DiscoveredService, paths: string[], name: string) {
   isTransferring = true;
   disableDropZones();
 
-  // Calculate total size
-  const totalSize = await calculateTotalSize(paths);
-
-  // Save current transfer info for history
-  currentTransfer = {
-    targetName: receiver.name,
-    targetRole: receiver.role,
-    fileCount: paths.length,
-    totalSize,
-  };
-
   // Show progress
   progressSection.classList.add("active");
   progressBar.style.width = "0%";
@@ -828,57 +957,37 @@ async function startTransfer(receiver: DiscoveredService, paths: string[], name:
     await invoke("send_files_to_host", {
       targetHost: receiver.host,
       targetPort: receiver.port,
+      targetName: receiver.name,
+      targetRole: receiver.role,
       photographerName: name,
       filePaths: paths,
     });
   } catch (e) {
     console.error("Transfer error:", e);
     showToast(`Eroare transfer: ${e}`, "error");
-    // Save error to history
-    if (currentTransfer) {
-      await saveToHistory(
-        currentTransfer.targetName,
-        currentTransfer.targetRole,
-        currentTransfer.fileCount,
-        currentTransfer.totalSize,
-        "error",
-        String(e)
-      );
-      currentTransfer = null;
-    }
     isTransferring = false;
     progressSection.classList.remove("active");
     enableDropZones();
+  } finally {
+    // Backend-ul a salvat trimiterea în istoric (reușită, eșuată sau anulată), cu viteza măsurată
+    loadHistory();
   }
 }
 
-async function calculateTotalSize(_paths: string[]): Promise<number> {
-  // We'll estimate based on paths - actual size comes from transfer progress
-  return 0; // Will be updated from transfer progress
-}
-
 async function startTransferWithSelection(
   receiver: DiscoveredService,
   paths: string[],
   name: string,
-  filesToSend: string[]
+  selection: Selection
 ) {
   isTransferring = true;
   disableDropZones();
 
-  // Save current transfer info for history
-  currentTransfer = {
-    targetName: receiver.name,
-    targetRole: receiver.role,
-    fileCount: filesToSend.length,
-    totalSize: 0, // Will be updated from progress
-  };
-
   // Show progress
   progressSection.classList.add("active");
   progressBar.style.width = "0%";
   progressTitle.textContent = `Se trimite la ${receiver.name}...`;
-  progressStats.textContent = `0 / ${filesToSend.length} fișiere`;
+  progressStats.textContent = `0 / ${selection.files.length} fișiere`;
   progressFile.textContent = "Se conectează...";
   progressSpeed.textContent = "";
 
@@ -886,28 +995,21 @@ async function startTransferWithSelection(
     await invoke("send_files_with_selection", {
       targetHost: receiver.host,
       targetPort: receiver.port,
+      targetName: receiver.name,
+      targetRole: receiver.role,
       photographerName: name,
       filePaths: paths,
-      filesToSend: filesToSend,
+      selection,
     });
   } catch (e) {
     console.error("Transfer error:", e);
     showToast(`Eroare transfer: ${e}`, "error");
-    // Save error to history
-    if (currentTransfer) {
-      await saveToHistory(
-        currentTransfer.targetName,
-        currentTransfer.targetRole,
-        currentTransfer.fileCount,
-        currentTransfer.totalSize,
-        "error",
-        String(e)
-      );
-      currentTransfer = null;
-    }
     isTransferring = false;
     progressSection.classList.remove("active");
     enableDropZones();
+  } finally {
+    // Backend-ul a salvat trimiterea în istoric (reușită, eșuată sau anulată), cu viteza măsurată
+    loadHistory();
   }
 }
 
@@ -1020,7 +1122,6 @@ function setupRestart() {
       // Reset frontend state
       isTransferring = false;
       pendingDuplicateCheck = null;
-      currentTransfer = null;
       pendingFiles = [];
 
       // Hide UI elements
@@ -1154,26 +1255,3 @@ async function loadHistory() {
     console.error("Error loading history:", e);
   }
 }
-
-async function saveToHistory(
-  targetName: string,
-  targetRole: string,
-  fileCount: number,
-  totalSize: number,
-  status: "success" | "error" | "cancelled",
-  errorMessage?: string
-) {
-  try {
-    await invoke("add_to_send_history", {
-      targetName,
-      targetRole,
-      fileCount,
-      totalSize,
-      status,
-      errorMessage: errorMessage || null,
-    });
-    loadHistory();
-  } catch (e) {
-    console.error("Error saving to history:", e);
-  }
-}
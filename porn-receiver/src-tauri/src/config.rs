@@ -1,7 +1,9 @@
-use chrono::{DateTime, Utc};
+use crate::folder_template::{Field, FolderTemplate, TemplateValues};
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
+use transfer_protocol::DuplicateAction;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReceiverConfig {
@@ -17,12 +19,116 @@ pub struct ReceiverConfig {
     pub day_counters: HashMap<String, u32>, // Contoare separate pentru fiecare zi
     #[serde(default = "default_port")]
     pub port: u16,              // Portul TCP (diferit pentru tagger și editor pe același Mac)
+    #[serde(default)]
+    pub allow_legacy_senders: bool, // Acceptă transferuri necriptate de la aplicațiile Mac/Windows vechi
+    #[serde(default)]
+    pub legacy_receivers: Vec<String>, // Editori vechi, fără criptare, către care s-a permis trimiterea în clar
+    #[serde(default)]
+    pub event_passphrase: String, // Parola evenimentului cerută senderilor (gol = fără parolă)
+    #[serde(default)]
+    pub allowed_senders: Vec<String>, // Nume sau amprente de cheie; dacă lista nu e goală, doar aceștia
+    #[serde(default)]
+    pub blocked_senders: Vec<String>, // Nume sau amprente de cheie refuzate întotdeauna
+    #[serde(default = "default_disk_reserve_mb")]
+    pub disk_reserve_mb: u64, // Spațiu lăsat liber pe disc; transferurile care nu încap sunt refuzate
+    #[serde(default)]
+    pub backup_paths: Vec<String>, // Volume pe care se scrie o copie a fiecărui fișier (aceeași structură)
+    #[serde(default)]
+    pub backup_required: bool, // true = un backup eșuat oprește transferul, false = transferul continuă
+    #[serde(default)]
+    pub duplicate_policy: DuplicateAction, // Pentru duplicatele fără acțiune aleasă de sender
+    #[serde(default)]
+    pub event_days: Vec<EventDay>, // Programul evenimentului; gol = ziua se schimbă doar manual
+    #[serde(default = "default_day_cutoff_hour")]
+    pub day_cutoff_hour: u32, // Până la această oră, transferurile intră încă în ziua precedentă
+    #[serde(default)]
+    pub day_override: Option<DayOverride>, // Ziua aleasă manual peste program
+    #[serde(default)]
+    pub profile_id: String, // Profilul de eveniment activ ("" = profilul inițial, cu istoricul vechi)
+    #[serde(default = "default_profile_name")]
+    pub profile_name: String,
+    #[serde(default)]
+    pub profiles: Vec<EventProfile>, // Celelalte profiluri, cu setările de la ultima comutare
+}
+
+/// Setările care țin de un eveniment. Profilul activ stă direct în `ReceiverConfig`
+/// (serverul le citește de acolo), celelalte în `ReceiverConfig::profiles`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventProfile {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub archived: bool, // Eveniment încheiat - nu mai apare la comutare
+    pub base_path: String,
+    pub folder_template: String,
+    pub folder_counter: u32,
+    pub use_day_folders: bool,
+    pub current_day: String,
+    pub reset_numbering_daily: bool,
+    #[serde(default)]
+    pub day_counters: HashMap<String, u32>,
+    #[serde(default)]
+    pub event_days: Vec<EventDay>,
+    #[serde(default = "default_day_cutoff_hour")]
+    pub day_cutoff_hour: u32,
+    #[serde(default)]
+    pub day_override: Option<DayOverride>,
+}
+
+/// Profil afișat în UI
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileInfo {
+    pub id: String,
+    pub name: String,
+    pub base_path: String,
+    pub current_day: String,
+    pub archived: bool,
+    pub active: bool,
+}
+
+impl EventProfile {
+    fn info(&self, active: bool) -> ProfileInfo {
+        ProfileInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            base_path: self.base_path.clone(),
+            current_day: self.current_day.clone(),
+            archived: self.archived,
+            active,
+        }
+    }
+}
+
+/// O zi din programul evenimentului
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventDay {
+    pub date: NaiveDate,
+    pub label: String, // Numele folderului zilei ("DAY 1")
+}
+
+/// Ziua aleasă manual; programul o înlocuiește la următoarea dată calendaristică
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayOverride {
+    pub label: String,
+    pub date: NaiveDate, // Data evenimentului (după ora de cutoff) la care a fost aleasă
 }
 
 fn default_port() -> u16 {
     45678
 }
 
+fn default_disk_reserve_mb() -> u64 {
+    1024
+}
+
+fn default_day_cutoff_hour() -> u32 {
+    4
+}
+
+fn default_profile_name() -> String {
+    "Eveniment".to_string()
+}
+
 impl Default for ReceiverConfig {
     fn default() -> Self {
         let default_path = dirs::home_dir()
@@ -42,6 +148,21 @@ impl Default for ReceiverConfig {
             reset_numbering_daily: true,
             day_counters: HashMap::new(),
             port: 45678,
+            allow_legacy_senders: false,
+            legacy_receivers: Vec::new(),
+            event_passphrase: String::new(),
+            allowed_senders: Vec::new(),
+            blocked_senders: Vec::new(),
+            disk_reserve_mb: default_disk_reserve_mb(),
+            backup_paths: Vec::new(),
+            backup_required: false,
+            duplicate_policy: DuplicateAction::Overwrite,
+            event_days: Vec::new(),
+            day_cutoff_hour: default_day_cutoff_hour(),
+            day_override: None,
+            profile_id: String::new(),
+            profile_name: default_profile_name(),
+            profiles: Vec::new(),
         }
     }
 }
@@ -69,74 +190,313 @@ impl ReceiverConfig {
         std::fs::write(&path, content).map_err(|e| e.to_string())
     }
 
-    pub fn generate_folder_name(&mut self, photographer: &str) -> String {
-        let counter = self.get_current_counter();
-        let name = self.folder_template
-            .replace("{name}", photographer)
-            .replace("{num:02d}", &format!("{:02}", counter))
-            .replace("{num:03d}", &format!("{:03}", counter))
-            .replace("{num}", &counter.to_string())
-            .replace("{date}", &chrono::Local::now().format("%Y-%m-%d").to_string())
-            .replace("{time}", &chrono::Local::now().format("%H-%M").to_string());
+    /// Obține contorul pentru ziua dată (sau contorul global dacă nu se folosesc zile)
+    fn get_counter(&self, day: &str) -> u32 {
+        if self.uses_day_folders() {
+            *self.day_counters.get(day).unwrap_or(&1)
+        } else {
+            self.folder_counter
+        }
+    }
 
-        self.increment_counter();
-        let _ = self.save();
+    /// Setează contorul pentru ziua dată
+    fn set_counter(&mut self, day: &str, counter: u32) {
+        if self.uses_day_folders() {
+            self.day_counters.insert(day.to_string(), counter);
+        } else {
+            self.folder_counter = counter;
+        }
+    }
 
-        name
+    fn uses_day_folders(&self) -> bool {
+        self.use_day_folders && self.role == "tagger"
     }
 
-    /// Obține contorul pentru ziua curentă (sau contorul global dacă nu se folosesc zile)
-    fn get_current_counter(&self) -> u32 {
-        if self.use_day_folders && self.role == "tagger" {
-            *self.day_counters.get(&self.current_day).unwrap_or(&1)
+    /// Folderul în care se creează transferurile: categoria sursei (editori), ziua curentă
+    /// (taggeri cu zile) sau direct folderul de bază
+    pub fn transfer_base(&self, source_category: &str) -> PathBuf {
+        let base = PathBuf::from(&self.base_path);
+        if self.role == "editor" {
+            base.join(source_category)
+        } else if self.uses_day_folders() {
+            base.join(&self.current_day)
         } else {
-            self.folder_counter
+            base
         }
     }
 
-    /// Incrementează contorul pentru ziua curentă
-    fn increment_counter(&mut self) {
-        if self.use_day_folders && self.role == "tagger" {
-            let counter = self.day_counters.entry(self.current_day.clone()).or_insert(1);
-            *counter += 1;
-        } else {
-            self.folder_counter += 1;
+    /// Primul nume liber pe disc, începând de la contorul curent. Returnează și contorul folosit.
+    fn next_free_folder_name(&self, transfer: &FolderTransfer, base_check_path: &std::path::Path) -> (String, u32) {
+        let template = FolderTemplate::from_config(&self.folder_template);
+        let mut counter = self.get_counter(transfer.day);
+        loop {
+            let values = TemplateValues {
+                photographer: transfer.photographer,
+                counter,
+                day: if self.uses_day_folders() { transfer.day } else { "" },
+                role: &self.role,
+                source: transfer.source,
+                camera: transfer.camera.clone(),
+                file_count: transfer.file_count,
+                preview: transfer.file_count.is_none(),
+            };
+            let name = template.render(&values);
+            if !base_check_path.join(&name).exists() {
+                return (name, counter);
+            }
+
+            // Un template fără {num} dă mereu același nume - contorul nu-l poate face unic,
+            // așa că se adaugă un sufix, ca la transferurile între receivere
+            if !template.uses(Field::Num) {
+                let mut suffix = 2;
+                let mut free = format!("{}_{}", name, suffix);
+                while base_check_path.join(&free).exists() {
+                    suffix += 1;
+                    free = format!("{}_{}", name, suffix);
+                }
+                return (free, counter);
+            }
+            counter += 1;
         }
     }
 
     /// Generează un nume de folder unic, verificând pe disc dacă există deja
     /// Această funcție trebuie apelată cu lock pe config
     /// Pentru taggeri cu zile: folosește contorul specific zilei curente
-    pub fn generate_unique_folder_name(&mut self, photographer: &str, base_check_path: &std::path::Path) -> String {
-        loop {
-            let counter = self.get_current_counter();
-            let name = self.folder_template
-                .replace("{name}", photographer)
-                .replace("{num:02d}", &format!("{:02}", counter))
-                .replace("{num:03d}", &format!("{:03}", counter))
-                .replace("{num}", &counter.to_string())
-                .replace("{date}", &chrono::Local::now().format("%Y-%m-%d").to_string())
-                .replace("{time}", &chrono::Local::now().format("%H-%M").to_string());
-
-            let full_path = base_check_path.join(&name);
-
-            self.increment_counter();
-
-            // Verifică dacă folderul există deja pe disc
-            if !full_path.exists() {
-                let _ = self.save();
-                return name;
+    pub fn generate_unique_folder_name(
+        &mut self,
+        transfer: &FolderTransfer,
+        base_check_path: &std::path::Path,
+    ) -> String {
+        let (name, counter) = self.next_free_folder_name(transfer, base_check_path);
+        self.set_counter(transfer.day, counter + 1);
+        let _ = self.save();
+        name
+    }
+
+    /// Ca `generate_unique_folder_name`, dar în profilul din `session` (config-ul de la începutul
+    /// transferului), chiar dacă între timp s-a comutat pe alt eveniment. Contorul se actualizează
+    /// în acel profil; numărul lui curent se citește de aici, nu din `session`, ca sesiunile simultane
+    /// să nu primească același număr.
+    pub fn generate_session_folder_name(
+        &mut self,
+        session: &ReceiverConfig,
+        transfer: &FolderTransfer,
+        base_check_path: &std::path::Path,
+    ) -> String {
+        if session.profile_id == self.profile_id {
+            return self.generate_unique_folder_name(transfer, base_check_path);
+        }
+
+        let mut profile_config = session.clone();
+        let index = self.profile_index(&session.profile_id).ok();
+        if let Some(index) = index {
+            profile_config.apply_profile(self.profiles[index].clone());
+        }
+        let (name, counter) = profile_config.next_free_folder_name(transfer, base_check_path);
+        if let Some(index) = index {
+            profile_config.set_counter(transfer.day, counter + 1);
+            let archived = self.profiles[index].archived;
+            self.profiles[index] = EventProfile { archived, ..profile_config.active_profile() };
+            let _ = self.save();
+        }
+        name
+    }
+
+    /// Numele pe care l-ar primi următorul transfer, fără să consume contorul
+    pub fn preview_folder_name(&self, transfer: &FolderTransfer) -> String {
+        let base_check_path = self.transfer_base(transfer.source);
+        self.next_free_folder_name(transfer, &base_check_path).0
+    }
+
+    /// Aplică programul evenimentului. Returnează ziua nouă dacă s-a schimbat.
+    /// Datele din afara programului păstrează ziua curentă.
+    pub fn refresh_day(&mut self) -> Option<String> {
+        if self.event_days.is_empty() {
+            return None;
+        }
+        let date = self.event_date();
+        if self.day_override.as_ref().is_some_and(|o| o.date != date) {
+            self.day_override = None;
+        }
+
+        let day = match self.day_override {
+            Some(ref day_override) => day_override.label.clone(),
+            None => self.event_days.iter().find(|d| d.date == date)?.label.clone(),
+        };
+        if day == self.current_day {
+            return None;
+        }
+        self.current_day = day.clone();
+        let _ = self.save();
+        Some(day)
+    }
+
+    /// Alegere manuală a zilei. Cu program, e valabilă până la următoarea dată din program;
+    /// `None` revine la program.
+    pub fn set_day(&mut self, day: Option<String>) -> Result<String, String> {
+        match day {
+            Some(day) => {
+                let day = day.trim().to_string();
+                if day.is_empty() {
+                    return Err("Numele zilei nu poate fi gol".to_string());
+                }
+                if !self.event_days.is_empty() {
+                    self.day_override = Some(DayOverride { label: day.clone(), date: self.event_date() });
+                }
+                self.current_day = day;
+            }
+            None => {
+                self.day_override = None;
+                self.refresh_day();
             }
+        }
+        self.save()?;
+        Ok(self.current_day.clone())
+    }
+
+    // Data evenimentului: înainte de ora de cutoff, noaptea aparține zilei precedente
+    fn event_date(&self) -> NaiveDate {
+        let now = Local::now().naive_local();
+        (now - chrono::Duration::hours(i64::from(self.day_cutoff_hour.min(23)))).date()
+    }
+
+    // Setările evenimentului activ, pentru a fi păstrate la comutare
+    fn active_profile(&self) -> EventProfile {
+        EventProfile {
+            id: self.profile_id.clone(),
+            name: self.profile_name.clone(),
+            archived: false,
+            base_path: self.base_path.clone(),
+            folder_template: self.folder_template.clone(),
+            folder_counter: self.folder_counter,
+            use_day_folders: self.use_day_folders,
+            current_day: self.current_day.clone(),
+            reset_numbering_daily: self.reset_numbering_daily,
+            day_counters: self.day_counters.clone(),
+            event_days: self.event_days.clone(),
+            day_cutoff_hour: self.day_cutoff_hour,
+            day_override: self.day_override.clone(),
+        }
+    }
+
+    fn apply_profile(&mut self, profile: EventProfile) {
+        self.profile_id = profile.id;
+        self.profile_name = profile.name;
+        self.base_path = profile.base_path;
+        self.folder_template = profile.folder_template;
+        self.folder_counter = profile.folder_counter;
+        self.use_day_folders = profile.use_day_folders;
+        self.current_day = profile.current_day;
+        self.reset_numbering_daily = profile.reset_numbering_daily;
+        self.day_counters = profile.day_counters;
+        self.event_days = profile.event_days;
+        self.day_cutoff_hour = profile.day_cutoff_hour;
+        self.day_override = profile.day_override;
+    }
+
+    /// Toate profilurile, cel activ primul
+    pub fn list_profiles(&self) -> Vec<ProfileInfo> {
+        std::iter::once(self.active_profile().info(true))
+            .chain(self.profiles.iter().map(|p| p.info(false)))
+            .collect()
+    }
+
+    /// Profil nou cu contoarele de la zero. Template-ul și organizarea pe zile se preiau din profilul activ.
+    pub fn create_profile(&mut self, name: &str, base_path: &str) -> Result<ProfileInfo, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Numele evenimentului nu poate fi gol".to_string());
+        }
+        if base_path.trim().is_empty() {
+            return Err("Alegeți folderul evenimentului".to_string());
+        }
+        if self.list_profiles().iter().any(|p| p.name.eq_ignore_ascii_case(name)) {
+            return Err(format!("Există deja un eveniment numit {:?}", name));
+        }
+
+        let profile = EventProfile {
+            id: self.unique_profile_id(name),
+            name: name.to_string(),
+            archived: false,
+            base_path: base_path.trim().to_string(),
+            folder_template: self.folder_template.clone(),
+            folder_counter: 1,
+            use_day_folders: self.use_day_folders,
+            current_day: "DAY 1".to_string(),
+            reset_numbering_daily: self.reset_numbering_daily,
+            day_counters: HashMap::new(),
+            event_days: Vec::new(),
+            day_cutoff_hour: self.day_cutoff_hour,
+            day_override: None,
+        };
+        let info = profile.info(false);
+        self.profiles.push(profile);
+        self.save()?;
+        Ok(info)
+    }
+
+    /// Activează profilul `id`; setările profilului curent se păstrează în `profiles`
+    pub fn switch_profile(&mut self, id: &str) -> Result<(), String> {
+        if id == self.profile_id {
+            return Ok(());
+        }
+        let index = self.profile_index(id)?;
+        if self.profiles[index].archived {
+            return Err(format!("Evenimentul {:?} e arhivat - dezarhivați-l întâi", self.profiles[index].name));
+        }
+
+        let profile = self.profiles.remove(index);
+        let previous = self.active_profile();
+        self.profiles.push(previous);
+        self.apply_profile(profile);
+        self.refresh_day();
+        self.save()
+    }
+
+    pub fn archive_profile(&mut self, id: &str, archived: bool) -> Result<(), String> {
+        if id == self.profile_id {
+            return Err("Evenimentul activ nu poate fi arhivat".to_string());
+        }
+        let index = self.profile_index(id)?;
+        self.profiles[index].archived = archived;
+        self.save()
+    }
+
+    fn profile_index(&self, id: &str) -> Result<usize, String> {
+        self.profiles
+            .iter()
+            .position(|p| p.id == id)
+            .ok_or_else(|| format!("Evenimentul {:?} nu există", id))
+    }
 
-            // Dacă există, continuă să incrementeze până găsim un număr liber
-            // (bucla va genera automat următorul număr)
+    // Id-ul dă și numele fișierului de istoric, deci doar litere mici, cifre și "-"
+    fn unique_profile_id(&self, name: &str) -> String {
+        let slug: String = name
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect::<String>()
+            .split('-')
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("-");
+        let slug = if slug.is_empty() { "eveniment".to_string() } else { slug };
+
+        let taken = |id: &str| id == self.profile_id || self.profiles.iter().any(|p| p.id == id);
+        let mut id = slug.clone();
+        let mut suffix = 1;
+        while taken(&id) {
+            suffix += 1;
+            id = format!("{}-{}", slug, suffix);
         }
+        id
     }
 
     pub fn get_full_path(&self, folder_name: &str) -> PathBuf {
         let base = PathBuf::from(&self.base_path);
 
-        if self.use_day_folders && self.role == "tagger" {
+        if self.uses_day_folders() {
             base.join(&self.current_day).join(folder_name)
         } else {
             base.join(folder_name)
@@ -144,6 +504,25 @@ impl ReceiverConfig {
     }
 }
 
+/// Transferul pentru care se generează numele folderului
+pub struct FolderTransfer<'a> {
+    pub photographer: &'a str,
+    pub day: &'a str, // Ziua în care a început transferul (ziua se poate schimba între timp)
+    pub source: &'a str, // Vezi `source_category`
+    pub camera: Option<String>,
+    pub file_count: Option<usize>, // None = transferul nu a început încă (previzualizare)
+}
+
+/// Categoria senderului după rol: fotografii sau oricine altcineva sunt "Fotograf".
+/// Editorii își organizează transferurile în subfoldere cu aceste nume.
+pub fn source_category(sender_role: Option<&str>) -> &'static str {
+    match sender_role {
+        Some("tagger") => "Tagger",
+        Some("editor") => "Editor",
+        _ => "Fotograf",
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransferRecord {
     #[serde(default)]
@@ -158,6 +537,22 @@ pub struct TransferRecord {
     pub status: TransferStatus,
     #[serde(default)]
     pub source_role: Option<String>, // "tagger", "editor", or None (fotograf)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backups: Vec<BackupCopy>, // Starea copiei de pe fiecare volum de backup
+    #[serde(default)]
+    pub speed_mbps: Option<f64>, // Viteza medie la salvare (lipsește în istoricul vechi)
+}
+
+/// Copia unui transfer pe un volum de backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupCopy {
+    pub root: String,   // Volumul de backup din config
+    pub folder: String, // Folderul copiei
+    pub file_count: usize,
+    pub total_size: u64,
+    pub status: TransferStatus, // Complete, Partial (lipsesc fișiere) sau Error
+    #[serde(default)]
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
@@ -168,14 +563,18 @@ pub enum TransferStatus {
     Error,
 }
 
-fn history_path() -> PathBuf {
-    dirs::home_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join(".porn_transfer_history.json")
+// Istoricul JSON al versiunilor vechi (un fișier per profil), importat o singură dată în `HistoryStore`
+pub fn history_path(profile_id: &str) -> PathBuf {
+    let file = if profile_id.is_empty() {
+        ".porn_transfer_history.json".to_string()
+    } else {
+        format!(".porn_transfer_history_{}.json", profile_id)
+    };
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")).join(file)
 }
 
-pub fn load_history() -> Result<Vec<TransferRecord>, String> {
-    let path = history_path();
+pub fn load_history(profile_id: &str) -> Result<Vec<TransferRecord>, String> {
+    let path = history_path(profile_id);
     if !path.exists() {
         return Ok(Vec::new());
     }
@@ -184,22 +583,6 @@ pub fn load_history() -> Result<Vec<TransferRecord>, String> {
     serde_json::from_str(&content).map_err(|e| e.to_string())
 }
 
-pub fn save_history(history: &[TransferRecord]) -> Result<(), String> {
-    let path = history_path();
-    // Keep only last 500 records
-    let to_save: Vec<_> = history.iter().rev().take(500).cloned().collect();
-    let content = serde_json::to_string_pretty(&to_save).map_err(|e| e.to_string())?;
-    std::fs::write(&path, content).map_err(|e| e.to_string())
-}
-
-pub fn clear_history() -> Result<(), String> {
-    let path = history_path();
-    if path.exists() {
-        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
-    }
-    Ok(())
-}
-
 // ========== ISTORIC TRIMITERI (SENT HISTORY) ==========
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -211,7 +594,7 @@ pub struct SentRecord {
     pub folder_name: Option<String>, // Numele folderului trimis (dacă e folder)
 }
 
-fn sent_history_path() -> PathBuf {
+pub fn sent_history_path() -> PathBuf {
     dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join(".porn_transfer_sent_history.json")
@@ -227,16 +610,54 @@ pub fn load_sent_history() -> Result<Vec<SentRecord>, String> {
     serde_json::from_str(&content).map_err(|e| e.to_string())
 }
 
-pub fn save_sent_history(history: &[SentRecord]) -> Result<(), String> {
-    let path = sent_history_path();
-    // Keep only last 500 records
-    let to_save: Vec<_> = history.iter().rev().take(500).cloned().collect();
-    let content = serde_json::to_string_pretty(&to_save).map_err(|e| e.to_string())?;
-    std::fs::write(&path, content).map_err(|e| e.to_string())
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub fn add_sent_record(record: SentRecord) -> Result<(), String> {
-    let mut history = load_sent_history().unwrap_or_default();
-    history.push(record);
-    save_sent_history(&history)
+    fn temp_base(name: &str, existing: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ptxf-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for folder in existing {
+            std::fs::create_dir_all(dir.join(folder)).unwrap();
+        }
+        dir
+    }
+
+    fn transfer() -> FolderTransfer<'static> {
+        FolderTransfer { photographer: "Ana", day: "", source: "Fotograf", camera: None, file_count: Some(3) }
+    }
+
+    #[test]
+    fn counter_skips_existing_folders() {
+        let base = temp_base("counter", &["01 - Ana", "02 - Ana"]);
+        let config = ReceiverConfig { folder_counter: 1, use_day_folders: false, ..ReceiverConfig::default() };
+        assert_eq!(config.next_free_folder_name(&transfer(), &base), ("03 - Ana".to_string(), 3));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn template_without_counter_gets_suffix() {
+        let base = temp_base("suffix", &["Ana", "Ana_2"]);
+        let config = ReceiverConfig {
+            folder_template: "{name}".to_string(),
+            folder_counter: 4,
+            use_day_folders: false,
+            ..ReceiverConfig::default()
+        };
+        assert_eq!(config.next_free_folder_name(&transfer(), &base), ("Ana_3".to_string(), 4));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn legacy_senders_refused_unless_allowed() {
+        assert!(!ReceiverConfig::default().allow_legacy_senders);
+
+        // Config-urile salvate înainte de opțiune (cu vechiul `require_encryption`) refuză și ele
+        let mut saved = serde_json::to_value(ReceiverConfig::default()).unwrap();
+        let fields = saved.as_object_mut().unwrap();
+        fields.remove("allow_legacy_senders");
+        fields.insert("require_encryption".to_string(), serde_json::json!(false));
+        let loaded: ReceiverConfig = serde_json::from_value(saved).unwrap();
+        assert!(!loaded.allow_legacy_senders);
+    }
 }
@@ -0,0 +1,383 @@
+use crate::files::received_files;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use transfer_protocol::{hash_prefix, DuplicateInfo, FileMetadata};
+
+// Stă în folderul de bază, lângă fișierele indexate: o linie JSON pe înregistrare, doar adăugări
+const INDEX_FILE: &str = ".porn_transfer_index.jsonl";
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    Add { hash: String, size: u64, path: String },
+    Move { from: String, to: String }, // Folder redenumit (temporar → numele final)
+}
+
+// Căile sunt relative la folderul de bază, cu "/" ca separator
+struct Entry {
+    hash: String,
+    size: u64,
+    path: String,
+}
+
+impl Entry {
+    fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+struct Index {
+    base: PathBuf,
+    entries: Vec<Entry>,
+    by_path: HashMap<String, usize>,
+    by_hash: HashMap<String, Vec<usize>>,
+    by_name: HashMap<String, Vec<usize>>, // Numele în litere mici
+}
+
+impl Index {
+    fn empty(base: &Path) -> Self {
+        Self {
+            base: base.to_path_buf(),
+            entries: Vec::new(),
+            by_path: HashMap::new(),
+            by_hash: HashMap::new(),
+            by_name: HashMap::new(),
+        }
+    }
+
+    // Liniile stricate (ex. ultima linie scrisă pe jumătate la o pană de curent) sunt ignorate
+    fn load(base: &Path) -> std::io::Result<Self> {
+        let mut index = Self::empty(base);
+        let file = std::fs::File::open(base.join(INDEX_FILE))?;
+        for line in std::io::BufReader::new(file).lines() {
+            if let Ok(record) = serde_json::from_str::<Record>(&line?) {
+                index.apply(record);
+            }
+        }
+        Ok(index)
+    }
+
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::Add { hash, size, path } => {
+                if let Some(&i) = self.by_path.get(&path) {
+                    // Fișier suprascris - conținut nou pe aceeași cale
+                    let old_hash = std::mem::replace(&mut self.entries[i].hash, hash.clone());
+                    if let Some(ids) = self.by_hash.get_mut(&old_hash) {
+                        ids.retain(|&id| id != i);
+                    }
+                    self.entries[i].size = size;
+                    self.by_hash.entry(hash).or_default().push(i);
+                    return;
+                }
+
+                let i = self.entries.len();
+                let entry = Entry { hash, size, path };
+                self.by_path.insert(entry.path.clone(), i);
+                self.by_hash.entry(entry.hash.clone()).or_default().push(i);
+                self.by_name.entry(entry.name().to_lowercase()).or_default().push(i);
+                self.entries.push(entry);
+            }
+            Record::Move { from, to } => {
+                let prefix = format!("{}/", from);
+                for (i, entry) in self.entries.iter_mut().enumerate() {
+                    let Some(rest) = entry.path.strip_prefix(&prefix) else {
+                        continue;
+                    };
+                    let moved = format!("{}/{}", to, rest);
+                    self.by_path.remove(&entry.path);
+                    self.by_path.insert(moved.clone(), i);
+                    entry.path = moved;
+                }
+            }
+        }
+    }
+
+    fn append(&mut self, record: Record) {
+        let written = serde_json::to_string(&record).map_err(std::io::Error::other).and_then(|line| {
+            let mut file =
+                std::fs::OpenOptions::new().create(true).append(true).open(self.base.join(INDEX_FILE))?;
+            writeln!(file, "{}", line)
+        });
+        if let Err(e) = written {
+            eprintln!("Nu pot scrie în indexul de duplicate: {}", e);
+        }
+        self.apply(record);
+    }
+
+    fn relative(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        let parts: Vec<_> = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+        (!parts.is_empty()).then(|| parts.join("/"))
+    }
+
+    // Fișierul indexat încă există și nu e în folderul exclus
+    fn usable(&self, entry: &Entry, exclude: Option<&Path>) -> Option<PathBuf> {
+        let path = self.base.join(&entry.path);
+        if exclude.is_some_and(|folder| path.starts_with(folder)) || !path.is_file() {
+            return None;
+        }
+        Some(path)
+    }
+
+    fn duplicate_of(&self, file: &FileMetadata, scope: &Path, exclude: Option<&Path>) -> Option<DuplicateInfo> {
+        let hash = file.checksum.to_lowercase();
+
+        // Același conținut oriunde în eveniment, chiar dacă fișierul a fost redenumit
+        let same_content = self.by_hash.get(&hash).into_iter().flatten().find_map(|&i| {
+            let entry = &self.entries[i];
+            self.usable(entry, exclude).map(|path| (entry, path))
+        });
+        if let Some((entry, path)) = same_content {
+            return Some(duplicate_info(file, entry, &path, true));
+        }
+
+        // Doar același nume: în zona căutată până acum (ziua curentă / categoria editorului)
+        let (entry, path) = self.by_name.get(&file.name.to_lowercase()).into_iter().flatten().find_map(|&i| {
+            let entry = &self.entries[i];
+            self.usable(entry, exclude).filter(|path| path.starts_with(scope)).map(|path| (entry, path))
+        })?;
+        // Fără hash de la sender (versiuni vechi) dimensiunea rămâne singura aproximare
+        let same_content = hash.is_empty() && entry.size == file.size;
+        Some(duplicate_info(file, entry, &path, same_content))
+    }
+}
+
+fn duplicate_info(file: &FileMetadata, entry: &Entry, path: &Path, same_checksum: bool) -> DuplicateInfo {
+    DuplicateInfo {
+        file_name: file.key().to_string(),
+        existing_path: path.parent().unwrap_or(path).to_string_lossy().to_string(),
+        existing_size: entry.size,
+        new_size: file.size,
+        same_checksum,
+        existing_name: (entry.name() != file.name).then(|| entry.name().to_string()),
+    }
+}
+
+/// Indexul de conținut al evenimentului: hash BLAKE3 → fișierele salvate cu acel conținut.
+/// Verificarea duplicatelor devine o căutare în memorie în loc de parcurgerea folderelor zilei,
+/// și deosebește copiile identice (chiar redenumite) de fișierele care doar au același nume.
+#[derive(Clone, Default)]
+pub struct ContentIndex {
+    inner: Arc<Mutex<Option<Index>>>,
+}
+
+impl ContentIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Indexul folderului de bază curent (se reîncarcă dacă folderul s-a schimbat din setări)
+    fn with<R>(&self, base: &Path, f: impl FnOnce(&mut Index) -> R) -> Option<R> {
+        let mut inner = self.inner.lock().ok()?;
+        if inner.as_ref().is_none_or(|index| index.base != base) {
+            let index = match Index::load(base) {
+                Ok(index) => index,
+                Err(e) => {
+                    if e.kind() == std::io::ErrorKind::NotFound {
+                        // Eveniment început înainte de index - fișierele existente se indexează în fundal
+                        self.backfill(base);
+                    } else {
+                        eprintln!("Nu pot citi indexul de duplicate: {}", e);
+                    }
+                    Index::empty(base)
+                }
+            };
+            *inner = Some(index);
+        }
+        inner.as_mut().map(f)
+    }
+
+    /// Duplicatele fișierelor din header, ignorând folderul `exclude` (folderul transferului curent).
+    /// Conținutul identic e căutat în tot evenimentul; numele doar în `scope`.
+    pub fn find_duplicates(
+        &self,
+        base: &Path,
+        scope: &Path,
+        files: &[FileMetadata],
+        exclude: Option<&Path>,
+    ) -> Vec<DuplicateInfo> {
+        self.with(base, |index| files.iter().filter_map(|f| index.duplicate_of(f, scope, exclude)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Înregistrează un fișier salvat (cu numele final)
+    pub fn add(&self, base: &Path, path: &Path, hash: &blake3::Hash, size: u64) {
+        self.with(base, |index| {
+            if let Some(path) = index.relative(path) {
+                index.append(Record::Add { hash: hash.to_hex().to_string(), size, path });
+            }
+        });
+    }
+
+    /// Folderul `from` a fost redenumit în `to`
+    pub fn moved(&self, base: &Path, from: &Path, to: &Path) {
+        self.with(base, |index| {
+            if let (Some(from), Some(to)) = (index.relative(from), index.relative(to)) {
+                index.append(Record::Move { from, to });
+            }
+        });
+    }
+
+    fn backfill(&self, base: &Path) {
+        let index = self.clone();
+        let base = base.to_path_buf();
+        std::thread::spawn(move || {
+            let files = received_files(&base);
+            println!("Indexez {} fișiere existente din {}", files.len(), base.display());
+
+            for path in files {
+                let known = index.with(&base, |i| i.relative(&path).is_some_and(|p| i.by_path.contains_key(&p)));
+                if known != Some(false) {
+                    continue; // Deja indexat de un transfer sau folderul de bază s-a schimbat
+                }
+                let hashed = std::fs::File::open(&path)
+                    .and_then(|f| Ok((f.metadata()?.len(), f)))
+                    .and_then(|(size, f)| Ok((size, hash_prefix(f, size)?.finalize())));
+                match hashed {
+                    Ok((size, hash)) => index.add(&base, &path, &hash, size),
+                    Err(e) => eprintln!("Nu pot indexa {}: {}", path.display(), e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transfer_protocol::FileAttributes;
+
+    // Folder de eveniment gol, cu indexul creat deja (fără indexare în fundal)
+    fn event_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ptxf-index-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::File::create(dir.join(INDEX_FILE)).unwrap();
+        dir
+    }
+
+    // Salvează fișierul și îl înregistrează, ca la primire
+    fn save(index: &ContentIndex, base: &Path, relative: &str, data: &[u8]) {
+        let path = base.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, data).unwrap();
+        index.add(base, &path, &blake3::hash(data), data.len() as u64);
+    }
+
+    fn incoming(name: &str, data: &[u8]) -> FileMetadata {
+        FileMetadata {
+            name: name.to_string(),
+            relative_path: String::new(),
+            size: data.len() as u64,
+            checksum: blake3::hash(data).to_hex().to_string(),
+            attributes: FileAttributes::default(),
+        }
+    }
+
+    #[test]
+    fn same_content_is_found_anywhere_even_renamed() {
+        let base = event_dir("content");
+        let index = ContentIndex::new();
+        save(&index, &base, "DAY 1/01 - Ana/IMG_1.CR3", b"raw one");
+
+        let scope = base.join("DAY 2");
+        let found = index.find_duplicates(&base, &scope, &[incoming("copy.cr3", b"raw one")], None);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].same_checksum);
+        assert_eq!(found[0].file_name, "copy.cr3");
+        assert_eq!(found[0].existing_name.as_deref(), Some("IMG_1.CR3"));
+        assert_eq!(Path::new(&found[0].existing_path), base.join("DAY 1/01 - Ana"));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn same_name_only_matches_in_scope_and_is_not_identical() {
+        let base = event_dir("name");
+        let index = ContentIndex::new();
+        save(&index, &base, "DAY 1/01 - Ana/IMG_1.CR3", b"raw one");
+
+        let other = [incoming("img_1.cr3", b"raw two")];
+        let found = index.find_duplicates(&base, &base.join("DAY 1"), &other, None);
+        assert_eq!(found.len(), 1);
+        assert!(!found[0].same_checksum);
+        assert_eq!(found[0].existing_name.as_deref(), Some("IMG_1.CR3"));
+
+        assert!(index.find_duplicates(&base, &base.join("DAY 2"), &other, None).is_empty());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn name_without_hash_compares_size() {
+        let base = event_dir("legacy");
+        let index = ContentIndex::new();
+        save(&index, &base, "01 - Ana/IMG_1.CR3", b"raw one");
+
+        let mut legacy = incoming("IMG_1.CR3", b"raw one");
+        legacy.checksum = String::new();
+        assert!(index.find_duplicates(&base, &base, &[legacy.clone()], None)[0].same_checksum);
+
+        legacy.size += 1;
+        assert!(!index.find_duplicates(&base, &base, &[legacy], None)[0].same_checksum);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn own_temp_folder_is_excluded() {
+        let base = event_dir("exclude");
+        let index = ContentIndex::new();
+        save(&index, &base, ".tmp_Ana_1/IMG_1.CR3", b"raw one");
+
+        let files = [incoming("IMG_1.CR3", b"raw one")];
+        let own = base.join(".tmp_Ana_1");
+        assert!(index.find_duplicates(&base, &base, &files, Some(&own)).is_empty());
+        assert_eq!(index.find_duplicates(&base, &base, &files, None).len(), 1);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn deleted_files_are_not_duplicates() {
+        let base = event_dir("deleted");
+        let index = ContentIndex::new();
+        save(&index, &base, "01 - Ana/IMG_1.CR3", b"raw one");
+        std::fs::remove_file(base.join("01 - Ana/IMG_1.CR3")).unwrap();
+
+        assert!(index.find_duplicates(&base, &base, &[incoming("IMG_1.CR3", b"raw one")], None).is_empty());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn records_replay_after_restart() {
+        let base = event_dir("replay");
+        let index = ContentIndex::new();
+        save(&index, &base, ".tmp_Ana_1/IMG_1.CR3", b"raw one");
+        save(&index, &base, ".tmp_Ana_1/IMG_2.CR3", b"first");
+        // Suprascris: doar conținutul nou mai e indexat pe această cale
+        save(&index, &base, ".tmp_Ana_1/IMG_2.CR3", b"second");
+        std::fs::rename(base.join(".tmp_Ana_1"), base.join("01 - Ana")).unwrap();
+        index.moved(&base, &base.join(".tmp_Ana_1"), &base.join("01 - Ana"));
+
+        // O linie scrisă pe jumătate (pană de curent) nu strică restul
+        let mut file = std::fs::OpenOptions::new().append(true).open(base.join(INDEX_FILE)).unwrap();
+        write!(file, "{{\"op\":\"add\",\"hash\":").unwrap();
+        drop(file);
+
+        let reloaded = Index::load(&base).unwrap();
+        assert_eq!(reloaded.entries.len(), 2);
+        assert!(reloaded.by_path.contains_key("01 - Ana/IMG_1.CR3"));
+        assert!(!reloaded.by_path.contains_key(".tmp_Ana_1/IMG_1.CR3"));
+
+        let restarted = ContentIndex::new();
+        let scope = base.clone();
+        let moved = restarted.find_duplicates(&base, &scope, &[incoming("a.cr3", b"raw one")], None);
+        assert_eq!(Path::new(&moved[0].existing_path), base.join("01 - Ana"));
+        let found = restarted.find_duplicates(&base, &scope, &[incoming("b.cr3", b"first")], None);
+        assert!(found.is_empty());
+        let found = restarted.find_duplicates(&base, &scope, &[incoming("b.cr3", b"second")], None);
+        assert_eq!(found[0].existing_name.as_deref(), Some("IMG_2.CR3"));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+
+/// Fișierele primite dintr-un folder, recursiv: fără cele ascunse (.DS_Store, manifestul, indexul
+/// și .part-urile neterminate, tot ascunse). Folderele temporare (.tmp_*) sunt incluse - conțin fișiere verificate.
+pub fn received_files(folder: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect(folder, &mut files);
+    files
+}
+
+fn collect(folder: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+        if path.is_dir() && (!name.starts_with('.') || name.starts_with(".tmp_")) {
+            collect(&path, files);
+        } else if path.is_file() && !name.starts_with('.') {
+            files.push(path);
+        }
+    }
+}
+
+// Face durabilă o intrare nouă/redenumită din folder. Pe Windows folderele nu pot fi
+// deschise pentru sync, iar NTFS jurnalizează oricum metadatele.
+pub fn sync_dir(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    std::fs::File::open(path)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_hidden_and_partial_files() {
+        let base = std::env::temp_dir().join(format!("ptxf-files-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        for file in [
+            "01 - Ana/a.jpg",
+            "01 - Ana/.b.jpg.part",
+            "01 - Ana/x.part", // Trimis chiar cu acest nume - e complet
+            "01 - Ana/.DS_Store",
+            ".tmp_Ion/c.jpg",
+            ".trash/d.jpg",
+        ] {
+            let path = base.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"x").unwrap();
+        }
+
+        let mut files = received_files(&base);
+        files.sort();
+        assert_eq!(files, vec![base.join(".tmp_Ion/c.jpg"), base.join("01 - Ana/a.jpg"), base.join("01 - Ana/x.part")]);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
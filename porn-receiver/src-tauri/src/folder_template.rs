@@ -0,0 +1,398 @@
+use std::path::Path;
+use transfer_protocol::check_folder_name;
+
+// Folosit când template-ul din config e invalid (fișier editat manual)
+pub const DEFAULT_TEMPLATE: &str = "{num:02d} - {name}";
+// Cifre maxime pentru "{num:0Nd}" - suficient pentru orice contor real
+const MAX_WIDTH: usize = 12;
+// Câte fișiere primite se citesc după modelul camerei
+const CAMERA_PROBE_FILES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Name,
+    Initials,
+    Num,
+    Day,
+    Role,
+    Source,
+    Camera,
+    Count,
+    Date,
+    Time,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "name" => Field::Name,
+            "initials" => Field::Initials,
+            "num" => Field::Num,
+            "day" => Field::Day,
+            "role" => Field::Role,
+            "source" => Field::Source,
+            "camera" => Field::Camera,
+            "count" => Field::Count,
+            "date" => Field::Date,
+            "time" => Field::Time,
+            _ => return None,
+        })
+    }
+
+    fn is_number(self) -> bool {
+        matches!(self, Field::Num | Field::Count)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Case {
+    Upper,
+    Lower,
+    Title,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field { field: Field, width: usize, case: Option<Case> },
+}
+
+/// Valorile unui transfer folosite în numele folderului
+pub struct TemplateValues<'a> {
+    pub photographer: &'a str,
+    pub counter: u32,
+    pub day: &'a str,    // Gol dacă nu se folosesc zile
+    pub role: &'a str,   // Rolul receiver-ului ("tagger"/"editor")
+    pub source: &'a str, // Categoria senderului ("Fotograf", "Tagger", "Editor")
+    pub camera: Option<String>,
+    pub file_count: Option<usize>,
+    pub preview: bool, // Valorile necunoscute încă (camera, numărul de fișiere) apar ca "[camera]"
+}
+
+/// Template de nume de folder: text cu câmpuri `{camp}`, `{camp:0Nd}` (zerouri în față, doar numere)
+/// și `{camp|upper}` / `|lower` / `|title`. Acoladele literale se scriu `{{` și `}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FolderTemplate {
+    parts: Vec<Part>,
+}
+
+impl FolderTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or_else(|| format!("Acoladă neînchisă în {:?}", template))?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(parse_field(&rest[..end])?);
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(format!("Acoladă închisă fără deschidere în {:?}", template)),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        if parts.is_empty() {
+            return Err("Template-ul de folder e gol".to_string());
+        }
+        Ok(Self { parts })
+    }
+
+    /// Parsează și verifică numele rezultat pentru un transfer de probă (separatori, caractere nepermise)
+    pub fn validate(template: &str) -> Result<Self, String> {
+        let parsed = Self::parse(template)?;
+        let sample = TemplateValues {
+            photographer: "Ana Pop",
+            counter: 7,
+            day: "DAY 1",
+            role: "tagger",
+            source: "Fotograf",
+            camera: Some("Canon EOS R5".to_string()),
+            file_count: Some(120),
+            preview: false,
+        };
+        check_folder_name(&parsed.render_raw(&sample))
+            .map_err(|e| format!("Template-ul {:?} produce un nume invalid: {}", template, e))?;
+        Ok(parsed)
+    }
+
+    /// Template-ul din config sau, dacă e invalid, cel implicit
+    pub fn from_config(template: &str) -> Self {
+        Self::parse(template).unwrap_or_else(|e| {
+            eprintln!("{} - folosesc {:?}", e, DEFAULT_TEMPLATE);
+            Self::parse(DEFAULT_TEMPLATE).expect("template implicit valid")
+        })
+    }
+
+    pub fn uses(&self, field: Field) -> bool {
+        self.parts.iter().any(|part| matches!(part, Part::Field { field: f, .. } if *f == field))
+    }
+
+    /// Numele folderului. Dacă rezultatul nu e un nume valid (ex. doar câmpuri goale),
+    /// se folosește template-ul implicit.
+    pub fn render(&self, values: &TemplateValues) -> String {
+        let name = self.render_raw(values);
+        if check_folder_name(&name).is_ok() {
+            return name;
+        }
+        let fallback = Self::parse(DEFAULT_TEMPLATE).expect("template implicit valid").render_raw(values);
+        eprintln!("Nume de folder invalid {:?} - folosesc {:?}", name, fallback);
+        fallback
+    }
+
+    fn render_raw(&self, values: &TemplateValues) -> String {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => name.push_str(text),
+                Part::Field { field, width, case } => {
+                    let value = field_value(*field, *width, values);
+                    name.push_str(&apply_case(&clean(&value), *case));
+                }
+            }
+        }
+        // Câmpurile goale pot lăsa separatori la capete ("01 - Ana - ")
+        name.trim_matches(|c: char| c == ' ' || c == '.' || c == '-' || c == '_').to_string()
+    }
+}
+
+fn parse_field(spec: &str) -> Result<Part, String> {
+    let (spec, case) = match spec.split_once('|') {
+        Some((spec, case)) => {
+            let case = match case.trim() {
+                "upper" => Case::Upper,
+                "lower" => Case::Lower,
+                "title" => Case::Title,
+                other => return Err(format!("Transformare necunoscută {:?} (upper, lower, title)", other)),
+            };
+            (spec, Some(case))
+        }
+        None => (spec, None),
+    };
+
+    let (name, format) = match spec.split_once(':') {
+        Some((name, format)) => (name.trim(), Some(format.trim())),
+        None => (spec.trim(), None),
+    };
+    let field = Field::parse(name).ok_or_else(|| {
+        format!("Câmp necunoscut {{{}}} (name, initials, num, day, role, source, camera, count, date, time)", name)
+    })?;
+
+    let width = match format {
+        None => 0,
+        Some(format) => {
+            if !field.is_number() {
+                return Err(format!("{{{}:{}}}: zerourile în față se pot pune doar la num și count", name, format));
+            }
+            format
+                .strip_prefix('0')
+                .and_then(|f| f.strip_suffix('d'))
+                .and_then(|digits| digits.parse::<usize>().ok())
+                .filter(|width| (1..=MAX_WIDTH).contains(width))
+                .ok_or_else(|| format!("Format invalid {{{}:{}}} (ex. {{{}:03d}})", name, format, name))?
+        }
+    };
+
+    Ok(Part::Field { field, width, case })
+}
+
+fn field_value(field: Field, width: usize, values: &TemplateValues) -> String {
+    let unknown = |placeholder: &str| if values.preview { format!("[{}]", placeholder) } else { String::new() };
+    match field {
+        Field::Name => values.photographer.to_string(),
+        Field::Initials => initials(values.photographer),
+        Field::Num => format!("{:0width$}", values.counter, width = width),
+        Field::Day => values.day.to_string(),
+        Field::Role => values.role.to_string(),
+        Field::Source => values.source.to_string(),
+        Field::Camera => values.camera.clone().unwrap_or_else(|| unknown("camera")),
+        Field::Count => match values.file_count {
+            Some(count) => format!("{:0width$}", count, width = width),
+            None => unknown("count"),
+        },
+        Field::Date => chrono::Local::now().format("%Y-%m-%d").to_string(),
+        Field::Time => chrono::Local::now().format("%H-%M").to_string(),
+    }
+}
+
+// "Ana-Maria Pop" → "AMP"
+fn initials(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter_map(|word| word.chars().next())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+// Valorile care nu vin din config (ex. modelul camerei) pot conține separatori
+fn clean(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_control() || "/\\<>:\"|?*".contains(c) { '-' } else { c })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn apply_case(value: &str, case: Option<Case>) -> String {
+    match case {
+        None => value.to_string(),
+        Some(Case::Upper) => value.to_uppercase(),
+        Some(Case::Lower) => value.to_lowercase(),
+        Some(Case::Title) => value
+            .split(' ')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// Modelul camerei din EXIF-ul primelor fișiere primite (JPEG, HEIF și RAW-urile bazate pe TIFF)
+pub fn camera_model(folder: &Path) -> Option<String> {
+    let mut files: Vec<_> = std::fs::read_dir(folder)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')))
+        .collect();
+    files.sort();
+
+    files.iter().take(CAMERA_PROBE_FILES).find_map(|path| {
+        let file = std::fs::File::open(path).ok()?;
+        let exif = exif::Reader::new().read_from_container(&mut std::io::BufReader::new(file)).ok()?;
+        let model = exif.get_field(exif::Tag::Model, exif::In::PRIMARY)?;
+        let model = match model.value {
+            exif::Value::Ascii(ref values) => String::from_utf8_lossy(values.first()?).trim().to_string(),
+            _ => return None,
+        };
+        (!model.is_empty()).then_some(model)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(photographer: &str, counter: u32) -> TemplateValues<'_> {
+        TemplateValues {
+            photographer,
+            counter,
+            day: "DAY 2",
+            role: "tagger",
+            source: "Fotograf",
+            camera: None,
+            file_count: None,
+            preview: false,
+        }
+    }
+
+    fn render(template: &str, values: &TemplateValues) -> String {
+        FolderTemplate::parse(template).unwrap().render(values)
+    }
+
+    #[test]
+    fn default_template_pads_counter() {
+        assert_eq!(render(DEFAULT_TEMPLATE, &values("Ana Pop", 7)), "07 - Ana Pop");
+        assert_eq!(render(DEFAULT_TEMPLATE, &values("Ana Pop", 123)), "123 - Ana Pop");
+    }
+
+    #[test]
+    fn padding_width() {
+        assert_eq!(render("{num:04d}", &values("Ana", 7)), "0007");
+        assert_eq!(render("{num}", &values("Ana", 7)), "7");
+        assert_eq!(render("{num:012d}", &values("Ana", 7)), "000000000007");
+        assert!(FolderTemplate::parse("{num:013d}").is_err());
+        assert!(FolderTemplate::parse("{num:00d}").is_err());
+        assert!(FolderTemplate::parse("{num:4}").is_err());
+    }
+
+    #[test]
+    fn padding_only_for_numbers() {
+        let err = FolderTemplate::parse("{name:03d}").unwrap_err();
+        assert!(err.contains("num și count"), "{}", err);
+    }
+
+    #[test]
+    fn case_transforms() {
+        let v = values("ana-maria POP", 1);
+        assert_eq!(render("{name|upper}", &v), "ANA-MARIA POP");
+        assert_eq!(render("{name|lower}", &v), "ana-maria pop");
+        assert_eq!(render("{name|title}", &v), "Ana-maria Pop");
+        assert_eq!(render("{day|lower} {num:02d|upper}", &v), "day 2 01");
+        assert!(FolderTemplate::parse("{name|shout}").is_err());
+    }
+
+    #[test]
+    fn initials_split_on_separators() {
+        assert_eq!(render("{initials}", &values("Ana-Maria Pop", 1)), "AMP");
+        assert_eq!(render("{initials}_{num:03d}", &values("ion_ionescu", 5)), "II_005");
+    }
+
+    #[test]
+    fn unknown_field_is_rejected() {
+        let err = FolderTemplate::parse("{num} - {photographer}").unwrap_err();
+        assert!(err.contains("Câmp necunoscut {photographer}"), "{}", err);
+    }
+
+    #[test]
+    fn unbalanced_braces_are_rejected() {
+        assert!(FolderTemplate::parse("{num - {name}").is_err());
+        assert!(FolderTemplate::parse("{num").unwrap_err().contains("neînchisă"));
+        assert!(FolderTemplate::parse("num} {name}").unwrap_err().contains("fără deschidere"));
+        assert!(FolderTemplate::parse("").is_err());
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        assert_eq!(render("{{{num}}} {name}", &values("Ana", 3)), "{3} Ana");
+    }
+
+    #[test]
+    fn empty_fields_trim_separators() {
+        assert_eq!(render("{num:02d} - {name} - {camera}", &values("Ana", 1)), "01 - Ana");
+        let mut preview = values("Ana", 1);
+        preview.preview = true;
+        assert_eq!(render("{name} {camera} {count}", &preview), "Ana [camera] [count]");
+    }
+
+    #[test]
+    fn validate_rejects_invalid_names() {
+        assert!(FolderTemplate::validate("{num:02d} - {name|upper}").is_ok());
+        assert!(FolderTemplate::validate("{day}/{name}").is_err());
+        assert!(FolderTemplate::validate("..").is_err());
+    }
+
+    #[test]
+    fn uses_reports_fields() {
+        let template = FolderTemplate::parse("{initials} {camera|upper}").unwrap();
+        assert!(template.uses(Field::Camera));
+        assert!(template.uses(Field::Initials));
+        assert!(!template.uses(Field::Num));
+    }
+
+    #[test]
+    fn invalid_config_falls_back_to_default() {
+        assert_eq!(FolderTemplate::from_config("{oops"), FolderTemplate::parse(DEFAULT_TEMPLATE).unwrap());
+    }
+}
@@ -0,0 +1,412 @@
+use crate::config::{
+    history_path, load_history, load_sent_history, sent_history_path, ReceiverConfig, SentRecord, TransferRecord,
+    TransferStatus,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS transfers (
+        id           INTEGER PRIMARY KEY,
+        profile_id   TEXT NOT NULL,
+        transfer_id  TEXT NOT NULL,
+        timestamp    TEXT NOT NULL,
+        photographer TEXT NOT NULL,
+        file_count   INTEGER NOT NULL,
+        total_size   INTEGER NOT NULL,
+        folder       TEXT NOT NULL,
+        day          TEXT,
+        status       TEXT NOT NULL,
+        source_role  TEXT,
+        backups      TEXT NOT NULL DEFAULT '[]'
+    );
+    CREATE INDEX IF NOT EXISTS transfers_by_time ON transfers (profile_id, timestamp);
+    CREATE INDEX IF NOT EXISTS transfers_by_folder ON transfers (profile_id, folder);
+
+    CREATE TABLE IF NOT EXISTS sent (
+        id          INTEGER PRIMARY KEY,
+        timestamp   TEXT NOT NULL,
+        target_name TEXT NOT NULL,
+        file_count  INTEGER NOT NULL,
+        total_size  INTEGER NOT NULL,
+        folder_name TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS imports (
+        file        TEXT PRIMARY KEY,
+        imported_at TEXT NOT NULL,
+        records     INTEGER NOT NULL
+    );
+";
+
+// Modificările schemei după prima versiune, aplicate o singură dată în ordine (PRAGMA user_version)
+const MIGRATIONS: &[&str] = &[
+    "ALTER TABLE transfers ADD COLUMN speed_mbps REAL",
+    // Trimiterile de dinainte de profiluri aparțin profilului inițial ("")
+    "ALTER TABLE sent ADD COLUMN profile_id TEXT NOT NULL DEFAULT '';
+     CREATE INDEX IF NOT EXISTS sent_by_time ON sent (profile_id, timestamp);",
+];
+
+const TRANSFER_COLUMNS: &str = "transfer_id, timestamp, photographer, file_count, total_size, folder, day, status, \
+                                source_role, backups, speed_mbps";
+
+/// Filtrele istoricului; câmpurile lipsă nu filtrează
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HistoryFilter {
+    pub day: Option<String>,
+    pub photographer: Option<String>, // Fără diferență între litere mari și mici
+    pub status: Option<TransferStatus>,
+    pub from: Option<DateTime<Utc>>, // Inclusiv
+    pub to: Option<DateTime<Utc>>,   // Exclusiv
+}
+
+/// Istoricul transferurilor primite și trimise, într-o bază SQLite. Fiecare modificare e o
+/// tranzacție, deci conexiunile simultane nu-și mai suprascriu una alteia fișierul.
+#[derive(Clone)]
+pub struct HistoryStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl HistoryStore {
+    pub fn path() -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".porn_transfer_receiver.db")
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("Nu pot deschide istoricul {}: {}", path.display(), e))?;
+        // WAL: citirile din UI nu așteaptă după scrierile serverului
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+        Self::init(conn)
+    }
+
+    /// Istoricul de pe disc sau, dacă nu poate fi deschis (fișier corupt, disc plin), unul în memorie:
+    /// aplicația pornește oricum, doar că istoricul nu se păstrează după închidere
+    pub fn open_or_in_memory(path: &Path) -> Self {
+        Self::open(path).unwrap_or_else(|e| {
+            eprintln!("{} - istoricul se păstrează doar până la închiderea aplicației", e);
+            Connection::open_in_memory()
+                .map_err(|e| e.to_string())
+                .and_then(Self::init)
+                .expect("SQLite în memorie")
+        })
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA).map_err(|e| format!("Eroare creare tabele istoric: {}", e))?;
+        migrate(&conn).map_err(|e| format!("Eroare actualizare tabele istoric: {}", e))?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut Connection) -> rusqlite::Result<R>) -> Result<R, String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        f(&mut conn).map_err(|e| format!("Eroare istoric: {}", e))
+    }
+
+    /// Stadiul unui transfer în curs sau întrerupt; actualizează înregistrarea folderului dacă există
+    pub fn record_progress(&self, profile_id: &str, record: &TransferRecord) -> Result<(), String> {
+        let backups = backups_json(record);
+        self.with(|conn| {
+            let tx = conn.transaction()?;
+            let updated = tx.execute(
+                "UPDATE transfers
+                 SET timestamp = ?3, file_count = ?4, total_size = ?5, status = ?6, backups = ?7, speed_mbps = ?8
+                 WHERE profile_id = ?1 AND folder = ?2",
+                params![
+                    profile_id,
+                    record.folder,
+                    timestamp_text(&record.timestamp),
+                    record.file_count as i64,
+                    record.total_size as i64,
+                    status_text(&record.status),
+                    backups,
+                    record.speed_mbps,
+                ],
+            )?;
+            if updated == 0 {
+                insert_transfer(&tx, profile_id, record)?;
+            }
+            tx.commit()
+        })
+    }
+
+    /// Transfer terminat: înregistrarea folderului temporar e înlocuită de cea cu folderul final
+    pub fn record_complete(&self, profile_id: &str, temp_folder: &str, record: &TransferRecord) -> Result<(), String> {
+        self.with(|conn| {
+            let tx = conn.transaction()?;
+            delete_folder(&tx, profile_id, temp_folder)?;
+            insert_transfer(&tx, profile_id, record)?;
+            tx.commit()
+        })
+    }
+
+    /// Transferurile profilului, în ordinea în care au avut loc
+    pub fn transfers(&self, profile_id: &str, filter: &HistoryFilter) -> Result<Vec<TransferRecord>, String> {
+        self.with(|conn| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {} FROM transfers
+                 WHERE profile_id = ?1
+                   AND (?2 IS NULL OR day = ?2)
+                   AND (?3 IS NULL OR photographer = ?3 COLLATE NOCASE)
+                   AND (?4 IS NULL OR status = ?4)
+                   AND (?5 IS NULL OR timestamp >= ?5)
+                   AND (?6 IS NULL OR timestamp < ?6)
+                 ORDER BY timestamp, id",
+                TRANSFER_COLUMNS
+            ))?;
+            let rows = stmt.query_map(
+                params![
+                    profile_id,
+                    filter.day,
+                    filter.photographer.as_deref().map(str::trim),
+                    filter.status.as_ref().map(status_text),
+                    filter.from.as_ref().map(timestamp_text),
+                    filter.to.as_ref().map(timestamp_text),
+                ],
+                transfer_from_row,
+            )?;
+            rows.collect()
+        })
+    }
+
+    /// Șterge istoricul profilului (sau doar al unei zile)
+    pub fn clear(&self, profile_id: &str, day: Option<&str>) -> Result<(), String> {
+        self.with(|conn| {
+            conn.execute(
+                "DELETE FROM transfers WHERE profile_id = ?1 AND (?2 IS NULL OR day = ?2)",
+                params![profile_id, day],
+            )
+        })
+        .map(|_| ())
+    }
+
+    /// Aplică rezultatul verificării pe disc: contoare noi pentru `updated`, foldere dispărute în `removed`
+    pub fn sync_folders(&self, profile_id: &str, updated: &[TransferRecord], removed: &[String]) -> Result<(), String> {
+        self.with(|conn| {
+            let tx = conn.transaction()?;
+            for record in updated {
+                tx.execute(
+                    "UPDATE transfers SET file_count = ?3, total_size = ?4, status = ?5
+                     WHERE profile_id = ?1 AND folder = ?2",
+                    params![
+                        profile_id,
+                        record.folder,
+                        record.file_count as i64,
+                        record.total_size as i64,
+                        status_text(&record.status),
+                    ],
+                )?;
+            }
+            for folder in removed {
+                delete_folder(&tx, profile_id, folder)?;
+            }
+            tx.commit()
+        })
+    }
+
+    pub fn add_sent(&self, profile_id: &str, record: &SentRecord) -> Result<(), String> {
+        self.with(|conn| insert_sent(conn, profile_id, record))
+    }
+
+    /// Trimiterile făcute din profilul `profile_id`, în ordinea în care au avut loc
+    pub fn sent(&self, profile_id: &str) -> Result<Vec<SentRecord>, String> {
+        self.with(|conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT timestamp, target_name, file_count, total_size, folder_name FROM sent
+                 WHERE profile_id = ?1
+                 ORDER BY timestamp, id",
+            )?;
+            let rows = stmt.query_map([profile_id], |row| {
+                Ok(SentRecord {
+                    timestamp: timestamp_from_row(row, 0)?,
+                    target_name: row.get(1)?,
+                    file_count: row.get::<_, i64>(2)? as usize,
+                    total_size: row.get::<_, i64>(3)? as u64,
+                    folder_name: row.get(4)?,
+                })
+            })?;
+            rows.collect()
+        })
+    }
+
+    /// Importă o singură dată fișierele JSON ale versiunilor vechi, pentru toate profilurile.
+    /// Fișierele rămân pe disc ca backup.
+    pub fn import_legacy(&self, config: &ReceiverConfig) {
+        for profile in config.list_profiles() {
+            let file = history_path(&profile.id);
+            if self.needs_import(&file) {
+                let result = load_history(&profile.id)
+                    .and_then(|records| self.import(&file, records, |tx, r| insert_transfer(tx, &profile.id, r)));
+                report_import(&file, result);
+            }
+        }
+
+        let file = sent_history_path();
+        if self.needs_import(&file) {
+            // Fișierul vechi e de dinainte de profiluri - trimiterile țin de profilul inițial
+            let result =
+                load_sent_history().and_then(|records| self.import(&file, records, |tx, r| insert_sent(tx, "", r)));
+            report_import(&file, result);
+        }
+    }
+
+    fn needs_import(&self, file: &Path) -> bool {
+        let imported = self.with(|conn| {
+            conn.query_row("SELECT 1 FROM imports WHERE file = ?1", [file.to_string_lossy()], |_| Ok(()))
+                .optional()
+        });
+        file.exists() && matches!(imported, Ok(None))
+    }
+
+    fn import<T: Timestamped>(
+        &self,
+        file: &Path,
+        mut records: Vec<T>,
+        insert: impl Fn(&Connection, &T) -> rusqlite::Result<()>,
+    ) -> Result<usize, String> {
+        // Fișierele vechi erau inversate la fiecare salvare
+        records.sort_by_key(|r| r.timestamp());
+        self.with(|conn| {
+            let tx = conn.transaction()?;
+            for record in &records {
+                insert(&tx, record)?;
+            }
+            tx.execute(
+                "INSERT INTO imports (file, imported_at, records) VALUES (?1, ?2, ?3)",
+                params![file.to_string_lossy(), timestamp_text(&Utc::now()), records.len() as i64],
+            )?;
+            tx.commit()?;
+            Ok(records.len())
+        })
+    }
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        conn.execute_batch(migration)?;
+        conn.pragma_update(None, "user_version", index + 1)?;
+    }
+    Ok(())
+}
+
+fn report_import(file: &Path, result: Result<usize, String>) {
+    match result {
+        Ok(count) => println!("Istoric importat din {}: {} înregistrări", file.display(), count),
+        Err(e) => eprintln!("Nu pot importa istoricul din {}: {}", file.display(), e),
+    }
+}
+
+trait Timestamped {
+    fn timestamp(&self) -> DateTime<Utc>;
+}
+
+impl Timestamped for TransferRecord {
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+}
+
+impl Timestamped for SentRecord {
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+}
+
+fn insert_transfer(conn: &Connection, profile_id: &str, record: &TransferRecord) -> rusqlite::Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO transfers (profile_id, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            TRANSFER_COLUMNS
+        ),
+        params![
+            profile_id,
+            record.transfer_id,
+            timestamp_text(&record.timestamp),
+            record.photographer,
+            record.file_count as i64,
+            record.total_size as i64,
+            record.folder,
+            record.day,
+            status_text(&record.status),
+            record.source_role,
+            backups_json(record),
+            record.speed_mbps,
+        ],
+    )
+    .map(|_| ())
+}
+
+fn delete_folder(conn: &Connection, profile_id: &str, folder: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM transfers WHERE profile_id = ?1 AND folder = ?2", params![profile_id, folder])
+        .map(|_| ())
+}
+
+fn insert_sent(conn: &Connection, profile_id: &str, record: &SentRecord) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO sent (profile_id, timestamp, target_name, file_count, total_size, folder_name)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            profile_id,
+            timestamp_text(&record.timestamp),
+            record.target_name,
+            record.file_count as i64,
+            record.total_size as i64,
+            record.folder_name,
+        ],
+    )
+    .map(|_| ())
+}
+
+fn transfer_from_row(row: &Row) -> rusqlite::Result<TransferRecord> {
+    let backups: String = row.get(9)?;
+    Ok(TransferRecord {
+        transfer_id: row.get(0)?,
+        timestamp: timestamp_from_row(row, 1)?,
+        photographer: row.get(2)?,
+        file_count: row.get::<_, i64>(3)? as usize,
+        total_size: row.get::<_, i64>(4)? as u64,
+        folder: row.get(5)?,
+        day: row.get(6)?,
+        status: status_from_text(&row.get::<_, String>(7)?),
+        source_role: row.get(8)?,
+        backups: serde_json::from_str(&backups).unwrap_or_default(),
+        speed_mbps: row.get(10)?,
+    })
+}
+
+fn backups_json(record: &TransferRecord) -> String {
+    serde_json::to_string(&record.backups).unwrap_or_else(|_| "[]".to_string())
+}
+
+// Format fix (UTC, microsecunde), ca ordinea textului să fie ordinea cronologică
+fn timestamp_text(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn timestamp_from_row(row: &Row, index: usize) -> rusqlite::Result<DateTime<Utc>> {
+    let text: String = row.get(index)?;
+    DateTime::parse_from_rfc3339(&text)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+fn status_text(status: &TransferStatus) -> &'static str {
+    match status {
+        TransferStatus::Complete => "Complete",
+        TransferStatus::Partial => "Partial",
+        TransferStatus::Error => "Error",
+    }
+}
+
+fn status_from_text(text: &str) -> TransferStatus {
+    match text {
+        "Partial" => TransferStatus::Partial,
+        "Error" => TransferStatus::Error,
+        _ => TransferStatus::Complete,
+    }
+}
@@ -1,10 +1,26 @@
 mod config;
+mod content_index;
 mod discovery;
+mod files;
+mod folder_template;
+mod history;
+mod manifest;
+mod report;
+mod security;
 mod server;
+mod session;
+mod stats;
 mod transfer;
 
-use config::{ReceiverConfig, TransferRecord, SentRecord, load_sent_history, add_sent_record};
+use config::{FolderTransfer, ProfileInfo, ReceiverConfig, SentRecord, TransferRecord};
+use content_index::ContentIndex;
 use discovery::{DiscoveredService, ServiceDiscovery};
+use folder_template::FolderTemplate;
+use history::{HistoryFilter, HistoryStore};
+use report::Report;
+use security::{Pairing, SharedPairing};
+use session::ActiveTransfers;
+use stats::TransferStats;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
@@ -15,10 +31,13 @@ const DEFAULT_PORT: u16 = 45678;
 pub struct AppState {
     pub config: Arc<Mutex<ReceiverConfig>>,
     pub is_running: Arc<Mutex<bool>>,
-    pub history: Arc<Mutex<Vec<TransferRecord>>>,
+    pub server_stop: Mutex<Option<tokio::sync::watch::Sender<bool>>>, // Oprește imediat serverul pornit
+    pub history: HistoryStore, // Transferurile primite (per profil) și trimise
     pub discovery: Arc<Mutex<Option<ServiceDiscovery>>>,
-    pub is_transfer_cancelled: Arc<AtomicBool>,
+    pub active_transfers: ActiveTransfers, // Transferurile primite în curs (anulabile din UI)
+    pub content_index: ContentIndex,       // Hash → fișierele salvate, pentru verificarea duplicatelor
     pub is_send_cancelled: Arc<AtomicBool>,
+    pub pairing: SharedPairing,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +52,13 @@ pub struct TransferProgress {
     pub speed_mbps: f64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairingInfo {
+    pub pin: String,         // PIN-ul pentru asocierea unui dispozitiv nou
+    pub fingerprint: String, // Amprenta cheii acestui receiver
+    pub devices: Vec<transfer_protocol::TrustedPeer>,
+}
+
 // Commands
 
 #[tauri::command]
@@ -43,18 +69,44 @@ async fn get_config(state: State<'_, AppState>) -> Result<ReceiverConfig, String
 
 #[tauri::command]
 async fn save_config(state: State<'_, AppState>, config: ReceiverConfig) -> Result<(), String> {
+    // Un template greșit s-ar descoperi abia la finalul primului transfer
+    FolderTemplate::validate(&config.folder_template)?;
+    if config.day_cutoff_hour > 23 {
+        return Err(format!("Ora de schimbare a zilei trebuie să fie între 0 și 23, nu {}", config.day_cutoff_hour));
+    }
+    if let Some(day) = config.event_days.iter().find(|d| d.label.trim().is_empty()) {
+        return Err(format!("Ziua din {} nu are nume", day.date));
+    }
+
     let mut current = state.config.lock().map_err(|e| e.to_string())?;
 
     // Păstrează counter-urile existente (frontend nu le trimite)
     let day_counters = current.day_counters.clone();
     let folder_counter = current.folder_counter;
+    // Ziua se schimbă doar prin `set_current_day` sau după program
+    let current_day = current.current_day.clone();
+    let day_override = current.day_override.clone();
+    // Profilurile se schimbă doar prin comenzile de profil
+    let profile_id = current.profile_id.clone();
+    let profile_name = current.profile_name.clone();
+    let profiles = std::mem::take(&mut current.profiles);
+    // Editorii vechi se permit doar prin `set_legacy_receiver`
+    let legacy_receivers = std::mem::take(&mut current.legacy_receivers);
 
     *current = config;
 
     // Restaurează counter-urile
     current.day_counters = day_counters;
     current.folder_counter = folder_counter;
-
+    current.current_day = current_day;
+    current.day_override = day_override;
+    current.profile_id = profile_id;
+    current.profile_name = profile_name;
+    current.profiles = profiles;
+    current.legacy_receivers = legacy_receivers;
+
+    // Programul poate fi nou
+    current.refresh_day();
     current.save()?;
     Ok(())
 }
@@ -77,20 +129,28 @@ async fn start_server(
         *running = true;
     }
 
+    let (stop_tx, stop) = tokio::sync::watch::channel(false);
+    *state.server_stop.lock().map_err(|e| e.to_string())? = Some(stop_tx);
+
     let is_running = Arc::clone(&state.is_running);
     let config_state = Arc::clone(&state.config);
-    let history = Arc::clone(&state.history);
-    let is_cancelled = Arc::clone(&state.is_transfer_cancelled);
-
-    // Reset flag la pornirea serverului
-    is_cancelled.store(false, Ordering::Relaxed);
+    let history = state.history.clone();
+    let active = state.active_transfers.clone();
+    let index = state.content_index.clone();
+    let pairing = Arc::clone(&state.pairing);
 
     // Folosește portul din config sau default
     let port = if config.port > 0 { config.port } else { DEFAULT_PORT };
 
-    std::thread::spawn(move || {
-        if let Err(e) = server::run_server(port, config, config_state, history, is_running, is_cancelled, window) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) =
+            server::run_server(port, config, config_state, history, stop, active, index, pairing, window).await
+        {
             eprintln!("Server error: {}", e);
+            // Ex. portul e ocupat - serverul poate fi pornit din nou
+            if let Ok(mut running) = is_running.lock() {
+                *running = false;
+            }
         }
     });
 
@@ -101,6 +161,9 @@ async fn start_server(
 async fn stop_server(state: State<'_, AppState>) -> Result<(), String> {
     let mut running = state.is_running.lock().map_err(|e| e.to_string())?;
     *running = false;
+    if let Some(stop) = state.server_stop.lock().map_err(|e| e.to_string())?.take() {
+        let _ = stop.send(true);
+    }
     Ok(())
 }
 
@@ -110,39 +173,71 @@ async fn is_server_running(state: State<'_, AppState>) -> Result<bool, String> {
     Ok(*running)
 }
 
+fn active_profile_id(state: &AppState) -> Result<String, String> {
+    Ok(state.config.lock().map_err(|e| e.to_string())?.profile_id.clone())
+}
+
 #[tauri::command]
 async fn get_history(state: State<'_, AppState>) -> Result<Vec<TransferRecord>, String> {
-    let history = state.history.lock().map_err(|e| e.to_string())?;
-    Ok(history.clone())
+    state.history.transfers(&active_profile_id(&state)?, &HistoryFilter::default())
+}
+
+/// Istoricul evenimentului activ filtrat după zi, fotograf, status și interval de timp
+#[tauri::command]
+async fn query_history(state: State<'_, AppState>, filter: HistoryFilter) -> Result<Vec<TransferRecord>, String> {
+    state.history.transfers(&active_profile_id(&state)?, &filter)
+}
+
+/// Raportul evenimentului activ, pe zile și fotografi, scris ca CSV, JSON și HTML în `folder`.
+/// Filtrul se aplică transferurilor primite; trimiterile se filtrează doar după interval.
+#[tauri::command]
+async fn export_report(
+    state: State<'_, AppState>,
+    folder: String,
+    filter: Option<HistoryFilter>,
+) -> Result<Vec<String>, String> {
+    let (event, station, profile_id) = {
+        let config = state.config.lock().map_err(|e| e.to_string())?;
+        (config.profile_name.clone(), config.name.clone(), config.profile_id.clone())
+    };
+    let filter = filter.unwrap_or_default();
+
+    let transfers = state.history.transfers(&profile_id, &filter)?;
+    let sent: Vec<SentRecord> = state
+        .history
+        .sent(&profile_id)?
+        .into_iter()
+        .filter(|r| filter.from.is_none_or(|from| r.timestamp >= from) && filter.to.is_none_or(|to| r.timestamp < to))
+        .collect();
+
+    let files = Report::build(&event, &station, &transfers, &sent).export(std::path::Path::new(&folder))?;
+    Ok(files.iter().map(|f| f.to_string_lossy().to_string()).collect())
+}
+
+/// Volum, viteză, erori și orele aglomerate pe fotografi, zile și surse, din istoricul profilului activ
+#[tauri::command]
+async fn get_transfer_stats(
+    state: State<'_, AppState>,
+    filter: Option<HistoryFilter>,
+) -> Result<TransferStats, String> {
+    let transfers = state.history.transfers(&active_profile_id(&state)?, &filter.unwrap_or_default())?;
+    Ok(TransferStats::build(&transfers))
 }
 
 #[tauri::command]
 async fn clear_history(state: State<'_, AppState>, day: Option<String>) -> Result<(), String> {
-    let mut history = state.history.lock().map_err(|e| e.to_string())?;
+    let mut config = state.config.lock().map_err(|e| e.to_string())?;
+    state.history.clear(&config.profile_id, day.as_deref())?;
 
     if let Some(ref day_to_clear) = day {
-        // Șterge doar intrările din ziua specificată
-        history.retain(|record| {
-            record.day.as_ref() != Some(day_to_clear)
-        });
-
         // Resetează counter-ul pentru ziua respectivă
-        let mut config = state.config.lock().map_err(|e| e.to_string())?;
         config.day_counters.remove(day_to_clear);
-        config.save()?;
     } else {
-        // Șterge tot istoricul
-        history.clear();
-
         // Resetează toate counter-urile
-        let mut config = state.config.lock().map_err(|e| e.to_string())?;
         config.day_counters.clear();
         config.folder_counter = 1;
-        config.save()?;
     }
-
-    config::save_history(&history)?;
-    Ok(())
+    config.save()
 }
 
 #[tauri::command]
@@ -150,30 +245,23 @@ async fn sync_history_from_disk(state: State<'_, AppState>) -> Result<(), String
     use config::TransferStatus;
     use std::path::Path;
 
-    let mut history = state.history.lock().map_err(|e| e.to_string())?;
+    let profile_id = active_profile_id(&state)?;
+    let mut history = state.history.transfers(&profile_id, &HistoryFilter::default())?;
 
     // Actualizează fiecare înregistrare pe baza fișierelor reale din folder
     for record in history.iter_mut() {
         let folder_path = Path::new(&record.folder);
 
         if folder_path.exists() {
-            // Numără fișierele reale
-            if let Ok(entries) = std::fs::read_dir(folder_path) {
-                let mut count = 0usize;
-                let mut size = 0u64;
-                for entry in entries.filter_map(|e| e.ok()) {
-                    if entry.path().is_file() {
-                        count += 1;
-                        size += entry.metadata().map(|m| m.len()).unwrap_or(0);
-                    }
-                }
-                record.file_count = count;
-                record.total_size = size;
-
-                // Dacă folderul există și are fișiere, marchează ca Complete
-                if count > 0 && record.status != TransferStatus::Complete {
-                    record.status = TransferStatus::Complete;
-                }
+            // Numără fișierele reale, la fel ca la sfârșitul transferului
+            let files = files::received_files(folder_path);
+            let count = files.len();
+            record.file_count = count;
+            record.total_size = files.iter().filter_map(|f| std::fs::metadata(f).ok()).map(|m| m.len()).sum();
+
+            // Dacă folderul există și are fișiere, marchează ca Complete
+            if count > 0 && record.status != TransferStatus::Complete {
+                record.status = TransferStatus::Complete;
             }
         } else {
             // Folderul nu mai există - marchează cu 0
@@ -183,10 +271,42 @@ async fn sync_history_from_disk(state: State<'_, AppState>) -> Result<(), String
     }
 
     // Elimină înregistrările cu 0 fișiere (foldere șterse)
-    history.retain(|r| r.file_count > 0);
+    let (updated, removed): (Vec<_>, Vec<_>) = history.into_iter().partition(|r| r.file_count > 0);
+    let removed: Vec<String> = removed.into_iter().map(|r| r.folder).collect();
+    state.history.sync_folders(&profile_id, &updated, &removed)
+}
 
-    config::save_history(&history)?;
-    Ok(())
+#[tauri::command]
+async fn list_profiles(state: State<'_, AppState>) -> Result<Vec<ProfileInfo>, String> {
+    let config = state.config.lock().map_err(|e| e.to_string())?;
+    Ok(config.list_profiles())
+}
+
+#[tauri::command]
+async fn create_profile(state: State<'_, AppState>, name: String, base_path: String) -> Result<ProfileInfo, String> {
+    let mut config = state.config.lock().map_err(|e| e.to_string())?;
+    config.create_profile(&name, &base_path)
+}
+
+/// Activează alt eveniment: transferurile noi se salvează în folderul lui, cu contoarele și istoricul lui.
+/// Returnează configurația nouă.
+#[tauri::command]
+async fn switch_profile(state: State<'_, AppState>, id: String) -> Result<ReceiverConfig, String> {
+    // Un transfer în curs și-ar termina folderul în evenimentul vechi, dar cu contoarele celui nou
+    if !state.active_transfers.list().is_empty() {
+        return Err("Evenimentul nu se poate schimba în timpul unui transfer".to_string());
+    }
+
+    let mut config = state.config.lock().map_err(|e| e.to_string())?;
+    config.switch_profile(&id)?;
+    println!("Eveniment activ: {} ({})", config.profile_name, config.base_path);
+    Ok(config.clone())
+}
+
+#[tauri::command]
+async fn archive_profile(state: State<'_, AppState>, id: String, archived: bool) -> Result<(), String> {
+    let mut config = state.config.lock().map_err(|e| e.to_string())?;
+    config.archive_profile(&id, archived)
 }
 
 #[tauri::command]
@@ -262,10 +382,25 @@ async fn send_to_editor(
     let file_count = files.len();
     let target = service.name.clone();
     let folder = folder_name.clone();
+    let profile_id = config.profile_id.clone();
 
     // Trimite fișierele (returnează send_id)
     let is_cancelled = Arc::clone(&state.is_send_cancelled);
-    let send_id = transfer::send_files_to_editor(&service, &config.name, &config.role, &files, folder_name, window, is_cancelled).await?;
+    let pairing = Arc::clone(&state.pairing);
+    let allow_legacy = config.legacy_receivers.contains(&service.name);
+    let send_id = transfer::send_files_to_editor(
+        service,
+        config.name,
+        config.role,
+        files,
+        folder_name,
+        config.event_passphrase,
+        allow_legacy,
+        window,
+        is_cancelled,
+        pairing,
+    )
+    .await?;
 
     // Salvează în istoricul de trimiteri
     let sent_record = SentRecord {
@@ -275,11 +410,41 @@ async fn send_to_editor(
         total_size,
         folder_name: folder,
     };
-    let _ = add_sent_record(sent_record);
+    if let Err(e) = state.history.add_sent(&profile_id, &sent_record) {
+        eprintln!("{}", e);
+    }
 
     Ok(send_id)
 }
 
+#[tauri::command]
+async fn pair_with_editor(
+    state: State<'_, AppState>,
+    target_host: String,
+    target_port: u16,
+    pin: String,
+) -> Result<String, String> {
+    let name = state.config.lock().map_err(|e| e.to_string())?.name.clone();
+    let addr = format!("{}:{}", target_host, target_port);
+    transfer::pair_with_receiver(addr, name, pin, Arc::clone(&state.pairing)).await
+}
+
+#[tauri::command]
+async fn get_pairing_info(state: State<'_, AppState>) -> Result<PairingInfo, String> {
+    let pairing = state.pairing.lock().map_err(|e| e.to_string())?;
+    Ok(PairingInfo {
+        pin: pairing.pin().to_string(),
+        fingerprint: pairing.keys.identity.fingerprint(),
+        devices: pairing.keys.peers.clone(),
+    })
+}
+
+#[tauri::command]
+async fn forget_paired_device(state: State<'_, AppState>, public_key: String) -> Result<(), String> {
+    let mut pairing = state.pairing.lock().map_err(|e| e.to_string())?;
+    pairing.forget(&public_key)
+}
+
 #[tauri::command]
 async fn get_temp_folders(state: State<'_, AppState>) -> Result<Vec<server::TempFolderInfo>, String> {
     let config = state.config.lock().map_err(|e| e.to_string())?;
@@ -288,9 +453,22 @@ async fn get_temp_folders(state: State<'_, AppState>) -> Result<Vec<server::Temp
 }
 
 #[tauri::command]
-async fn cancel_current_transfer(state: State<'_, AppState>) -> Result<(), String> {
-    state.is_transfer_cancelled.store(true, Ordering::Relaxed);
-    Ok(())
+async fn list_active_transfers(state: State<'_, AppState>) -> Result<Vec<TransferProgress>, String> {
+    Ok(state.active_transfers.list())
+}
+
+#[tauri::command]
+async fn cancel_transfer(state: State<'_, AppState>, transfer_id: String) -> Result<(), String> {
+    if state.active_transfers.cancel(&transfer_id) {
+        Ok(())
+    } else {
+        Err("Transferul nu mai este activ".to_string())
+    }
+}
+
+#[tauri::command]
+async fn cancel_all_transfers(state: State<'_, AppState>) -> Result<usize, String> {
+    Ok(state.active_transfers.cancel_all())
 }
 
 #[tauri::command]
@@ -300,8 +478,27 @@ async fn cancel_send_transfer(state: State<'_, AppState>) -> Result<(), String>
 }
 
 #[tauri::command]
-async fn get_sent_history() -> Result<Vec<SentRecord>, String> {
-    load_sent_history()
+async fn get_sent_history(state: State<'_, AppState>) -> Result<Vec<SentRecord>, String> {
+    state.history.sent(&active_profile_id(&state)?)
+}
+
+/// Alege ziua manual (până la următoarea dată din program) sau, cu `None`, revine la program.
+/// Returnează ziua curentă.
+/// Permite (sau retrage) trimiterea necriptată către un editor vechi, confirmată explicit în UI
+#[tauri::command]
+async fn set_legacy_receiver(state: State<'_, AppState>, target_name: String, allowed: bool) -> Result<(), String> {
+    let mut config = state.config.lock().map_err(|e| e.to_string())?;
+    config.legacy_receivers.retain(|name| *name != target_name);
+    if allowed {
+        config.legacy_receivers.push(target_name);
+    }
+    config.save()
+}
+
+#[tauri::command]
+async fn set_current_day(state: State<'_, AppState>, day: Option<String>) -> Result<String, String> {
+    let mut config = state.config.lock().map_err(|e| e.to_string())?;
+    config.set_day(day)
 }
 
 #[tauri::command]
@@ -321,6 +518,31 @@ async fn set_day_counter(state: State<'_, AppState>, day: String, value: u32) ->
     Ok(())
 }
 
+/// Numele folderului pe care l-ar primi următorul transfer de la `photographer`. Cu `template`
+/// se previzualizează un template încă nesalvat (din setări).
+#[tauri::command]
+async fn preview_folder_name(
+    state: State<'_, AppState>,
+    photographer: String,
+    sender_role: Option<String>,
+    template: Option<String>,
+) -> Result<String, String> {
+    let mut config = state.config.lock().map_err(|e| e.to_string())?.clone();
+    if let Some(template) = template {
+        FolderTemplate::validate(&template)?;
+        config.folder_template = template;
+    }
+
+    let transfer = FolderTransfer {
+        photographer: photographer.trim(),
+        day: &config.current_day,
+        source: config::source_category(sender_role.as_deref()),
+        camera: None,
+        file_count: None,
+    };
+    Ok(config.preview_folder_name(&transfer))
+}
+
 #[tauri::command]
 async fn show_notification(title: String, body: String, sound: Option<String>) -> Result<(), String> {
     #[cfg(target_os = "macos")]
@@ -433,16 +655,22 @@ async fn delete_temp_folder(path: String) -> Result<(), String> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let config = ReceiverConfig::load().unwrap_or_default();
-    let history = config::load_history().unwrap_or_default();
+    let mut config = ReceiverConfig::load().unwrap_or_default();
+    config.refresh_day();
+    let history = HistoryStore::open_or_in_memory(&HistoryStore::path());
+    history.import_legacy(&config);
+    let pairing = Pairing::load_or_regenerate();
 
     let app_state = AppState {
         config: Arc::new(Mutex::new(config)),
         is_running: Arc::new(Mutex::new(false)),
-        history: Arc::new(Mutex::new(history)),
+        server_stop: Mutex::new(None),
+        history,
         discovery: Arc::new(Mutex::new(None)),
-        is_transfer_cancelled: Arc::new(AtomicBool::new(false)),
+        active_transfers: ActiveTransfers::new(),
+        content_index: ContentIndex::new(),
         is_send_cancelled: Arc::new(AtomicBool::new(false)),
+        pairing: Arc::new(Mutex::new(pairing)),
     };
 
     tauri::Builder::default()
@@ -459,18 +687,33 @@ pub fn run() {
             stop_server,
             is_server_running,
             get_history,
+            query_history,
+            export_report,
+            get_transfer_stats,
             clear_history,
             sync_history_from_disk,
+            list_profiles,
+            create_profile,
+            switch_profile,
+            archive_profile,
             get_local_ip,
             start_discovery,
             get_editors,
             send_to_editor,
+            pair_with_editor,
+            get_pairing_info,
+            set_legacy_receiver,
+            forget_paired_device,
             get_temp_folders,
             delete_temp_folder,
-            cancel_current_transfer,
+            list_active_transfers,
+            cancel_transfer,
+            cancel_all_transfers,
             cancel_send_transfer,
+            set_current_day,
             get_day_counter,
             set_day_counter,
+            preview_folder_name,
             get_sent_history,
             show_notification,
             play_sound,
@@ -0,0 +1,99 @@
+use crate::files::{received_files, sync_dir};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Scris în fiecare folder primit complet. Începe cu "." - nu e retrimis mai departe și nu intră în index.
+pub const MANIFEST_FILE: &str = ".transfer-manifest.json";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestSender {
+    pub name: String,
+    pub role: String, // "photographer", "tagger" sau "editor"
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestStation {
+    pub name: String, // Numele acestui receiver
+    pub role: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestFile {
+    pub path: String, // Relativ la folder, cu "/" ca separator
+    pub size: u64,
+    pub hash: String, // blake3, hex
+}
+
+/// Ce s-a primit, de la cine și când - rămâne lângă fișiere și după ce istoricul se pierde
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    pub transfer_id: String,
+    pub sender: ManifestSender,
+    pub station: ManifestStation,
+    pub event: String,
+    pub day: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub files: Vec<ManifestFile>,
+}
+
+impl Manifest {
+    /// Lista fișierelor din `folder`. Hash-urile calculate la primire sunt în `received` (căi relative);
+    /// fișierele rămase de la o sesiune întreruptă anterior se citesc de pe disc.
+    pub fn files(folder: &Path, received: &HashMap<PathBuf, (u64, blake3::Hash)>) -> Vec<ManifestFile> {
+        let mut paths = received_files(folder);
+        paths.sort();
+
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let relative = path.strip_prefix(folder).ok()?.to_path_buf();
+                let (size, hash) = match received.get(&relative) {
+                    Some(&(size, hash)) => (size, hash),
+                    None => match hash_file(&path) {
+                        Ok(hashed) => hashed,
+                        Err(e) => {
+                            eprintln!("Nu pot calcula hash-ul pentru {}: {}", path.display(), e);
+                            return None;
+                        }
+                    },
+                };
+                let path = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                Some(ManifestFile { path, size, hash: hash.to_hex().to_string() })
+            })
+            .collect()
+    }
+
+    /// Scris întâi ca .part, sincronizat pe disc și abia apoi redenumit - o pană de curent
+    /// nu lasă în folder un manifest trunchiat
+    pub fn write(&self, folder: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let path = folder.join(MANIFEST_FILE);
+        let part = folder.join(format!("{}.part", MANIFEST_FILE));
+        let written = (|| -> std::io::Result<()> {
+            let mut file = std::fs::File::create(&part)?;
+            file.write_all(json.as_bytes())?;
+            file.sync_all()?;
+            drop(file);
+            std::fs::rename(&part, &path)?;
+            sync_dir(folder)
+        })();
+        if written.is_err() {
+            let _ = std::fs::remove_file(&part);
+        }
+        written.map_err(|e| format!("Eroare scriere {}: {}", path.display(), e))
+    }
+}
+
+fn hash_file(path: &Path) -> std::io::Result<(u64, blake3::Hash)> {
+    let mut hasher = blake3::Hasher::new();
+    let size = std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok((size, hasher.finalize()))
+}
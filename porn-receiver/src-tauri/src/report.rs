@@ -0,0 +1,343 @@
+use crate::config::{source_category, SentRecord, TransferRecord, TransferStatus};
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Totalurile unui grup de transferuri (fotograf, zi sau tot evenimentul)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Totals {
+    pub transfers: usize,
+    pub files: usize,
+    pub bytes: u64,
+    pub complete: usize,
+    pub partial: usize,
+    pub failed: usize,
+    pub first: Option<DateTime<Utc>>, // Primul transfer terminat
+    pub last: Option<DateTime<Utc>>,  // Ultimul transfer terminat
+}
+
+impl Totals {
+    fn add(&mut self, record: &TransferRecord) {
+        self.transfers += 1;
+        self.files += record.file_count;
+        self.bytes += record.total_size;
+        match record.status {
+            TransferStatus::Complete => self.complete += 1,
+            TransferStatus::Partial => self.partial += 1,
+            TransferStatus::Error => self.failed += 1,
+        }
+        self.first = Some(self.first.map_or(record.timestamp, |t| t.min(record.timestamp)));
+        self.last = Some(self.last.map_or(record.timestamp, |t| t.max(record.timestamp)));
+    }
+
+    fn merge(&mut self, other: &Totals) {
+        self.transfers += other.transfers;
+        self.files += other.files;
+        self.bytes += other.bytes;
+        self.complete += other.complete;
+        self.partial += other.partial;
+        self.failed += other.failed;
+        self.first = [self.first, other.first].into_iter().flatten().min();
+        self.last = [self.last, other.last].into_iter().flatten().max();
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PhotographerReport {
+    pub photographer: String,
+    pub source: String, // "Fotograf", "Tagger" sau "Editor"
+    pub totals: Totals,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DayReport {
+    pub day: String,
+    pub photographers: Vec<PhotographerReport>,
+    pub totals: Totals,
+}
+
+/// Trimiterile către un destinatar într-o zi calendaristică
+#[derive(Debug, Clone, Serialize)]
+pub struct SentReport {
+    pub date: String,
+    pub target: String,
+    pub sends: usize,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub event: String,
+    pub station: String, // Numele acestui receiver
+    pub generated_at: DateTime<Utc>,
+    pub days: Vec<DayReport>,
+    pub totals: Totals,
+    pub sent: Vec<SentReport>,
+}
+
+impl Report {
+    /// Grupează istoricul pe zile și fotografi. Transferurile fără zi (organizare fără foldere
+    /// pe zile) se grupează după data calendaristică.
+    pub fn build(event: &str, station: &str, transfers: &[TransferRecord], sent: &[SentRecord]) -> Self {
+        let mut days: BTreeMap<String, BTreeMap<(String, String), Totals>> = BTreeMap::new();
+        let mut totals = Totals::default();
+        for record in transfers {
+            let day = record.day.clone().unwrap_or_else(|| local_date(&record.timestamp));
+            let source = source_category(record.source_role.as_deref()).to_string();
+            days.entry(day)
+                .or_default()
+                .entry((record.photographer.clone(), source))
+                .or_default()
+                .add(record);
+            totals.add(record);
+        }
+
+        let mut days: Vec<DayReport> = days
+            .into_iter()
+            .map(|(day, photographers)| {
+                let mut day_totals = Totals::default();
+                let photographers = photographers
+                    .into_iter()
+                    .map(|((photographer, source), totals)| {
+                        day_totals.merge(&totals);
+                        PhotographerReport { photographer, source, totals }
+                    })
+                    .collect();
+                DayReport { day, photographers, totals: day_totals }
+            })
+            .collect();
+        days.sort_by_cached_key(|d| day_order(&d.day));
+
+        let mut sent_groups: BTreeMap<(String, String), SentReport> = BTreeMap::new();
+        for record in sent {
+            let date = local_date(&record.timestamp);
+            let group = sent_groups.entry((date.clone(), record.target_name.clone())).or_insert_with(|| SentReport {
+                date,
+                target: record.target_name.clone(),
+                sends: 0,
+                files: 0,
+                bytes: 0,
+            });
+            group.sends += 1;
+            group.files += record.file_count;
+            group.bytes += record.total_size;
+        }
+
+        Self {
+            event: event.to_string(),
+            station: station.to_string(),
+            generated_at: Utc::now(),
+            days,
+            totals,
+            sent: sent_groups.into_values().collect(),
+        }
+    }
+
+    /// Scrie raportul ca CSV, JSON și HTML în `folder`. Returnează fișierele scrise.
+    pub fn export(&self, folder: &Path) -> Result<Vec<PathBuf>, String> {
+        let stem = format!(
+            "raport_{}_{}",
+            file_safe(&self.event),
+            self.generated_at.with_timezone(&Local).format("%Y%m%d_%H%M")
+        );
+        let files = [
+            (folder.join(format!("{}.csv", stem)), self.to_csv()),
+            (folder.join(format!("{}.json", stem)), serde_json::to_string_pretty(self).map_err(|e| e.to_string())?),
+            (folder.join(format!("{}.html", stem)), self.to_html()),
+        ];
+        for (path, content) in &files {
+            std::fs::write(path, content).map_err(|e| format!("Eroare scriere {}: {}", path.display(), e))?;
+        }
+        Ok(files.into_iter().map(|(path, _)| path).collect())
+    }
+
+    /// Un rând pe zi și fotograf, apoi trimiterile (coloanele care nu se aplică rămân goale)
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "tip,zi,nume,sursa,transferuri,fisiere,bytes,complete,partiale,erori,primul,ultimul\n",
+        );
+        for day in &self.days {
+            for p in &day.photographers {
+                let t = &p.totals;
+                let row = [
+                    "primit".to_string(),
+                    day.day.clone(),
+                    p.photographer.clone(),
+                    p.source.clone(),
+                    t.transfers.to_string(),
+                    t.files.to_string(),
+                    t.bytes.to_string(),
+                    t.complete.to_string(),
+                    t.partial.to_string(),
+                    t.failed.to_string(),
+                    t.first.as_ref().map(local_time).unwrap_or_default(),
+                    t.last.as_ref().map(local_time).unwrap_or_default(),
+                ];
+                csv_row(&mut csv, &row);
+            }
+        }
+        for s in &self.sent {
+            let row = [
+                "trimis".to_string(),
+                s.date.clone(),
+                s.target.clone(),
+                String::new(),
+                s.sends.to_string(),
+                s.files.to_string(),
+                s.bytes.to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+            ];
+            csv_row(&mut csv, &row);
+        }
+        csv
+    }
+
+    /// Pagină HTML fără resurse externe, de trimis sau printat ca atare
+    pub fn to_html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html lang=\"ro\">\n<head>\n<meta charset=\"utf-8\">\n<title>Raport {}</title>\n\
+             <style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n<p class=\"meta\">Receiver {} &middot; generat {}</p>\n",
+            escape(&self.event),
+            HTML_STYLE,
+            escape(&self.event),
+            escape(&self.station),
+            local_time(&self.generated_at)
+        );
+        html.push_str(&format!(
+            "<p class=\"total\">Total: {} transferuri &middot; {} fișiere &middot; {}{}</p>\n",
+            self.totals.transfers,
+            self.totals.files,
+            format_size(self.totals.bytes),
+            failures_text(&self.totals)
+        ));
+
+        for day in &self.days {
+            html.push_str(&format!(
+                "<h2>{}</h2>\n<table>\n<tr><th>Nume</th><th>Sursa</th><th>Transferuri</th><th>Fișiere</th>\
+                 <th>Mărime</th><th>Parțiale</th><th>Erori</th><th>Primul</th><th>Ultimul</th></tr>\n",
+                escape(&day.day)
+            ));
+            for p in &day.photographers {
+                html.push_str(&totals_row(&escape(&p.photographer), &escape(&p.source), &p.totals, ""));
+            }
+            html.push_str(&totals_row("Total zi", "", &day.totals, " class=\"day-total\""));
+            html.push_str("</table>\n");
+        }
+
+        if !self.sent.is_empty() {
+            html.push_str(
+                "<h2>Trimise</h2>\n<table>\n<tr><th>Data</th><th>Destinatar</th><th>Trimiteri</th>\
+                 <th>Fișiere</th><th>Mărime</th></tr>\n",
+            );
+            for s in &self.sent {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape(&s.date),
+                    escape(&s.target),
+                    s.sends,
+                    s.files,
+                    format_size(s.bytes)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+const HTML_STYLE: &str = "body{font-family:-apple-system,Segoe UI,Helvetica,sans-serif;margin:32px;color:#222}\
+h1{margin-bottom:4px}.meta{color:#777;margin-top:0}.total{font-weight:600}\
+table{border-collapse:collapse;width:100%;margin-bottom:24px}\
+th,td{border-bottom:1px solid #ddd;padding:6px 10px;text-align:left}\
+th{background:#f4f4f4}td.num{text-align:right}.failed{color:#c0392b;font-weight:600}\
+tr.day-total td{font-weight:600;border-top:2px solid #999}";
+
+fn totals_row(name: &str, source: &str, t: &Totals, class: &str) -> String {
+    let failed_class = |n: usize| if n > 0 { "num failed" } else { "num" };
+    format!(
+        "<tr{}><td>{}</td><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td>\
+         <td class=\"{}\">{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td></tr>\n",
+        class,
+        name,
+        source,
+        t.transfers,
+        t.files,
+        format_size(t.bytes),
+        failed_class(t.partial),
+        t.partial,
+        failed_class(t.failed),
+        t.failed,
+        t.first.as_ref().map(local_time).unwrap_or_default(),
+        t.last.as_ref().map(local_time).unwrap_or_default()
+    )
+}
+
+fn failures_text(t: &Totals) -> String {
+    if t.partial + t.failed == 0 {
+        return String::new();
+    }
+    format!(" &middot; <span class=\"failed\">{} parțiale, {} erori</span>", t.partial, t.failed)
+}
+
+fn csv_row(csv: &mut String, fields: &[String]) {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|f| {
+            if f.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f.clone()
+            }
+        })
+        .collect();
+    csv.push_str(&fields.join(","));
+    csv.push('\n');
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// "DAY 2" înaintea lui "DAY 10"
+pub fn day_order(day: &str) -> (String, u64) {
+    let prefix = day.trim_end_matches(|c: char| c.is_ascii_digit());
+    (prefix.to_string(), day[prefix.len()..].parse().unwrap_or(0))
+}
+
+fn local_date(timestamp: &DateTime<Utc>) -> String {
+    timestamp.with_timezone(&Local).format("%Y-%m-%d").to_string()
+}
+
+fn local_time(timestamp: &DateTime<Utc>) -> String {
+    timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+}
+
+fn format_size(bytes: u64) -> String {
+    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= GB {
+        format!("{:.2} GB", bytes as f64 / GB)
+    } else {
+        format!("{:.1} MB", bytes as f64 / MB)
+    }
+}
+
+// Numele evenimentului ajunge în numele fișierului
+fn file_safe(name: &str) -> String {
+    let safe: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if safe.is_empty() { "eveniment".to_string() } else { safe }
+}
@@ -0,0 +1,131 @@
+use crate::config::ReceiverConfig;
+use rand::Rng;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use transfer_protocol::{event_key_matches, fingerprint, KeyStore, TransferHeader};
+
+/// Cheia receiver-ului, dispozitivele asociate și PIN-ul afișat pentru asocieri noi
+pub struct Pairing {
+    pub keys: KeyStore,
+    pin: String,
+    persistent: bool, // false = cheile nu au putut fi salvate, asocierile țin până la închidere
+}
+
+pub type SharedPairing = Arc<Mutex<Pairing>>;
+
+impl Pairing {
+    pub fn keys_path() -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".porn_transfer_keys.json")
+    }
+
+    /// Cheile de pe disc sau, dacă fișierul nu poate fi citit (corupt, fără drepturi), chei noi:
+    /// receiver-ul pornește oricum, dar dispozitivele trebuie reasociate. Fișierul vechi rămâne
+    /// alături (.corrupt); dacă nici cheile noi nu pot fi salvate, ele rămân doar în memorie.
+    pub fn load_or_regenerate() -> Self {
+        let path = Self::keys_path();
+        let loaded = KeyStore::load_or_create(&path).or_else(|e| {
+            eprintln!("Cheile de criptare nu pot fi încărcate ({}) - se generează altele, asocierile se refac", e);
+            let _ = std::fs::rename(&path, path.with_extension("json.corrupt"));
+            KeyStore::load_or_create(&path)
+        });
+        let (keys, persistent) = match loaded {
+            Ok(keys) => (keys, true),
+            Err(e) => {
+                eprintln!("Cheile noi nu pot fi salvate ({}) - asocierile se păstrează doar până la închidere", e);
+                // Generarea nu depinde de disc
+                (KeyStore::generate().expect("generare chei"), false)
+            }
+        };
+        Self { keys, pin: generate_pin(), persistent }
+    }
+
+    pub fn pin(&self) -> &str {
+        &self.pin
+    }
+
+    /// PIN-ul e de unică folosință: se schimbă după fiecare încercare de asociere,
+    /// reușită sau nu, ca să nu poată fi ghicit prin încercări repetate
+    pub fn rotate_pin(&mut self) -> &str {
+        self.pin = generate_pin();
+        &self.pin
+    }
+
+    pub fn trust(&mut self, public_key: &str, name: &str) -> Result<(), String> {
+        self.keys.trust(public_key, name);
+        self.save()
+    }
+
+    pub fn forget(&mut self, public_key: &str) -> Result<(), String> {
+        self.keys.forget(public_key);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if !self.persistent {
+            return Ok(());
+        }
+        self.keys.save(&Self::keys_path())
+    }
+}
+
+fn generate_pin() -> String {
+    format!("{:06}", rand::rng().random_range(0..1_000_000))
+}
+
+/// Motivul pentru care un sender e refuzat înainte de ACK-ul "ready"
+pub struct Rejection {
+    pub status: &'static str, // "rejected", "passphrase_required" sau "wrong_passphrase"
+    pub reason: String,
+}
+
+/// Verifică listele de senderi și parola evenimentului. Un sender e identificat după
+/// numele din header sau după amprenta cheii (doar pe canalul criptat - numele poate fi
+/// ales de oricine, amprenta nu).
+pub fn check_sender(
+    config: &ReceiverConfig,
+    header: &TransferHeader,
+    remote_key: Option<&str>,
+) -> Result<(), Rejection> {
+    let key_fingerprint = remote_key.map(fingerprint);
+    let matches = |entry: &String| {
+        let entry = entry.trim();
+        !entry.is_empty()
+            && (entry.eq_ignore_ascii_case(header.photographer.trim())
+                || key_fingerprint.as_deref().is_some_and(|f| entry.eq_ignore_ascii_case(f)))
+    };
+
+    if config.blocked_senders.iter().any(matches) {
+        return Err(Rejection {
+            status: "rejected",
+            reason: format!("Receiver-ul {} a blocat acest sender", config.name),
+        });
+    }
+    if !config.allowed_senders.is_empty() && !config.allowed_senders.iter().any(matches) {
+        return Err(Rejection {
+            status: "rejected",
+            reason: format!("{} nu este pe lista de senderi acceptați de {}", header.photographer, config.name),
+        });
+    }
+
+    if !config.event_passphrase.trim().is_empty() {
+        match header.event_key.as_deref() {
+            None => {
+                return Err(Rejection {
+                    status: "passphrase_required",
+                    reason: format!("Receiver-ul {} cere parola evenimentului", config.name),
+                })
+            }
+            Some(key) if !event_key_matches(&config.event_passphrase, key) => {
+                return Err(Rejection {
+                    status: "wrong_passphrase",
+                    reason: "Parola evenimentului este greșită".to_string(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(())
+}
@@ -1,66 +1,213 @@
-use crate::config::{save_history, ReceiverConfig, TransferRecord, TransferStatus};
+use crate::config::{source_category, BackupCopy, FolderTransfer, ReceiverConfig, TransferRecord, TransferStatus};
+use crate::content_index::ContentIndex;
+use crate::files::{received_files, sync_dir};
+use crate::folder_template::{camera_model, Field, FolderTemplate};
+use crate::history::HistoryStore;
+use crate::manifest::{Manifest, ManifestSender, ManifestStation};
+use crate::security::{check_sender, SharedPairing};
+use crate::session::{ActiveTransfers, SessionRegistry, SessionState};
 use crate::TransferProgress;
 use chrono::Utc;
 use mdns_sd::{ServiceDaemon, ServiceInfo};
-use serde::{Deserialize, Serialize};
 use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
+use std::net::TcpStream;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tauri::Emitter;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::sync::{watch, Semaphore};
+use transfer_protocol::{
+    fingerprint, hash_prefix, is_part_file_name, keep_both_path, parse_hello, part_file_name, read_checksum, read_frame,
+    read_json, sanitize_name, secure_accept, send_auth_reply, write_json, AckResponse, Channel, DuplicateAction,
+    DuplicateInfo, FileAck, FileErrorCode, FileMetadata, Hello, HelloIntent, Negotiated, PartialFileInfo, ReceiverInfo,
+    SecureMode, SecureStream, SendDecision, TransferHeader, AUTH_OK, AUTH_PAIRED, AUTH_PAIRING_REQUIRED, CAP_CHECKSUM,
+    CAP_ENCRYPTION, CAP_FILE_RESULT, CAP_PARALLEL, CAP_RESUME, MAX_CHECKSUM_RETRIES, MAX_HEADER_LEN, MAX_HELLO_LEN,
+    PROTOCOL_MAGIC,
+};
 
 const TCP_TIMEOUT_SECS: u64 = 30;
+// Conexiuni servite simultan (un transfer paralel folosește până la 16)
+const MAX_CONNECTIONS: usize = 64;
+
+// Cât de des se verifică programul zilelor evenimentului
+const DAY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
 
 const SERVICE_TYPE: &str = "_phototransfer._tcp.local.";
 const CHUNK_SIZE: usize = 4 * 1024 * 1024; // 4 MB
 
-#[derive(Serialize, Deserialize)]
-struct TransferHeader {
-    photographer: String,
-    files: Vec<FileMetadata>,
-    is_folder_transfer: bool,
-    folder_name: Option<String>,
-    #[serde(default)]
-    sender_role: Option<String>, // "tagger", "editor", sau None pentru fotografii
+fn get_local_ip() -> Result<String, String> {
+    use std::net::UdpSocket;
+
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+    socket.connect("8.8.8.8:80").map_err(|e| e.to_string())?;
+    let addr = socket.local_addr().map_err(|e| e.to_string())?;
+    Ok(addr.ip().to_string())
 }
 
-#[derive(Serialize, Deserialize)]
-struct FileMetadata {
-    name: String,
-    #[serde(default)]
-    relative_path: String, // Calea relativă pentru structura subfolder
-    size: u64,
-    checksum: String,
+
+// Deschide un fișier parțial pentru continuare: taie ce depășește offset-ul
+// și returnează hasher-ul inițializat cu conținutul existent
+fn open_for_resume(path: &std::path::Path, offset: u64) -> std::io::Result<(std::fs::File, blake3::Hasher)> {
+    use std::io::{Seek, SeekFrom};
+
+    let hasher = hash_prefix(std::fs::File::open(path)?, offset)?;
+    let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+    file.set_len(offset)?;
+    file.seek(SeekFrom::Start(offset))?;
+    Ok((file, hasher))
 }
 
-#[derive(Serialize, Deserialize)]
-struct AckResponse {
-    status: String,
-    folder: Option<String>,
-    #[serde(default)]
-    duplicates: Vec<DuplicateInfo>,
-    #[serde(default)]
-    resume_folder: Option<String>, // Folderul existent pentru reluare transfer
+// Fișierele se scriu ca `.nume.part` și primesc numele final doar după verificare,
+// ca un fișier trunchiat de o pană de curent să nu pară complet
+fn part_path(path: &std::path::Path) -> std::path::PathBuf {
+    path.with_file_name(part_file_name(&path.file_name().unwrap_or_default().to_string_lossy()))
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-struct DuplicateInfo {
-    file_name: String,
-    existing_path: String,  // Unde există deja fișierul
-    existing_size: u64,
-    new_size: u64,
-    same_checksum: bool,    // True dacă checksumul e identic
+fn is_part_file(path: &std::path::Path) -> bool {
+    path.file_name().is_some_and(|name| is_part_file_name(&name.to_string_lossy()))
 }
 
-fn get_local_ip() -> Result<String, String> {
-    use std::net::UdpSocket;
+// Fișierul .part (cu hash-ul deja verificat) ajunge pe disc, i se verifică dimensiunea
+// și abia apoi primește numele final. Timpii și flag-urile sursei nu opresc transferul.
+fn commit_part_file(
+    file: std::fs::File,
+    part: &std::path::Path,
+    final_path: &std::path::Path,
+    file_meta: &FileMetadata,
+) -> std::io::Result<()> {
+    if let Err(e) = file_meta.attributes.apply_times(&file) {
+        eprintln!("Nu pot păstra data originală pentru {}: {}", file_meta.name, e);
+    }
+    file.sync_all()?;
+    let written = file.metadata()?.len();
+    if written != file_meta.size {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("pe disc sunt {} bytes în loc de {}", written, file_meta.size),
+        ));
+    }
+    drop(file);
 
-    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
-    socket.connect("8.8.8.8:80").map_err(|e| e.to_string())?;
-    let addr = socket.local_addr().map_err(|e| e.to_string())?;
-    Ok(addr.ip().to_string())
+    std::fs::rename(part, final_path)?;
+    if let Err(e) = file_meta.attributes.apply_flags(final_path) {
+        eprintln!("Nu pot păstra atributele pentru {}: {}", file_meta.name, e);
+    }
+    match final_path.parent() {
+        Some(parent) => sync_dir(parent),
+        None => Ok(()),
+    }
+}
+
+// Câți bytes lipsesc pe volumul folderului de bază pentru `needed` bytes plus rezerva.
+// Dacă spațiul liber nu poate fi citit, transferul nu e blocat.
+fn missing_space(base_path: &std::path::Path, needed: u64, reserve_mb: u64) -> Option<u64> {
+    if needed == 0 {
+        return None;
+    }
+
+    // Folderul de bază poate să nu existe încă - contează volumul pe care va fi creat
+    let existing = base_path.ancestors().find(|p| p.exists()).unwrap_or(base_path);
+    let available = match fs4::available_space(existing) {
+        Ok(available) => available,
+        Err(e) => {
+            eprintln!("Nu pot citi spațiul liber pentru {}: {}", existing.display(), e);
+            return None;
+        }
+    };
+
+    let required = needed.saturating_add(reserve_mb.saturating_mul(1024 * 1024));
+    required.checked_sub(available).filter(|&missing| missing > 0)
 }
 
+// Calea copiei de backup: aceeași cale relativă la folderul de bază, sub volumul de backup
+fn mirror_path(root: &str, base_path: &std::path::Path, path: &std::path::Path) -> Option<std::path::PathBuf> {
+    path.strip_prefix(base_path).ok().map(|relative| std::path::Path::new(root).join(relative))
+}
+
+// Copiază fișierul primit (deja verificat) pe un volum de backup. Copia e citită înapoi și
+// comparată cu hash-ul originalului înainte de a primi numele final.
+fn mirror_file(
+    source: &std::path::Path,
+    target: &std::path::Path,
+    checksum: &blake3::Hash,
+    file_meta: &FileMetadata,
+) -> std::io::Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let part = part_path(target);
+    let copied = (|| -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&part)?;
+        std::io::copy(&mut std::fs::File::open(source)?, &mut file)?;
+        file.sync_all()?;
+        if hash_prefix(std::fs::File::open(&part)?, file_meta.size)?.finalize() != *checksum {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "copia diferă de original"));
+        }
+        commit_part_file(file, &part, target, file_meta)
+    })();
+    if copied.is_err() {
+        let _ = std::fs::remove_file(&part);
+    }
+    copied
+}
+
+// Starea copiilor de backup ale folderului, pentru istoric
+fn backup_copies(
+    config: &ReceiverConfig,
+    base_path: &std::path::Path,
+    folder: &std::path::Path,
+    file_count: usize,
+    state: &SessionState,
+) -> Vec<BackupCopy> {
+    config
+        .backup_paths
+        .iter()
+        .map(|root| {
+            let mirror = mirror_path(root, base_path, folder);
+            let (copied, size) = mirror.as_deref().map(count_files_recursive).unwrap_or((0, 0));
+            let error = state.backup_error(root);
+            let status = if error.is_some() {
+                TransferStatus::Error
+            } else if copied < file_count {
+                TransferStatus::Partial
+            } else {
+                TransferStatus::Complete
+            };
+            BackupCopy {
+                root: root.clone(),
+                folder: mirror.map(|p| p.to_string_lossy().to_string()).unwrap_or_default(),
+                file_count: copied,
+                total_size: size,
+                status,
+                error,
+            }
+        })
+        .collect()
+}
+
+// Găsește fișierele incomplete (.part) din folderul de reluare. Un .part complet (întrerupt
+// înainte de redenumire) e raportat și el - senderul trimite doar hash-ul.
+fn find_partial_files(folder_path: &std::path::Path, files: &[FileMetadata]) -> Vec<PartialFileInfo> {
+    files
+        .iter()
+        .filter_map(|file_meta| {
+            let path = part_path(&folder_path.join(file_meta.local_path().ok()?));
+            let existing_size = std::fs::metadata(&path).ok()?.len();
+            if existing_size == 0 || existing_size > file_meta.size {
+                return None;
+            }
+
+            let hasher = hash_prefix(std::fs::File::open(&path).ok()?, existing_size).ok()?;
+            Some(PartialFileInfo {
+                file_name: file_meta.key().to_string(),
+                bytes_received: existing_size,
+                prefix_checksum: hasher.finalize().to_hex().to_string(),
+            })
+        })
+        .collect()
+}
 
 // Caută un folder temporar existent pentru acest fotograf (pentru reluare transfer)
 // Folderele temporare au format: .tmp_{photographer}_{timestamp}
@@ -69,7 +216,7 @@ fn find_temp_folder(base_path: &std::path::Path, photographer: &str) -> Option<s
         return None;
     }
 
-    let prefix = format!(".tmp_{}_", photographer.to_lowercase().replace(' ', "_"));
+    let prefix = format!(".tmp_{}_", temp_name_part(photographer));
 
     if let Ok(entries) = std::fs::read_dir(base_path) {
         let mut matching_folders: Vec<_> = entries
@@ -93,27 +240,16 @@ fn find_temp_folder(base_path: &std::path::Path, photographer: &str) -> Option<s
     }
 }
 
-// Numără fișierele recursiv într-un folder (include subfoldere)
+// Numără fișierele complete recursiv într-un folder (include subfoldere, fără .part și fișiere ascunse)
 fn count_files_recursive(path: &std::path::Path) -> (usize, u64) {
-    let mut count = 0usize;
-    let mut size = 0u64;
-
-    if let Ok(entries) = std::fs::read_dir(path) {
-        for entry in entries.filter_map(|e| e.ok()) {
-            let entry_path = entry.path();
-            if entry_path.is_file() {
-                count += 1;
-                size += entry.metadata().map(|m| m.len()).unwrap_or(0);
-            } else if entry_path.is_dir() {
-                // Recursiv în subfolder
-                let (sub_count, sub_size) = count_files_recursive(&entry_path);
-                count += sub_count;
-                size += sub_size;
-            }
-        }
-    }
+    let files = received_files(path);
+    let size = files.iter().filter_map(|f| std::fs::metadata(f).ok()).map(|m| m.len()).sum();
+    (files.len(), size)
+}
 
-    (count, size)
+// Numele fotografului sau al folderului, așa cum apare în numele folderului temporar
+fn temp_name_part(name: &str) -> String {
+    sanitize_name(name).to_lowercase().replace(' ', "_")
 }
 
 // Generează un nume de folder temporar unic
@@ -121,7 +257,7 @@ fn count_files_recursive(path: &std::path::Path) -> (usize, u64) {
 fn generate_temp_folder_name(photographer: &str, folder_name: Option<&str>) -> String {
     use rand::Rng;
 
-    let sanitized = photographer.to_lowercase().replace(' ', "_");
+    let sanitized = temp_name_part(photographer);
     let timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .map(|d| d.as_millis())
@@ -131,7 +267,7 @@ fn generate_temp_folder_name(photographer: &str, folder_name: Option<&str>) -> S
     let random_id: u32 = rand::rng().random_range(1000..9999);
 
     if let Some(fname) = folder_name {
-        let folder_sanitized = fname.to_lowercase().replace(' ', "_");
+        let folder_sanitized = temp_name_part(fname);
         format!(".tmp_{}_{}_{}_{}", sanitized, folder_sanitized, timestamp, random_id)
     } else {
         format!(".tmp_{}_{}_{}", sanitized, timestamp, random_id)
@@ -180,7 +316,7 @@ fn scan_for_temp_folders(path: &std::path::Path, results: &mut Vec<TempFolderInf
                     let mut count = 0usize;
                     let mut size = 0u64;
                     for f in files.filter_map(|e| e.ok()) {
-                        if f.path().is_file() {
+                        if f.path().is_file() && !is_part_file(&f.path()) {
                             count += 1;
                             size += f.metadata().map(|m| m.len()).unwrap_or(0);
                         }
@@ -211,64 +347,7 @@ pub struct TempFolderInfo {
     pub day: Option<String>,
 }
 
-// Caută duplicate în toată ziua curentă (toate folderele) - doar după nume
-fn find_duplicates_in_day(
-    base_path: &std::path::Path,
-    day_folder: Option<&str>,
-    files: &[FileMetadata],
-    exclude_folder: Option<&std::path::Path>,
-) -> Vec<DuplicateInfo> {
-    let mut duplicates = Vec::new();
-
-    let search_path = if let Some(day) = day_folder {
-        base_path.join(day)
-    } else {
-        base_path.to_path_buf()
-    };
-
-    if !search_path.exists() {
-        return duplicates;
-    }
-
-    // Parcurge toate folderele din ziua curentă
-    if let Ok(entries) = std::fs::read_dir(&search_path) {
-        for entry in entries.filter_map(|e| e.ok()) {
-            let folder_path = entry.path();
-
-            // Skip folderul curent (exclude_folder)
-            if let Some(exclude) = exclude_folder {
-                if folder_path == exclude {
-                    continue;
-                }
-            }
-
-            if folder_path.is_dir() {
-                // Verifică fiecare fișier din transfer - doar după nume
-                for file_meta in files {
-                    let potential_duplicate = folder_path.join(&file_meta.name);
-                    if potential_duplicate.exists() {
-                        if let Ok(metadata) = std::fs::metadata(&potential_duplicate) {
-                            // Verificare doar după nume - same_checksum = true dacă dimensiunea e aceeași
-                            let same_size = metadata.len() == file_meta.size;
-
-                            duplicates.push(DuplicateInfo {
-                                file_name: file_meta.name.clone(),
-                                existing_path: folder_path.to_string_lossy().to_string(),
-                                existing_size: metadata.len(),
-                                new_size: file_meta.size,
-                                same_checksum: same_size, // Folosim dimensiunea ca aproximare
-                            });
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    duplicates
-}
-
-// Verifică duplicate în folderul curent de transfer - doar după nume (instant)
+// Verifică duplicate în folderul curent de transfer - doar după cale (instant)
 fn check_duplicates_in_folder(
     folder_path: &std::path::Path,
     files: &[FileMetadata],
@@ -278,18 +357,19 @@ fn check_duplicates_in_folder(
     files
         .iter()
         .filter_map(|file_meta| {
-            let file_path = std::path::Path::new(&folder_path_str).join(&file_meta.name);
+            let file_path = folder_path.join(file_meta.local_path().ok()?);
             if file_path.exists() {
                 if let Ok(metadata) = std::fs::metadata(&file_path) {
                     // Verificare doar după nume - same_checksum = true dacă dimensiunea e aceeași
                     let same_size = metadata.len() == file_meta.size;
 
                     return Some(DuplicateInfo {
-                        file_name: file_meta.name.clone(),
+                        file_name: file_meta.key().to_string(),
                         existing_path: folder_path_str.clone(),
                         existing_size: metadata.len(),
                         new_size: file_meta.size,
                         same_checksum: same_size,
+                        existing_name: None,
                     });
                 }
             }
@@ -298,13 +378,16 @@ fn check_duplicates_in_folder(
         .collect()
 }
 
-pub fn run_server(
+#[allow(clippy::too_many_arguments)]
+pub async fn run_server(
     port: u16,
     config: ReceiverConfig,
     config_state: Arc<Mutex<ReceiverConfig>>,
-    history: Arc<Mutex<Vec<TransferRecord>>>,
-    is_running: Arc<Mutex<bool>>,
-    is_cancelled: Arc<std::sync::atomic::AtomicBool>,
+    history: HistoryStore,
+    mut stop: watch::Receiver<bool>,
+    active: ActiveTransfers,
+    index: ContentIndex,
+    pairing: SharedPairing,
     window: tauri::Window,
 ) -> Result<(), String> {
     // Start mDNS registration
@@ -314,6 +397,9 @@ pub fn run_server(
     let mut properties = std::collections::HashMap::new();
     properties.insert("role".to_string(), config.role.clone());
     properties.insert("name".to_string(), config.name.clone());
+    // Cheia publică identifică receiver-ul și după redenumire (numele nu e unic)
+    let public_key = pairing.lock().map_err(|e| e.to_string())?.keys.identity.public_key.clone();
+    properties.insert("key".to_string(), public_key);
 
     // Get local IP address
     let local_ip = get_local_ip().unwrap_or_else(|_| "0.0.0.0".to_string());
@@ -337,151 +423,394 @@ pub fn run_server(
     let _ = window.emit("server-started", port);
 
     // Start TCP listener
-    let listener = TcpListener::bind(format!("0.0.0.0:{}", port)).map_err(|e| e.to_string())?;
-    listener
-        .set_nonblocking(true)
-        .map_err(|e| e.to_string())?;
+    let listener = TcpListener::bind(format!("0.0.0.0:{}", port)).await.map_err(|e| e.to_string())?;
+
+    // Transferurile paralele (mai multe conexiuni pentru același transfer)
+    let sessions = SessionRegistry::new();
+    let slots = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+
+    // Ziua evenimentului trece singură după program, și fără conexiuni noi
+    let day_watch = tokio::spawn(watch_event_day(Arc::clone(&config_state), window.clone()));
 
     loop {
-        // Check if should stop
-        {
-            let running = is_running.lock().map_err(|e| e.to_string())?;
-            if !*running {
-                break;
-            }
-        }
+        // Backpressure: cu toate locurile ocupate nu mai acceptăm, conexiunile noi așteaptă
+        // în coada listener-ului până se termină una din cele în curs
+        let slot = tokio::select! {
+            _ = stopped(&mut stop) => break,
+            slot = Arc::clone(&slots).acquire_owned() => slot.map_err(|e| e.to_string())?,
+        };
 
-        // Try to accept connection
-        match listener.accept() {
-            Ok((stream, addr)) => {
-                println!("=== Conexiune nouă de la: {} ===", addr);
+        let (stream, addr) = tokio::select! {
+            _ = stopped(&mut stop) => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    eprintln!("Accept error: {}", e);
+                    // Ex. prea multe fișiere deschise - nu reîncerca imediat
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+        };
+        println!("=== Conexiune nouă de la: {} ===", addr);
 
-                // Clone-uri pentru thread-ul nou
-                let config = {
-                    let c = config_state.lock().map_err(|e| e.to_string())?;
-                    c.clone()
-                };
-                let config_state_clone = Arc::clone(&config_state);
-                let history_clone = Arc::clone(&history);
-                let window_clone = window.clone();
-
-                // Fiecare transfer are propriul cancel flag
-                let transfer_cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
-
-                // Spawn thread dedicat pentru această conexiune
-                // Permite transferuri simultane de la mai mulți fotografi
-                std::thread::spawn(move || {
-                    match handle_connection(
-                        stream,
-                        config,
-                        &config_state_clone,
-                        &history_clone,
-                        &transfer_cancelled,
-                        &window_clone,
-                    ) {
-                        Ok(()) => {
-                            println!("=== Conexiune finalizată cu succes de la {} ===", addr);
-                        }
-                        Err(e) => {
-                            eprintln!("!!! Eroare conexiune de la {}: {} !!!", addr, e);
-                            let _ = window_clone.emit("transfer-error", e.to_string());
-                        }
-                    }
-                });
-            }
-            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                // No connection waiting, sleep a bit
-                std::thread::sleep(std::time::Duration::from_millis(100));
-            }
-            Err(e) => {
-                eprintln!("Accept error: {}", e);
+        // Clone-uri pentru task-ul nou. Transferul rămâne în ziua în care a început.
+        refresh_event_day(&config_state, &window);
+        let config = {
+            let c = config_state.lock().map_err(|e| e.to_string())?;
+            c.clone()
+        };
+        let config_state_clone = Arc::clone(&config_state);
+        let history_clone = history.clone();
+        let window_clone = window.clone();
+        let sessions_clone = sessions.clone();
+        let pairing_clone = Arc::clone(&pairing);
+        let active_clone = active.clone();
+        let index_clone = index.clone();
+
+        // Task dedicat pentru această conexiune
+        // Permite transferuri simultane de la mai mulți fotografi
+        tokio::spawn(async move {
+            let _slot = slot; // Eliberat când conexiunea se încheie
+            match serve_connection(
+                stream,
+                config,
+                config_state_clone,
+                history_clone,
+                sessions_clone,
+                pairing_clone,
+                active_clone,
+                index_clone,
+                window_clone.clone(),
+            )
+            .await
+            {
+                Ok(()) => {
+                    println!("=== Conexiune finalizată cu succes de la {} ===", addr);
+                }
+                Err(e) => {
+                    eprintln!("!!! Eroare conexiune de la {}: {} !!!", addr, e);
+                    let _ = window_clone.emit("transfer-error", e.to_string());
+                }
             }
-        }
+        });
     }
 
     // Cleanup
+    day_watch.abort();
     let _ = mdns.shutdown();
     let _ = window.emit("server-stopped", ());
 
     Ok(())
 }
 
+/// Aplică programul evenimentului și anunță UI-ul dacă ziua s-a schimbat
+pub fn refresh_event_day(config_state: &Mutex<ReceiverConfig>, window: &tauri::Window) {
+    let changed = config_state.lock().ok().and_then(|mut c| c.refresh_day());
+    if let Some(day) = changed {
+        println!("Ziua evenimentului: {}", day);
+        let _ = window.emit("day-changed", &day);
+    }
+}
+
+async fn watch_event_day(config_state: Arc<Mutex<ReceiverConfig>>, window: tauri::Window) {
+    let mut interval = tokio::time::interval(DAY_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        refresh_event_day(&config_state, &window);
+    }
+}
+
+// Se termină când serverul e oprit (sau când AppState a renunțat la semnalul de oprire)
+async fn stopped(stop: &mut watch::Receiver<bool>) {
+    let _ = stop.wait_for(|stopped| *stopped).await;
+}
+
+/// Primul frame e citit asincron și cu timeout, ca o conexiune care nu trimite nimic să nu țină
+/// ocupat un thread. Restul protocolului (Noise, fișiere, checksum) e încă `std::io` blocant și rulează
+/// pe pool-ul de thread-uri blocante al lui tokio, nu pe worker-ii runtime-ului: fiecare transfer activ
+/// ține un thread din pool, iar timeout-urile sunt cele de socket (`TCP_TIMEOUT_SECS`). Trecerea lui
+/// `handle_connection` pe I/O async (backpressure fără thread-uri) nu e făcută încă.
+#[allow(clippy::too_many_arguments)]
+async fn serve_connection(
+    mut tcp: tokio::net::TcpStream,
+    config: ReceiverConfig,
+    config_state: Arc<Mutex<ReceiverConfig>>,
+    history: HistoryStore,
+    sessions: SessionRegistry,
+    pairing: SharedPairing,
+    active: ActiveTransfers,
+    index: ContentIndex,
+    window: tauri::Window,
+) -> Result<(), String> {
+    let allow_legacy = config.allow_legacy_senders;
+    let first_frame = read_first_frame(&mut tcp, allow_legacy);
+    let first_frame = tokio::time::timeout(Duration::from_secs(TCP_TIMEOUT_SECS), first_frame)
+        .await
+        .map_err(|_| "Timeout la citirea header-ului".to_string())?
+        .map_err(|e| format!("Eroare citire header: {}", e))?;
+
+    let tcp = tcp.into_std().map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || {
+        handle_connection(
+            tcp,
+            first_frame,
+            config,
+            &config_state,
+            &history,
+            &sessions,
+            &pairing,
+            &active,
+            &index,
+            &window,
+        )
+    })
+    .await
+    .map_err(|e| format!("Conexiune oprită neașteptat: {}", e))?
+}
+
+// Același format ca `read_frame`: lungime u32 big endian + payload. Primul frame e citit înainte
+// de orice autentificare, deci e mic (preambul sau INFO). Doar senderii vechi trimit direct header-ul,
+// care poate fi mare - acceptat doar dacă transferurile necriptate sunt permise.
+async fn read_first_frame(tcp: &mut tokio::net::TcpStream, allow_legacy: bool) -> std::io::Result<Vec<u8>> {
+    let too_large = |len: usize| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Frame prea mare: {} bytes", len))
+    };
+
+    let len = tcp.read_u32().await? as usize;
+    if len <= MAX_HELLO_LEN {
+        let mut buf = vec![0u8; len];
+        tcp.read_exact(&mut buf).await?;
+        return Ok(buf);
+    }
+    if !allow_legacy || len > MAX_HEADER_LEN {
+        return Err(too_large(len));
+    }
+
+    let mut buf = vec![0u8; PROTOCOL_MAGIC.len()];
+    tcp.read_exact(&mut buf).await?;
+    if buf == PROTOCOL_MAGIC.as_slice() {
+        return Err(too_large(len)); // Preambul umflat, nu un header vechi
+    }
+    // Memoria crește doar cu datele primite efectiv, nu cu lungimea anunțată
+    (&mut *tcp).take((len - buf.len()) as u64).read_to_end(&mut buf).await?;
+    if buf.len() != len {
+        let msg = "Conexiune închisă în timpul header-ului";
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, msg));
+    }
+    Ok(buf)
+}
+
+// Receiver-ul nu poate salva fișierul: consumă restul datelor lui (și hash-ul), ca senderul să
+// ajungă la confirmare și să afle exact de ce s-a oprit transferul
+fn reject_file<S: Read + Write>(
+    stream: &mut S,
+    remaining: u64,
+    use_checksum: bool,
+    ack: &FileAck,
+) -> std::io::Result<()> {
+    std::io::copy(&mut (&mut *stream).take(remaining), &mut std::io::sink())?;
+    if use_checksum {
+        read_checksum(stream)?;
+    }
+    ack.write_to(stream, true)
+}
+
+// Duplicat sărit de politica receiver-ului: senderul îl trimite oricum, datele se citesc și se aruncă
+fn skip_file<S: Read>(stream: &mut S, size: u64, use_checksum: bool) -> std::io::Result<(u64, blake3::Hash)> {
+    let mut hasher = blake3::Hasher::new();
+    let skipped = std::io::copy(&mut (&mut *stream).take(size), &mut hasher)?;
+    if skipped < size {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Conexiune închisă prematur"));
+    }
+    if use_checksum {
+        read_checksum(stream)?;
+    }
+    Ok((skipped, hasher.finalize()))
+}
+
+/// Handshake-ul criptat după HelloReply. Returnează None dacă conexiunea s-a încheiat aici:
+/// asociere cu PIN (intent `Pair`) sau dispozitiv neasociat care trebuie întâi asociat.
+fn accept_secure(
+    tcp: TcpStream,
+    hello: &Hello,
+    negotiated: &Negotiated,
+    config: &ReceiverConfig,
+    pairing: &SharedPairing,
+    window: &tauri::Window,
+) -> Result<Option<SecureStream<TcpStream>>, String> {
+    let (identity, pin) = {
+        let pairing = pairing.lock().map_err(|e| e.to_string())?;
+        (pairing.keys.identity.clone(), pairing.pin().to_string())
+    };
+
+    if hello.intent == HelloIntent::Pair {
+        let result = secure_accept(tcp, &identity, SecureMode::Pin(&pin), negotiated);
+
+        let mut pairing = pairing.lock().map_err(|e| e.to_string())?;
+        let new_pin = pairing.rotate_pin().to_string();
+        let _ = window.emit("pairing-pin", new_pin);
+
+        let mut secure = result.map_err(|e| format!("Asociere eșuată pentru {}: {}", hello.name, e))?;
+        pairing.trust(secure.remote_key(), &hello.name)?;
+        send_auth_reply(&mut secure, AUTH_PAIRED, &config.name)?;
+
+        println!("Dispozitiv asociat: {} ({})", hello.name, fingerprint(secure.remote_key()));
+        let _ = window.emit("device-paired", hello.name.clone());
+        return Ok(None);
+    }
+
+    let mut secure = secure_accept(tcp, &identity, SecureMode::Paired, negotiated)?;
+
+    let trusted = pairing.lock().map_err(|e| e.to_string())?.keys.is_trusted(secure.remote_key());
+    if !trusted {
+        let _ = send_auth_reply(&mut secure, AUTH_PAIRING_REQUIRED, &config.name);
+        println!("Dispozitiv neasociat: {} ({})", hello.name, fingerprint(secure.remote_key()));
+        // UI-ul afișează PIN-ul ca să poată fi introdus pe sender
+        let _ = window.emit("pairing-requested", hello.name.clone());
+        return Ok(None);
+    }
+
+    send_auth_reply(&mut secure, AUTH_OK, &config.name)?;
+    Ok(Some(secure))
+}
+
+#[allow(clippy::too_many_arguments)]
 fn handle_connection(
-    mut stream: TcpStream,
+    mut tcp: TcpStream,
+    first_frame: Vec<u8>,
     config: ReceiverConfig,
     config_state: &Arc<Mutex<ReceiverConfig>>,
-    history: &Arc<Mutex<Vec<TransferRecord>>>,
-    is_cancelled: &Arc<std::sync::atomic::AtomicBool>,
+    history: &HistoryStore,
+    sessions: &SessionRegistry,
+    pairing: &SharedPairing,
+    active: &ActiveTransfers,
+    index: &ContentIndex,
     window: &tauri::Window,
 ) -> Result<(), String> {
-    stream.set_nonblocking(false).map_err(|e| e.to_string())?;
-    stream.set_nodelay(true).map_err(|e| e.to_string())?;
+    tcp.set_nonblocking(false).map_err(|e| e.to_string())?;
+    tcp.set_nodelay(true).map_err(|e| e.to_string())?;
 
     // Set timeout pentru a detecta deconectări
-    stream.set_read_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECS))).map_err(|e| e.to_string())?;
-    stream.set_write_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECS))).map_err(|e| e.to_string())?;
-
-    // Read header length
-    let mut len_buf = [0u8; 4];
-    stream
-        .read_exact(&mut len_buf)
-        .map_err(|e| format!("Eroare citire lungime header: {}", e))?;
-    let header_len = u32::from_be_bytes(len_buf) as usize;
-
-    // If header_len is 0, this is an INFO request
-    if header_len == 0 {
-        let info = serde_json::json!({
-            "name": config.name,
-            "role": config.role,
-        });
-        let info_json = serde_json::to_string(&info).map_err(|e| e.to_string())?;
-        let info_bytes = info_json.as_bytes();
-        stream
-            .write_all(&(info_bytes.len() as u32).to_be_bytes())
-            .map_err(|e| format!("Eroare trimitere info len: {}", e))?;
-        stream
-            .write_all(info_bytes)
+    tcp.set_read_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECS))).map_err(|e| e.to_string())?;
+    tcp.set_write_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECS))).map_err(|e| e.to_string())?;
+
+    // Primul frame (citit deja): preambulul protocolului versionat, header-ul direct
+    // (senderi vechi) sau un frame gol (INFO request)
+    let (mut stream, negotiated, header_buf) = match parse_hello(&first_frame) {
+        Some(hello) => {
+            let hello = hello?;
+            let reply = hello.answer(&config.name, &config.role);
+            write_json(&mut tcp, &reply)
+                .map_err(|e| format!("Eroare trimitere handshake: {}", e))?;
+
+            if let Some(ref error) = reply.error {
+                eprintln!("Handshake refuzat: {}", error);
+                let _ = window.emit("transfer-error", error.clone());
+                return Ok(());
+            }
+            if hello.intent == HelloIntent::Info {
+                return Ok(());
+            }
+
+            println!("Protocol v{} negociat, capabilități: {:?}", reply.version, reply.capabilities);
+            let negotiated = reply.negotiated();
+
+            // Tot ce urmează după handshake trece prin canalul criptat (dacă ambele părți îl suportă)
+            let mut stream = if negotiated.has(CAP_ENCRYPTION) {
+                match accept_secure(tcp, &hello, &negotiated, &config, pairing, window)? {
+                    Some(secure) => Channel::Secure(Box::new(secure)),
+                    None => return Ok(()), // Asociere încheiată sau dispozitiv neasociat
+                }
+            } else if hello.intent == HelloIntent::Pair {
+                return Err("Asocierea cere un canal criptat".to_string());
+            } else {
+                Channel::Plain(tcp)
+            };
+
+            // Header-ul urmează după handshake
+            let header_buf = read_frame(&mut stream, MAX_HEADER_LEN)
+                .map_err(|e| format!("Eroare citire header: {}", e))?;
+            (stream, negotiated, header_buf)
+        }
+        None => (Channel::Plain(tcp), Negotiated::legacy(), first_frame),
+    };
+
+    // Frame gol = INFO request
+    if header_buf.is_empty() {
+        let info = ReceiverInfo {
+            name: config.name.clone(),
+            role: config.role.clone(),
+        };
+        write_json(&mut stream, &info)
             .map_err(|e| format!("Eroare trimitere info: {}", e))?;
         return Ok(());
     }
 
-    // Read header
-    let mut header_buf = vec![0u8; header_len];
-    stream
-        .read_exact(&mut header_buf)
-        .map_err(|e| format!("Eroare citire header: {}", e))?;
+    // Fișierele nu circulă în clar decât dacă receiver-ul permite explicit senderii vechi
+    if !config.allow_legacy_senders && !stream.is_encrypted() {
+        let refusal = AckResponse::refused(
+            "encryption_required",
+            "Receiver-ul acceptă doar transferuri criptate. Actualizați aplicația sender.",
+        );
+        let _ = write_json(&mut stream, &refusal);
+        return Err("Transfer necriptat refuzat - actualizați aplicația sender".to_string());
+    }
 
     let header: TransferHeader =
         serde_json::from_slice(&header_buf).map_err(|e| format!("Eroare parsare header: {}", e))?;
 
+    // Toate numele din header ajung în căi pe disc: "../", căi absolute, discuri Windows sau nume
+    // rezervate ar scrie în afara folderului de bază (sau ar eșua la jumătatea transferului)
+    if let Err(reason) = header.check_paths() {
+        println!("Header refuzat de la {:?}: {}", header.photographer, reason);
+        let _ = write_json(&mut stream, &AckResponse::refused("invalid_path", &reason));
+        let _ = window.emit("transfer-rejected", format!("{:?}: {}", header.photographer, reason));
+        return Ok(());
+    }
+
+    // Parola evenimentului + lista de senderi, înainte de orice acces la folderele evenimentului
+    if let Err(rejection) = check_sender(&config, &header, stream.remote_key()) {
+        println!("Sender refuzat: {} ({})", header.photographer, rejection.reason);
+        let _ = write_json(&mut stream, &AckResponse::refused(rejection.status, &rejection.reason));
+        let _ = window.emit("transfer-rejected", format!("{}: {}", header.photographer, rejection.reason));
+        return Ok(());
+    }
+
     // Determină categoria în funcție de sender_role
     // Pentru editori: organizare în subfoldere Fotograf/Tagger/Editor
-    let source_category = match header.sender_role.as_deref() {
-        Some("tagger") => "Tagger",
-        Some("editor") => "Editor",
-        _ => "Fotograf", // fotografii sau oricine altcineva
+    let source_category = source_category(header.sender_role.as_deref());
+
+    // Transfer paralel: prima conexiune a sesiunii alege folderul temporar, celelalte îl folosesc
+    let session = match header.session {
+        Some(ref info) if negotiated.has(CAP_PARALLEL) => Some(sessions.join(info, &header.photographer, &config, || {
+            let search_base = config.transfer_base(source_category);
+            let existing = find_temp_folder(&search_base, &header.photographer);
+            let temp_path = existing.clone().unwrap_or_else(|| {
+                search_base.join(generate_temp_folder_name(&header.photographer, header.folder_name.as_deref()))
+            });
+            (temp_path, existing)
+        })?),
+        _ => None,
     };
 
-    // Determină base_path și day_folder pentru căutarea duplicatelor
-    let base_path = std::path::PathBuf::from(&config.base_path);
-    let day_folder = if config.use_day_folders && config.role == "tagger" {
-        Some(config.current_day.as_str())
-    } else {
-        None
+    // Toată sesiunea folosește setările de la începutul ei: dacă între timp s-a comutat profilul,
+    // folderul, numele și contorul rămân ale evenimentului în care a început transferul
+    let config = match session {
+        Some(ref conn) => conn.state.config.clone(),
+        None => config,
     };
 
-    // Calculează calea de căutare pentru foldere existente
-    let search_base = if config.role == "editor" {
-        base_path.join(source_category)
-    } else if let Some(day) = day_folder {
-        base_path.join(day)
-    } else {
-        base_path.clone()
-    };
+    // Determină base_path și calea de căutare pentru foldere existente (și duplicate)
+    let base_path = std::path::PathBuf::from(&config.base_path);
+    let search_base = config.transfer_base(source_category);
 
     // Caută folder TEMPORAR existent pentru acest fotograf (pentru reluare transfer)
     // Nu căutăm foldere finalizate - doar temporare pentru a relua transferul întrerupt
-    let existing_temp_folder = find_temp_folder(&search_base, &header.photographer);
+    let existing_temp_folder = match session {
+        Some(ref conn) => conn.state.resume_folder.clone(),
+        None => find_temp_folder(&search_base, &header.photographer),
+    };
 
     // Dacă există folder temporar, îl folosim pentru reluare
     let (resume_temp_folder, is_resume) = if let Some(ref temp) = existing_temp_folder {
@@ -497,82 +826,102 @@ fn handle_connection(
         Vec::new()
     };
 
-    // Verifică duplicate în toată ziua (doar pentru taggeri sau în alte foldere pentru editori)
-    // IMPORTANT: Nu căutăm duplicate în folderele finalizate - doar în folderul temporar curent
-    let day_duplicates = if config.role == "tagger" {
-        find_duplicates_in_day(&base_path, day_folder, &header.files, resume_temp_folder.as_deref())
-    } else if config.role == "editor" {
-        find_duplicates_in_day(&search_base, None, &header.files, resume_temp_folder.as_deref())
-    } else {
-        Vec::new()
-    };
+    // Duplicate din indexul de conținut: același hash oriunde în eveniment, același nume doar în
+    // ziua curentă (taggeri) sau în categoria sursei (editori).
+    // Folderul sesiunii poate exista deja (creat de altă conexiune) - nu e sursă de duplicate
+    let own_temp_folder = session.as_ref().map(|conn| conn.state.temp_path.clone()).or(resume_temp_folder.clone());
+    let day_duplicates = index.find_duplicates(&base_path, &search_base, &header.files, own_temp_folder.as_deref());
 
-    // Adaugă duplicatele din ziuă (care nu sunt deja în lista din folderul curent)
+    // Adaugă duplicatele din index (care nu sunt deja în lista din folderul curent)
     for dup in day_duplicates {
         if !all_duplicates.iter().any(|d| d.file_name == dup.file_name) {
             all_duplicates.push(dup);
         }
     }
 
+    // Capabilități negociate în handshake (senderii vechi nu au niciuna)
+    let use_checksum = negotiated.has(CAP_CHECKSUM);
+    let use_resume = use_checksum && negotiated.has(CAP_RESUME);
+    let use_file_result = negotiated.has(CAP_FILE_RESULT);
+
+    // Reluarea la nivel de byte cere verificare prin hash
+    let partial_files = match resume_temp_folder {
+        Some(ref temp_folder) if use_resume => find_partial_files(temp_folder, &header.files),
+        _ => Vec::new(),
+    };
+
+    // Fișierele parțiale nu sunt duplicate - se continuă de unde au rămas
+    all_duplicates.retain(|d| {
+        !header
+            .files
+            .iter()
+            .any(|f| f.key() == d.file_name && partial_files.iter().any(|p| p.file_name == f.key()))
+    });
+
+    // Spațiul liber se verifică înainte de ACK: un disc plin descoperit la jumătatea unui fișier
+    // înseamnă un transfer parțial și un fotograf care a așteptat degeaba
+    let needed = match session {
+        Some(ref conn) if !conn.is_first => 0, // Prima conexiune a verificat pentru toată sesiunea
+        Some(ref conn) => conn.state.total_bytes,
+        None => header.files.iter().map(|f| f.size).sum(),
+    }
+    .saturating_sub(partial_files.iter().map(|p| p.bytes_received).sum());
+    if let Some(missing) = missing_space(&base_path, needed, config.disk_reserve_mb) {
+        let ack = AckResponse::insufficient_space(missing);
+        println!("Spațiu insuficient pentru {}: lipsesc {} bytes", header.photographer, missing);
+        let _ = write_json(&mut stream, &ack);
+        let _ = window.emit("transfer-rejected", format!("{}: {}", header.photographer, ack.refusal()));
+        return Ok(());
+    }
+
     // Send ACK cu informații despre duplicate și folder temporar de reluare
     let ack = AckResponse {
         status: "ready".to_string(),
         folder: resume_temp_folder.as_ref().map(|p| p.to_string_lossy().to_string()),
         duplicates: all_duplicates.clone(),
         resume_folder: if is_resume { resume_temp_folder.as_ref().map(|p| p.to_string_lossy().to_string()) } else { None },
+        partial_files,
+        error: None,
+        missing_bytes: None,
     };
-    let ack_json = serde_json::to_string(&ack).map_err(|e| e.to_string())?;
-    let ack_bytes = ack_json.as_bytes();
-
-    stream
-        .write_all(&(ack_bytes.len() as u32).to_be_bytes())
-        .map_err(|e| format!("Eroare trimitere ACK len: {}", e))?;
-    stream
-        .write_all(ack_bytes)
-        .map_err(|e| format!("Eroare trimitere ACK: {}", e))?;
+    write_json(&mut stream, &ack).map_err(|e| format!("Eroare trimitere ACK: {}", e))?;
 
     // Întotdeauna așteaptă răspunsul senderului cu lista de fișiere de trimis
     // (pentru a permite check_duplicates să funcționeze corect)
-    let mut decision_len_buf = [0u8; 4];
-    stream
-        .read_exact(&mut decision_len_buf)
+    let decision: SendDecision = read_json(&mut stream, MAX_HEADER_LEN)
         .map_err(|e| format!("Eroare citire decizie duplicate: {}", e))?;
-    let decision_len = u32::from_be_bytes(decision_len_buf) as usize;
-
-    let mut decision_buf = vec![0u8; decision_len];
-    stream
-        .read_exact(&mut decision_buf)
-        .map_err(|e| format!("Eroare citire date decizie: {}", e))?;
-
-    let files_to_send: Vec<String> = serde_json::from_slice(&decision_buf)
-        .map_err(|e| format!("Eroare parsare decizie: {}", e))?;
+    let (files_to_send, resume_offsets, actions) = decision.into_parts();
 
     // Dacă lista e goală, senderul a anulat (check_duplicates only)
     // Nu am creat niciun folder, deci nu trebuie să curățăm nimic
     if files_to_send.is_empty() {
         println!("--- Verificare duplicate finalizată (lista goală, fără transfer) ---");
+        if let Some(conn) = session {
+            conn.finish();
+        }
         return Ok(());
     }
 
-    println!("--- Se vor primi {} fișiere de la {} ---", files_to_send.len(), header.photographer);
+    println!(
+        "--- Se vor primi {} fișiere de la {} (protocol v{}) ---",
+        files_to_send.len(),
+        header.photographer,
+        negotiated.version
+    );
 
     // ACUM creăm folderul TEMPORAR - sau folosim cel existent pentru reluare
     // Folderul va fi redenumit la final cu numele numerotat
-    let (temp_path, _is_new_transfer) = if let Some(temp_folder) = resume_temp_folder {
+    let (temp_path, _is_new_transfer) = if let Some(ref conn) = session {
+        // Folderul ales de prima conexiune a sesiunii
+        (conn.state.temp_path.clone(), conn.is_first)
+    } else if let Some(temp_folder) = resume_temp_folder {
         // Folosim folderul temporar existent pentru reluare
         (temp_folder, false)
     } else {
         // Creăm folder TEMPORAR nou - NU incrementăm contorul încă
         // Include folder_name pentru unicitate la transferuri paralele receiver→receiver
         let temp_folder_name = generate_temp_folder_name(&header.photographer, header.folder_name.as_deref());
-        let path = if config.role == "editor" {
-            base_path.join(source_category).join(&temp_folder_name)
-        } else if let Some(day) = day_folder {
-            base_path.join(day).join(&temp_folder_name)
-        } else {
-            base_path.join(&temp_folder_name)
-        };
-        (path, true)
+        (search_base.join(&temp_folder_name), true)
     };
 
     let full_path = temp_path.clone();
@@ -585,34 +934,45 @@ fn handle_connection(
     let files_to_receive: Vec<&FileMetadata> = header
         .files
         .iter()
-        .filter(|f| {
-            let key = if f.relative_path.is_empty() { &f.name } else { &f.relative_path };
-            files_to_send.contains(key)
-        })
+        .filter(|f| files_to_send.iter().any(|k| k == f.key()))
         .collect();
 
-    // Generează un transfer_id unic pentru acest transfer
-    let transfer_id = format!(
-        "{}_{}",
-        header.photographer.replace(' ', "_"),
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_millis())
-            .unwrap_or(0)
-    );
-
-    // Emit transfer started cu transfer_id
-    let _ = window.emit("transfer-started", serde_json::json!({
-        "transfer_id": transfer_id,
-        "photographer": header.photographer
-    }));
+    // Progresul e comun tuturor conexiunilor unei sesiuni; fără sesiune, e doar al acestei conexiuni
+    let progress_state = match session {
+        Some(ref conn) => Arc::clone(&conn.state),
+        None => {
+            // Generează un transfer_id unic pentru acest transfer
+            let transfer_id = format!(
+                "{}_{}",
+                header.photographer.replace(' ', "_"),
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_millis())
+                    .unwrap_or(0)
+            );
+            Arc::new(SessionState::standalone(
+                config.clone(),
+                transfer_id,
+                header.photographer.clone(),
+                full_path.clone(),
+                files_to_receive.len(),
+                files_to_receive.iter().map(|f| f.size).sum(),
+            ))
+        }
+    };
+    let transfer_id = progress_state.transfer_id.clone();
+    let _active = active.track(&progress_state);
+
+    // Emit transfer started cu transfer_id (o singură dată pe sesiune)
+    if session.as_ref().is_none_or(|conn| conn.is_first) {
+        let _ = window.emit("transfer-started", serde_json::json!({
+            "transfer_id": transfer_id,
+            "photographer": header.photographer
+        }));
+    }
 
     // Receive files
-    let total_bytes: u64 = files_to_receive.iter().map(|f| f.size).sum();
-    let mut total_received: u64 = 0;
-    let start_time = Instant::now();
-    let total_files_count = files_to_receive.len();
-    let mut files_completed: usize = 0;
+    let mut file_index = progress_state.files_completed.load(Ordering::Relaxed);
 
     // Numără fișierele reale din folder (recursiv, include subfoldere)
     let count_real_files = || -> (usize, u64) {
@@ -622,7 +982,7 @@ fn handle_connection(
     let folder_path_str = full_path.to_string_lossy().to_string();
 
     // Helper function pentru salvare/actualizare în istoric
-    let save_to_history = |hist: &Arc<Mutex<Vec<TransferRecord>>>, status: TransferStatus, tid: &str| {
+    let save_to_history = |hist: &HistoryStore, status: TransferStatus, tid: &str| {
         // Numără fișierele reale din folder
         let (real_file_count, real_total_size) = count_real_files();
 
@@ -640,41 +1000,88 @@ fn handle_connection(
             },
             status,
             source_role: header.sender_role.clone(),
+            backups: backup_copies(&config, &base_path, &full_path, real_file_count, &progress_state),
+            speed_mbps: Some(progress_state.speed_mbps()),
         };
 
-        if let Ok(mut h) = hist.lock() {
-            // Caută înregistrare existentă pentru acest folder și actualizează-o
-            if let Some(existing) = h.iter_mut().find(|r| r.folder == folder_path_str) {
-                existing.timestamp = record.timestamp;
-                existing.file_count = record.file_count;
-                existing.total_size = record.total_size;
-                existing.status = record.status.clone();
-            } else {
-                // Nu există - adaugă nouă
-                h.push(record.clone());
-            }
-            let _ = save_history(&h);
+        // Actualizează înregistrarea existentă pentru acest folder sau adaugă una nouă
+        if let Err(e) = hist.record_progress(&config.profile_id, &record) {
+            eprintln!("{}", e);
         }
 
         record
     };
 
-    for (index, file_meta) in files_to_receive.iter().enumerate() {
-        // Verifică dacă transferul a fost anulat
-        if is_cancelled.load(std::sync::atomic::Ordering::Relaxed) {
+    // Anulare din UI: fișierele primite (și cel întrerupt) rămân în folderul temporar pentru
+    // reluare. O singură conexiune a sesiunii salvează istoricul și anunță UI-ul.
+    let cancel_transfer = || {
+        if progress_state.report_cancel() {
             let record = save_to_history(history, TransferStatus::Partial, &transfer_id);
+            if record.file_count == 0 {
+                // Nimic de reluat - nu lăsăm un folder temporar gol (un .part început îl păstrează)
+                let _ = std::fs::remove_dir(&full_path);
+                for root in &config.backup_paths {
+                    if let Some(mirror) = mirror_path(root, &base_path, &full_path) {
+                        let _ = std::fs::remove_dir(mirror);
+                    }
+                }
+            }
             let _ = window.emit("transfer-cancelled", &record);
-            return Err("Transfer anulat de utilizator".to_string());
+        }
+        "Transfer anulat de utilizator".to_string()
+    };
+
+    for file_meta in files_to_receive.iter() {
+        // Verifică dacă transferul a fost anulat
+        if progress_state.is_cancelled() {
+            return Err(cancel_transfer());
         }
 
         // Folosește relative_path pentru a păstra structura de subfoldere
         // Dacă relative_path e gol, folosește name
-        let relative = if file_meta.relative_path.is_empty() {
-            &file_meta.name
+        let relative = file_meta.key();
+        // Verificată deja în header (`check_paths`)
+        let target_path = full_path.join(file_meta.local_path()?);
+
+        // Acțiunea pentru duplicate: cea aleasă pe sender, altfel politica din setări - dar doar pentru
+        // copii identice sau coliziuni în același folder. Un fișier diferit cu același nume în alt folder
+        // (alt fotograf) se salvează oricum: header-ul nu are checksum, Skip l-ar pierde.
+        let action = match actions.get(relative) {
+            Some(&action) => action,
+            None if all_duplicates
+                .iter()
+                .any(|d| d.file_name == relative && (d.same_checksum || target_path.exists())) =>
+            {
+                config.duplicate_policy
+            }
+            None => DuplicateAction::Overwrite,
+        };
+
+        if action == DuplicateAction::Skip {
+            println!("Sar peste duplicatul {}", relative);
+            let skipped = skip_file(&mut stream, file_meta.size, use_checksum)
+                .and_then(|(len, checksum)| FileAck::skipped(len, &checksum).write_to(&mut stream, use_file_result));
+            if let Err(e) = skipped {
+                let record = save_to_history(history, TransferStatus::Partial, &transfer_id);
+                let _ = window.emit("transfer-partial", &record);
+                return Err(format!("Eroare citire date: {}", e));
+            }
+            progress_state.bytes_received.fetch_add(file_meta.size, Ordering::Relaxed);
+            file_index = progress_state.files_completed.fetch_add(1, Ordering::Relaxed) + 1;
+            continue;
+        }
+
+        // "Păstrează ambele": fișierul existent rămâne neatins, cel nou primește un sufix liber.
+        // Nu și pentru un fișier reluat - .part-ul anunțat senderului e lângă calea originală
+        let resuming = resume_offsets.get(relative).is_some_and(|&offset| offset > 0);
+        let file_path = if action == DuplicateAction::KeepBoth && !resuming && target_path.exists() {
+            let free_path = keep_both_path(&target_path);
+            println!("{} există deja, salvez ca {}", relative, free_path.display());
+            free_path
         } else {
-            &file_meta.relative_path
+            target_path
         };
-        let file_path = full_path.join(relative);
+        let part = part_path(&file_path);
 
         // Creează subfoldere dacă e necesar
         if let Some(parent) = file_path.parent() {
@@ -683,89 +1090,217 @@ fn handle_connection(
                     let record = save_to_history(history, TransferStatus::Error, &transfer_id);
                     let _ = window.emit("transfer-error", format!("Eroare creare subfolder: {}", e));
                     let _ = window.emit("transfer-partial", &record);
+                    if use_file_result {
+                        let error = format!("Eroare creare subfolder: {}", e);
+                        let ack = FileAck::failed(FileErrorCode::from_io(&e), error);
+                        let _ = reject_file(&mut stream, file_meta.size, use_checksum, &ack);
+                    }
                     return Err(format!("Eroare creare subfolder: {}", e));
                 }
             }
         }
 
-        let mut file = match std::fs::File::create(&file_path) {
-            Ok(f) => f,
-            Err(e) => {
-                // Salvează în istoric ca eroare
-                let record = save_to_history(history, TransferStatus::Error, &transfer_id);
-                let _ = window.emit("transfer-error", format!("Eroare creare fișier {}: {}", file_meta.name, e));
-                let _ = window.emit("transfer-partial", &record);
-                return Err(format!("Eroare creare fișier {}: {}", file_meta.name, e));
-            }
-        };
+        let mut attempt: u32 = 1;
 
-        let mut file_received: u64 = 0;
-        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let (file, bytes_written, checksum) = loop {
+            // Reluare de la offset-ul ales de sender - doar la prima încercare și doar
+            // dacă pe disc există cel puțin atâția bytes (altfel se scrie de la zero)
+            let existing_len = std::fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+            let resume_offset = match resume_offsets.get(relative) {
+                Some(&offset) if attempt == 1 && use_resume && offset <= existing_len && offset <= file_meta.size => offset,
+                _ => 0,
+            };
 
-        while file_received < file_meta.size {
-            let to_read = std::cmp::min(CHUNK_SIZE, (file_meta.size - file_received) as usize);
-            let bytes_read = match stream.read(&mut buffer[..to_read]) {
-                Ok(0) => {
-                    // Conexiune închisă - salvează transferul parțial în istoric
-                    let record = save_to_history(history, TransferStatus::Partial, &transfer_id);
-                    let _ = window.emit("transfer-partial", &record);
-                    return Err("Conexiune închisă prematur".to_string());
-                }
-                Ok(n) => n,
+            let opened = if resume_offset > 0 {
+                open_for_resume(&part, resume_offset)
+            } else {
+                std::fs::File::create(&part).map(|f| (f, blake3::Hasher::new()))
+            };
+
+            let (mut file, mut hasher) = match opened {
+                Ok(opened) => opened,
                 Err(e) => {
-                    // Eroare de citire (inclusiv timeout) - salvează transferul parțial
-                    let record = save_to_history(history, TransferStatus::Partial, &transfer_id);
+                    // Salvează în istoric ca eroare
+                    let record = save_to_history(history, TransferStatus::Error, &transfer_id);
+                    let _ = window.emit("transfer-error", format!("Eroare creare fișier {}: {}", file_meta.name, e));
                     let _ = window.emit("transfer-partial", &record);
-                    return Err(format!("Eroare citire date: {}", e));
+                    if use_file_result {
+                        let ack = FileAck::failed(FileErrorCode::from_io(&e), e.to_string());
+                        let _ = reject_file(&mut stream, file_meta.size - resume_offset, use_checksum, &ack);
+                    }
+                    return Err(format!("Eroare creare fișier {}: {}", file_meta.name, e));
                 }
             };
 
-            if let Err(e) = file.write_all(&buffer[..bytes_read]) {
-                let record = save_to_history(history, TransferStatus::Error, &transfer_id);
-                let _ = window.emit("transfer-partial", &record);
-                return Err(format!("Eroare scriere fișier: {}", e));
+            if resume_offset > 0 {
+                println!("Reiau {} de la byte-ul {}", relative, resume_offset);
             }
 
-            file_received += bytes_read as u64;
-            total_received += bytes_read as u64;
+            let mut file_received: u64 = resume_offset;
+            progress_state.bytes_received.fetch_add(resume_offset, Ordering::Relaxed);
+            progress_state.bytes_resumed.fetch_add(resume_offset, Ordering::Relaxed);
+            let mut buffer = vec![0u8; CHUNK_SIZE];
+
+            while file_received < file_meta.size {
+                let to_read = std::cmp::min(CHUNK_SIZE, (file_meta.size - file_received) as usize);
+                let bytes_read = match stream.read(&mut buffer[..to_read]) {
+                    Ok(0) => {
+                        // Conexiune închisă - salvează transferul parțial în istoric
+                        let record = save_to_history(history, TransferStatus::Partial, &transfer_id);
+                        let _ = window.emit("transfer-partial", &record);
+                        return Err("Conexiune închisă prematur".to_string());
+                    }
+                    Ok(n) => n,
+                    Err(e) => {
+                        // Eroare de citire (inclusiv timeout) - salvează transferul parțial
+                        let record = save_to_history(history, TransferStatus::Partial, &transfer_id);
+                        let _ = window.emit("transfer-partial", &record);
+                        return Err(format!("Eroare citire date: {}", e));
+                    }
+                };
 
-            // Verifică dacă transferul a fost anulat după fiecare chunk
-            if is_cancelled.load(std::sync::atomic::Ordering::Relaxed) {
-                let record = save_to_history(history, TransferStatus::Partial, &transfer_id);
-                let _ = window.emit("transfer-cancelled", &record);
-                return Err("Transfer anulat de utilizator".to_string());
+                if let Err(e) = file.write_all(&buffer[..bytes_read]) {
+                    let record = save_to_history(history, TransferStatus::Error, &transfer_id);
+                    let _ = window.emit("transfer-partial", &record);
+                    if use_file_result {
+                        let remaining = file_meta.size - file_received - bytes_read as u64;
+                        let ack = FileAck::failed(FileErrorCode::from_io(&e), e.to_string());
+                        let _ = reject_file(&mut stream, remaining, use_checksum, &ack);
+                    }
+                    return Err(format!("Eroare scriere fișier: {}", e));
+                }
+                hasher.update(&buffer[..bytes_read]);
+
+                file_received += bytes_read as u64;
+                let total_received =
+                    progress_state.bytes_received.fetch_add(bytes_read as u64, Ordering::Relaxed) + bytes_read as u64;
+
+                // Verifică dacă transferul a fost anulat după fiecare chunk
+                if progress_state.is_cancelled() {
+                    return Err(cancel_transfer());
+                }
+
+                // Emit progress
+                let progress = TransferProgress {
+                    transfer_id: transfer_id.clone(),
+                    photographer: header.photographer.clone(),
+                    file_name: file_meta.name.clone(),
+                    file_index,
+                    total_files: progress_state.total_files,
+                    bytes_received: total_received,
+                    total_bytes: progress_state.total_bytes,
+                    speed_mbps: progress_state.speed_mbps(),
+                };
+                let _ = window.emit("transfer-progress", &progress);
             }
 
-            // Calculate speed
-            let elapsed = start_time.elapsed().as_secs_f64();
-            let speed_mbps = if elapsed > 0.0 {
-                (total_received as f64 / elapsed) / (1024.0 * 1024.0)
+            // Verifică hash-ul înainte de confirmare (doar dacă a fost negociat)
+            let actual = hasher.finalize();
+            let checksum_ok = if use_checksum {
+                let expected = match read_checksum(&mut stream) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        let record = save_to_history(history, TransferStatus::Partial, &transfer_id);
+                        let _ = window.emit("transfer-partial", &record);
+                        return Err(format!("Eroare citire checksum: {}", e));
+                    }
+                };
+                expected.eq_ignore_ascii_case(actual.to_hex().as_str())
             } else {
-                0.0
+                true
             };
 
-            // Emit progress
-            let progress = TransferProgress {
-                transfer_id: transfer_id.clone(),
-                photographer: header.photographer.clone(),
-                file_name: file_meta.name.clone(),
-                file_index: index,
-                total_files: total_files_count,
-                bytes_received: total_received,
-                total_bytes,
-                speed_mbps,
+            if checksum_ok {
+                break (file, file_received, actual);
+            }
+
+            eprintln!(
+                "Checksum diferit pentru {} (încercarea {}/{})",
+                file_meta.name, attempt, MAX_CHECKSUM_RETRIES
+            );
+            let mismatch = FileAck::checksum_mismatch(file_received, &actual);
+            if let Err(e) = mismatch.write_to(&mut stream, use_file_result) {
+                let record = save_to_history(history, TransferStatus::Partial, &transfer_id);
+                let _ = window.emit("transfer-partial", &record);
+                return Err(format!("Eroare trimitere confirmare: {}", e));
+            }
+
+            if attempt >= MAX_CHECKSUM_RETRIES {
+                drop(file);
+                let _ = std::fs::remove_file(&part);
+                let record = save_to_history(history, TransferStatus::Error, &transfer_id);
+                let _ = window.emit("transfer-error", format!("Checksum diferit pentru {}", file_meta.name));
+                let _ = window.emit("transfer-partial", &record);
+                return Err(format!(
+                    "Checksum diferit pentru {} după {} încercări",
+                    file_meta.name, MAX_CHECKSUM_RETRIES
+                ));
+            }
+
+            // Senderul retrimite fișierul de la început
+            progress_state.bytes_received.fetch_sub(file_received, Ordering::Relaxed);
+            progress_state.bytes_resumed.fetch_sub(resume_offset, Ordering::Relaxed);
+            attempt += 1;
+        };
+
+        // Copiile de backup, fiecare verificată separat, făcute din .part înainte ca fișierul să primească
+        // numele final. Politica din config decide dacă un volum de backup care cedează oprește transferul
+        // (fișierul rămâne neprimit, fără urme în folder) sau doar apare ca eroare în istoric.
+        for root in &config.backup_paths {
+            let copied = mirror_path(root, &base_path, &file_path)
+                .ok_or_else(|| std::io::Error::other("fișierul nu e în folderul de bază"))
+                .and_then(|target| mirror_file(&part, &target, &checksum, file_meta));
+            let Err(e) = copied else {
+                continue;
             };
-            let _ = window.emit("transfer-progress", &progress);
+            let error = format!("Backup eșuat pe {} pentru {}: {}", root, file_meta.name, e);
+            eprintln!("{}", error);
+            progress_state.backup_failed(root, error.clone());
+            if config.backup_required {
+                drop(file);
+                let _ = std::fs::remove_file(&part);
+                let record = save_to_history(history, TransferStatus::Error, &transfer_id);
+                let _ = window.emit("transfer-error", &error);
+                let _ = window.emit("transfer-partial", &record);
+                if use_file_result {
+                    let _ = FileAck::failed(FileErrorCode::from_io(&e), error.clone()).write_to(&mut stream, true);
+                }
+                return Err(error);
+            }
+        }
+
+        if let Err(e) = commit_part_file(file, &part, &file_path, file_meta) {
+            let record = save_to_history(history, TransferStatus::Error, &transfer_id);
+            let _ = window.emit("transfer-error", format!("Eroare salvare fișier {}: {}", file_meta.name, e));
+            let _ = window.emit("transfer-partial", &record);
+            if use_file_result {
+                let ack = FileAck::failed(FileErrorCode::from_io(&e), e.to_string());
+                let _ = ack.write_to(&mut stream, true);
+            }
+            return Err(format!("Eroare salvare fișier {}: {}", file_meta.name, e));
         }
 
-        // Trimite OK - fără verificare checksum
-        if let Err(e) = stream.write_all(b"OK") {
+        index.add(&base_path, &file_path, &checksum, bytes_written);
+        if let Ok(relative) = file_path.strip_prefix(&full_path) {
+            progress_state.file_received(relative, bytes_written, checksum);
+        }
+
+        // Trimite OK - fișierul a fost primit (și verificat, dacă s-a negociat checksum)
+        if let Err(e) = FileAck::ok(bytes_written, &checksum).write_to(&mut stream, use_file_result) {
             let record = save_to_history(history, TransferStatus::Partial, &transfer_id);
             let _ = window.emit("transfer-partial", &record);
             return Err(format!("Eroare trimitere confirmare: {}", e));
         }
 
-        files_completed += 1;
+        file_index = progress_state.files_completed.fetch_add(1, Ordering::Relaxed) + 1;
+    }
+
+    // Transfer paralel: doar ultima conexiune terminată redenumește folderul
+    if let Some(conn) = session {
+        if !conn.finish() {
+            println!("--- Conexiune terminată, restul sesiunii {} continuă ---", transfer_id);
+            return Ok(());
+        }
     }
 
     // Transfer complet! Acum redenumim folderul temporar la numele final
@@ -793,9 +1328,22 @@ fn handle_connection(
         }
         name
     } else {
-        // Transfer fotograf→receiver: generează nume nou cu counter
+        // Transfer fotograf→receiver: generează nume nou din template, cu counter.
+        // Modelul camerei se citește din EXIF doar dacă template-ul îl folosește.
+        let camera = FolderTemplate::from_config(&config.folder_template)
+            .uses(Field::Camera)
+            .then(|| camera_model(&temp_path))
+            .flatten();
+        let transfer = FolderTransfer {
+            photographer: &header.photographer,
+            day: &config.current_day,
+            source: source_category,
+            camera,
+            file_count: Some(count_files_recursive(&temp_path).0),
+        };
+        // Numele și contorul vin din profilul în care a început sesiunea, nu din cel activ acum
         let mut cfg = config_state.lock().map_err(|e| e.to_string())?;
-        cfg.generate_unique_folder_name(&header.photographer, &search_base)
+        cfg.generate_session_folder_name(&config, &transfer, &search_base)
     };
 
     let final_path = if config.role == "editor" {
@@ -807,6 +1355,54 @@ fn handle_connection(
     // Redenumește folderul temporar la numele final
     std::fs::rename(&temp_path, &final_path)
         .map_err(|e| format!("Eroare redenumire folder: {}", e))?;
+    index.moved(&base_path, &temp_path, &final_path);
+
+    // Copiile de backup primesc același nume. Originalul e deja finalizat, deci o eroare aici
+    // apare doar în istoric, indiferent de politică.
+    for root in &config.backup_paths {
+        let (Some(from), Some(to)) =
+            (mirror_path(root, &base_path, &temp_path), mirror_path(root, &base_path, &final_path))
+        else {
+            continue;
+        };
+        if !from.exists() {
+            continue;
+        }
+        let renamed = std::fs::rename(&from, &to).and_then(|()| to.parent().map_or(Ok(()), sync_dir));
+        if let Err(e) = renamed {
+            eprintln!("Nu pot redenumi copia de backup {}: {}", from.display(), e);
+            progress_state.backup_failed(root, format!("Eroare redenumire folder: {}", e));
+        }
+    }
+    // Fișierele sunt deja pe disc - fără sync pe folderul părinte, redenumirea se poate pierde
+    if let Err(e) = final_path.parent().map_or(Ok(()), sync_dir) {
+        eprintln!("Nu pot sincroniza redenumirea folderului {}: {}", final_path.display(), e);
+    }
+
+    // Manifestul folderului, scris și în copiile de backup. Transferul e deja complet,
+    // deci o eroare aici e doar afișată.
+    let manifest = Manifest {
+        transfer_id: transfer_id.clone(),
+        sender: ManifestSender {
+            name: header.photographer.clone(),
+            role: header.sender_role.clone().unwrap_or_else(|| "photographer".to_string()),
+        },
+        station: ManifestStation {
+            name: config.name.clone(),
+            role: config.role.clone(),
+        },
+        event: config.profile_name.clone(),
+        day: config.use_day_folders.then(|| config.current_day.clone()),
+        started_at: progress_state.started_at,
+        finished_at: Utc::now(),
+        files: Manifest::files(&final_path, &progress_state.received_files()),
+    };
+    let mirrors = config.backup_paths.iter().filter_map(|root| mirror_path(root, &base_path, &final_path));
+    for folder in std::iter::once(final_path.clone()).chain(mirrors.filter(|m| m.is_dir())) {
+        if let Err(e) = manifest.write(&folder) {
+            eprintln!("{}", e);
+        }
+    }
 
     // Actualizează folder_path_str pentru salvarea în istoric
     let final_folder_path_str = final_path.to_string_lossy().to_string();
@@ -829,14 +1425,13 @@ fn handle_connection(
         },
         status: TransferStatus::Complete,
         source_role: header.sender_role.clone(),
+        backups: backup_copies(&config, &base_path, &final_path, final_file_count, &progress_state),
+        speed_mbps: Some(progress_state.speed_mbps()),
     };
 
-    if let Ok(mut h) = history.lock() {
-        // Elimină orice înregistrare anterioară pentru folderul temporar
-        h.retain(|r| r.folder != folder_path_str);
-        // Adaugă înregistrarea cu folderul final
-        h.push(record.clone());
-        let _ = save_history(&h);
+    // Înregistrarea folderului temporar e înlocuită de cea cu folderul final
+    if let Err(e) = history.record_complete(&config.profile_id, &folder_path_str, &record) {
+        eprintln!("{}", e);
     }
 
     // Emit transfer complete cu folderul final
@@ -0,0 +1,326 @@
+use crate::config::ReceiverConfig;
+use crate::TransferProgress;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Starea comună a unui transfer trimis pe mai multe conexiuni paralele.
+/// Toate conexiunile scriu în același folder temporar și raportează progresul combinat.
+pub struct SessionState {
+    pub transfer_id: String,
+    pub photographer: String,
+    pub temp_path: PathBuf,
+    pub resume_folder: Option<PathBuf>, // Folderul temporar găsit la prima conexiune (reluare)
+    pub total_files: usize,
+    pub total_bytes: u64,
+    pub start_time: Instant,
+    pub started_at: DateTime<Utc>, // Pentru manifestul folderului
+    pub config: ReceiverConfig,    // Setările (profilul) de la începutul sesiunii - comutarea nu o mută
+    pub bytes_received: AtomicU64,
+    pub bytes_resumed: AtomicU64, // Bytes existenți deja pe disc (nu intră în calculul vitezei)
+    pub files_completed: AtomicUsize,
+    connections: u32,
+    finished: Mutex<u32>,
+    failed: AtomicBool,
+    cancelled: AtomicBool,       // Anulat din UI - toate conexiunile sesiunii se opresc
+    cancel_reported: AtomicBool, // Istoricul și evenimentul de anulare se emit o singură dată
+    backup_errors: Mutex<HashMap<String, String>>, // Ultima eroare pe fiecare volum de backup
+    received: Mutex<HashMap<PathBuf, (u64, blake3::Hash)>>, // Fișierele salvate, relativ la folderul temporar
+}
+
+impl SessionState {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        config: ReceiverConfig,
+        transfer_id: String,
+        photographer: String,
+        temp_path: PathBuf,
+        resume_folder: Option<PathBuf>,
+        total_files: usize,
+        total_bytes: u64,
+        connections: u32,
+    ) -> Self {
+        Self {
+            transfer_id,
+            photographer,
+            temp_path,
+            resume_folder,
+            total_files,
+            total_bytes,
+            start_time: Instant::now(),
+            started_at: Utc::now(),
+            config,
+            bytes_received: AtomicU64::new(0),
+            bytes_resumed: AtomicU64::new(0),
+            files_completed: AtomicUsize::new(0),
+            connections: connections.max(1),
+            finished: Mutex::new(0),
+            failed: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+            cancel_reported: AtomicBool::new(false),
+            backup_errors: Mutex::new(HashMap::new()),
+            received: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Transfer pe o singură conexiune (fără sesiune) - aceleași contoare de progres
+    pub fn standalone(
+        config: ReceiverConfig,
+        transfer_id: String,
+        photographer: String,
+        temp_path: PathBuf,
+        total_files: usize,
+        total_bytes: u64,
+    ) -> Self {
+        Self::new(config, transfer_id, photographer, temp_path, None, total_files, total_bytes, 1)
+    }
+
+    /// Viteza combinată a tuturor conexiunilor, în MB/s
+    pub fn speed_mbps(&self) -> f64 {
+        let elapsed = self.start_time.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            let transferred = self.bytes_received.load(Ordering::Relaxed) - self.bytes_resumed.load(Ordering::Relaxed);
+            (transferred as f64 / elapsed) / (1024.0 * 1024.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn has_failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returnează true doar pentru prima conexiune care observă anularea - aceea
+    /// salvează istoricul și anunță UI-ul
+    pub fn report_cancel(&self) -> bool {
+        !self.cancel_reported.swap(true, Ordering::Relaxed)
+    }
+
+    /// O copie pe volumul de backup `root` a eșuat (pentru istoric)
+    pub fn backup_failed(&self, root: &str, error: String) {
+        self.backup_errors.lock().unwrap_or_else(|e| e.into_inner()).insert(root.to_string(), error);
+    }
+
+    pub fn backup_error(&self, root: &str) -> Option<String> {
+        self.backup_errors.lock().unwrap_or_else(|e| e.into_inner()).get(root).cloned()
+    }
+
+    /// Un fișier salvat și verificat, cu calea relativă la folderul temporar
+    pub fn file_received(&self, path: &Path, size: u64, hash: blake3::Hash) {
+        self.received.lock().unwrap_or_else(|e| e.into_inner()).insert(path.to_path_buf(), (size, hash));
+    }
+
+    /// Fișierele primite de toate conexiunile sesiunii (fără cele rămase de la o sesiune anterioară)
+    pub fn received_files(&self) -> HashMap<PathBuf, (u64, blake3::Hash)> {
+        self.received.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    // Marchează conexiunea ca terminată. Returnează true doar pentru ultima conexiune,
+    // și doar dacă niciuna nu a eșuat - aceea finalizează transferul.
+    fn finish_connection(&self, success: bool) -> bool {
+        if !success {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+        let mut finished = self.finished.lock().unwrap_or_else(|e| e.into_inner());
+        *finished += 1;
+        *finished >= self.connections && !self.has_failed()
+    }
+
+    fn is_done(&self) -> bool {
+        *self.finished.lock().unwrap_or_else(|e| e.into_inner()) >= self.connections
+    }
+}
+
+/// Transferurile paralele active, după id-ul trimis de sender în header
+#[derive(Clone, Default)]
+pub struct SessionRegistry {
+    sessions: Arc<Mutex<HashMap<String, Arc<SessionState>>>>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Atașează o conexiune la sesiunea cu acest id. Prima conexiune creează sesiunea cu `config`-ul ei:
+    /// `locate` alege folderul temporar (existent pentru reluare, sau unul nou) o singură dată.
+    pub fn join(
+        &self,
+        session: &transfer_protocol::TransferSession,
+        photographer: &str,
+        config: &ReceiverConfig,
+        locate: impl FnOnce() -> (PathBuf, Option<PathBuf>),
+    ) -> Result<SessionConnection, String> {
+        let mut sessions = self.sessions.lock().map_err(|e| e.to_string())?;
+
+        let (state, is_first) = match sessions.get(&session.id) {
+            Some(existing) => (Arc::clone(existing), false),
+            None => {
+                let (temp_path, resume_folder) = locate();
+                let state = Arc::new(SessionState::new(
+                    config.clone(),
+                    format!("session_{}", session.id),
+                    photographer.to_string(),
+                    temp_path,
+                    resume_folder,
+                    session.total_files,
+                    session.total_bytes,
+                    session.connections,
+                ));
+                sessions.insert(session.id.clone(), Arc::clone(&state));
+                (state, true)
+            }
+        };
+
+        Ok(SessionConnection {
+            id: session.id.clone(),
+            state,
+            registry: self.clone(),
+            is_first,
+            done: false,
+        })
+    }
+
+    fn remove(&self, id: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(id);
+        }
+    }
+}
+
+/// O conexiune atașată unei sesiuni. Dacă e abandonată fără `finish` (eroare, conexiune
+/// închisă), sesiunea e marcată eșuată și nu mai e finalizată de celelalte conexiuni.
+pub struct SessionConnection {
+    id: String,
+    pub state: Arc<SessionState>,
+    registry: SessionRegistry,
+    pub is_first: bool,
+    done: bool,
+}
+
+impl SessionConnection {
+    /// Returnează true dacă această conexiune trebuie să finalizeze transferul
+    pub fn finish(mut self) -> bool {
+        self.done = true;
+        let is_last = self.state.finish_connection(true);
+        if self.state.is_done() {
+            self.registry.remove(&self.id);
+        }
+        is_last
+    }
+}
+
+impl Drop for SessionConnection {
+    fn drop(&mut self) {
+        if !self.done {
+            self.state.finish_connection(false);
+            // O sesiune eșuată nu mai poate fi finalizată - senderul va porni una nouă
+            self.registry.remove(&self.id);
+        }
+    }
+}
+
+/// Transferurile primite în curs, după transfer_id - listate și anulate din UI.
+/// Conexiunile unei sesiuni paralele împart aceeași intrare.
+#[derive(Clone, Default)]
+pub struct ActiveTransfers {
+    transfers: Arc<Mutex<HashMap<String, ActiveEntry>>>,
+}
+
+struct ActiveEntry {
+    state: Arc<SessionState>,
+    connections: usize,
+}
+
+impl ActiveTransfers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Înregistrează conexiunea; transferul dispare din listă când se închide ultima conexiune
+    pub fn track(&self, state: &Arc<SessionState>) -> ActiveTransfer {
+        if let Ok(mut transfers) = self.transfers.lock() {
+            transfers
+                .entry(state.transfer_id.clone())
+                .or_insert_with(|| ActiveEntry {
+                    state: Arc::clone(state),
+                    connections: 0,
+                })
+                .connections += 1;
+        }
+        ActiveTransfer {
+            transfer_id: state.transfer_id.clone(),
+            registry: self.clone(),
+        }
+    }
+
+    pub fn list(&self) -> Vec<TransferProgress> {
+        let Ok(transfers) = self.transfers.lock() else {
+            return Vec::new();
+        };
+        transfers
+            .values()
+            .map(|ActiveEntry { state, .. }| TransferProgress {
+                transfer_id: state.transfer_id.clone(),
+                photographer: state.photographer.clone(),
+                file_name: String::new(),
+                file_index: state.files_completed.load(Ordering::Relaxed),
+                total_files: state.total_files,
+                bytes_received: state.bytes_received.load(Ordering::Relaxed),
+                total_bytes: state.total_bytes,
+                speed_mbps: state.speed_mbps(),
+            })
+            .collect()
+    }
+
+    /// Returnează false dacă transferul nu (mai) e activ
+    pub fn cancel(&self, transfer_id: &str) -> bool {
+        let Ok(transfers) = self.transfers.lock() else {
+            return false;
+        };
+        match transfers.get(transfer_id) {
+            Some(entry) => {
+                entry.state.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returnează câte transferuri au fost anulate
+    pub fn cancel_all(&self) -> usize {
+        let Ok(transfers) = self.transfers.lock() else {
+            return 0;
+        };
+        for entry in transfers.values() {
+            entry.state.cancelled.store(true, Ordering::Relaxed);
+        }
+        transfers.len()
+    }
+}
+
+/// O conexiune a unui transfer activ; la drop, transferul e scos din listă dacă era ultima
+pub struct ActiveTransfer {
+    transfer_id: String,
+    registry: ActiveTransfers,
+}
+
+impl Drop for ActiveTransfer {
+    fn drop(&mut self) {
+        if let Ok(mut transfers) = self.registry.transfers.lock() {
+            if let Some(entry) = transfers.get_mut(&self.transfer_id) {
+                entry.connections -= 1;
+                if entry.connections == 0 {
+                    transfers.remove(&self.transfer_id);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::config::{source_category, TransferRecord, TransferStatus};
+use crate::report::day_order;
+use chrono::{Local, Timelike};
+use serde::Serialize;
+use std::collections::HashMap;
+use transfer_protocol::{Accumulator, HourLoads, Outcome, Summary};
+
+/// Volumul, viteza și rata de eșec ale unui grup de transferuri
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Throughput {
+    pub transfers: usize,
+    pub files: usize,
+    pub bytes: u64,
+    pub average_mbps: Option<f64>, // MB primiți / timpul total, doar transferurile cu viteza salvată
+    pub peak_mbps: Option<f64>,    // Cel mai rapid transfer
+    pub failed: usize,
+    pub partial: usize,
+    pub failure_rate: f64, // 0..1
+    pub partial_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupThroughput {
+    pub name: String,
+    #[serde(flatten)]
+    pub throughput: Throughput,
+}
+
+/// Transferurile terminate într-o oră din zi (ora locală)
+#[derive(Debug, Clone, Serialize)]
+pub struct HourLoad {
+    pub hour: u32,
+    pub transfers: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferStats {
+    pub totals: Throughput,
+    pub photographers: Vec<GroupThroughput>, // Cei mai mulți bytes primii
+    pub days: Vec<GroupThroughput>,
+    pub sources: Vec<GroupThroughput>, // "Fotograf", "Tagger", "Editor"
+    pub busiest_hours: Vec<HourLoad>,  // Cele mai încărcate primele, doar orele cu transferuri
+}
+
+impl From<Summary> for Throughput {
+    fn from(s: Summary) -> Self {
+        Self {
+            transfers: s.count,
+            files: s.files,
+            bytes: s.bytes,
+            average_mbps: s.average_mbps,
+            peak_mbps: s.peak_mbps,
+            failed: s.failed,
+            partial: s.interrupted,
+            failure_rate: s.failure_rate,
+            partial_rate: s.interrupted_rate,
+        }
+    }
+}
+
+fn add(acc: &mut Accumulator, record: &TransferRecord) {
+    let outcome = match record.status {
+        TransferStatus::Complete => Outcome::Complete,
+        TransferStatus::Partial => Outcome::Interrupted,
+        TransferStatus::Error => Outcome::Failed,
+    };
+    acc.add(record.file_count, record.total_size, outcome, record.speed_mbps);
+}
+
+impl TransferStats {
+    pub fn build(transfers: &[TransferRecord]) -> Self {
+        let mut totals = Accumulator::default();
+        let mut photographers: HashMap<String, Accumulator> = HashMap::new();
+        let mut days: HashMap<String, Accumulator> = HashMap::new();
+        let mut sources: HashMap<String, Accumulator> = HashMap::new();
+        let mut hours = HourLoads::default();
+
+        for record in transfers {
+            let local = record.timestamp.with_timezone(&Local);
+            let day = record.day.clone().unwrap_or_else(|| local.format("%Y-%m-%d").to_string());
+            let source = source_category(record.source_role.as_deref()).to_string();
+
+            add(&mut totals, record);
+            add(photographers.entry(record.photographer.clone()).or_default(), record);
+            add(days.entry(day).or_default(), record);
+            add(sources.entry(source).or_default(), record);
+            hours.add(local.hour(), record.total_size);
+        }
+
+        let mut photographers = groups(photographers);
+        photographers.sort_by(|a, b| b.throughput.bytes.cmp(&a.throughput.bytes).then(a.name.cmp(&b.name)));
+        let mut days = groups(days);
+        days.sort_by_cached_key(|d| day_order(&d.name));
+        let mut sources = groups(sources);
+        sources.sort_by(|a, b| a.name.cmp(&b.name));
+        let busiest_hours = hours
+            .busiest()
+            .into_iter()
+            .map(|h| HourLoad { hour: h.hour, transfers: h.count, bytes: h.bytes })
+            .collect();
+
+        Self {
+            totals: totals.finish().into(),
+            photographers,
+            days,
+            sources,
+            busiest_hours,
+        }
+    }
+}
+
+fn groups(groups: HashMap<String, Accumulator>) -> Vec<GroupThroughput> {
+    groups
+        .into_iter()
+        .map(|(name, acc)| GroupThroughput { name, throughput: acc.finish().into() })
+        .collect()
+}
@@ -1,12 +1,20 @@
 use crate::discovery::DiscoveredService;
+use crate::security::SharedPairing;
 use serde::{Deserialize, Serialize};
-use std::io::{Read, Write};
-use std::net::TcpStream;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::{TcpStream, ToSocketAddrs};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tauri::Emitter;
+use transfer_protocol::{
+    check_plaintext, client_handshake, event_key, hash_prefix, open_channel, read_json, secure_connect,
+    write_checksum, write_json, AckResponse, Channel, ClientHandshake, FileAck, FileAttributes, FileMetadata,
+    FileStatus, Hello, HelloIntent, Negotiated, PartialFileInfo, SecureMode, SendDecision, TransferHeader,
+    CAP_CHECKSUM, CAP_ENCRYPTION, CAP_FILE_RESULT, CAP_RESUME, MAX_CHECKSUM_RETRIES, MAX_HEADER_LEN,
+};
 
 /// Deschide un fișier pentru citire, cu suport pentru sharing pe Windows
 fn open_file_for_read(path: &str) -> std::io::Result<std::fs::File> {
@@ -27,6 +35,7 @@ fn open_file_for_read(path: &str) -> std::io::Result<std::fs::File> {
 }
 
 const CHUNK_SIZE: usize = 4 * 1024 * 1024; // 4 MB chunks
+const CONNECT_TIMEOUT_SECS: u64 = 5;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileInfo {
@@ -62,39 +71,174 @@ pub struct SendError {
     pub error: String,
 }
 
-#[derive(Serialize, Deserialize)]
-struct TransferHeader {
-    photographer: String,
-    files: Vec<FileMetadata>,
-    is_folder_transfer: bool,
-    folder_name: Option<String>,
-    #[serde(default)]
-    sender_role: Option<String>, // "tagger" sau "editor"
+/// Verifică fișierele parțiale raportate de receiver. Dacă începutul fișierului local are
+/// același hash, transferul continuă de la acel offset cu hasher-ul deja inițializat.
+fn find_resume_points(
+    files: &[FileInfo],
+    partial_files: &[PartialFileInfo],
+) -> HashMap<String, (u64, blake3::Hasher)> {
+    let mut points = HashMap::new();
+
+    for partial in partial_files {
+        let Some(file) = files.iter().find(|f| f.relative_path == partial.file_name) else {
+            continue;
+        };
+        if partial.bytes_received == 0 || partial.bytes_received > file.size {
+            continue;
+        }
+
+        let Ok(hasher) = open_file_for_read(&file.path).and_then(|h| hash_prefix(h, partial.bytes_received)) else {
+            continue;
+        };
+
+        if hasher.finalize().to_hex().as_str() == partial.prefix_checksum {
+            points.insert(file.relative_path.clone(), (partial.bytes_received, hasher));
+        } else {
+            println!("Fișierul parțial {} diferă pe receiver, se retrimite complet", file.relative_path);
+        }
+    }
+
+    points
+}
+
+/// I/O-ul de rețea și de fișiere e încă `std::io` blocant (protocolul nu e portat pe tokio): rulează
+/// pe pool-ul de thread-uri blocante al lui tokio, ca un transfer lung să nu țină ocupat un worker
+/// al runtime-ului async. Fiecare conexiune de trimitere ține un thread din pool cât durează.
+async fn run_blocking<T: Send + 'static>(
+    task: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|e| format!("Transfer oprit neașteptat: {}", e))?
+}
+
+fn connect(addr: &str) -> Result<TcpStream, String> {
+    let socket_addr = addr
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| format!("Adresă invalidă: {}", addr))?;
+    let stream = TcpStream::connect_timeout(&socket_addr, Duration::from_secs(CONNECT_TIMEOUT_SECS))
+        .map_err(|e| format!("Nu m-am putut conecta la {}: {}", addr, e))?;
+
+    // Setează timeout pentru operațiuni
+    stream
+        .set_read_timeout(Some(Duration::from_secs(60)))
+        .map_err(|e| format!("Eroare setare read timeout: {}", e))?;
+    stream
+        .set_write_timeout(Some(Duration::from_secs(60)))
+        .map_err(|e| format!("Eroare setare write timeout: {}", e))?;
+    stream
+        .set_nodelay(true)
+        .map_err(|e| format!("Eroare setare TCP nodelay: {}", e))?;
+
+    Ok(stream)
+}
+
+/// Conectare + handshake versionat. Cu receiver-ele noi, transferul trece prin canalul criptat
+/// (receiver-ul trebuie asociat). Un receiver vechi închide conexiunea la vederea preambulului -
+/// atunci folosim protocolul vechi, necriptat, doar dacă trimiterea în clar către el a fost permisă.
+fn connect_with_handshake(
+    addr: &str,
+    sender_name: &str,
+    target_name: &str,
+    allow_legacy: bool,
+    pairing: &SharedPairing,
+) -> Result<(Channel<TcpStream>, Negotiated), String> {
+    let mut stream = connect(addr)?;
+    let mut keys = pairing.lock().map_err(|e| e.to_string())?.keys.clone();
+
+    match client_handshake(&mut stream, &Hello::new(HelloIntent::Transfer).with_name(sender_name))? {
+        ClientHandshake::Accepted(reply) => {
+            println!("Protocol v{} negociat cu {}, capabilități: {:?}", reply.version, addr, reply.capabilities);
+            let negotiated = reply.negotiated();
+            let channel = open_channel(stream, &mut keys, &negotiated, target_name, allow_legacy)?;
+            if !channel.is_encrypted() {
+                println!("ATENȚIE: receiver-ul {} nu suportă criptarea, transfer necriptat (permis)", addr);
+            }
+            Ok((channel, negotiated))
+        }
+        ClientHandshake::Legacy => {
+            check_plaintext(&keys, target_name, allow_legacy)?;
+            println!("Receiver-ul {} folosește protocolul vechi, reconectare fără handshake (necriptat)", addr);
+            Ok((Channel::Plain(connect(addr)?), Negotiated::legacy()))
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct FileMetadata {
-    name: String,
-    #[serde(default)]
-    relative_path: String, // Calea relativă pentru structura folder
-    size: u64,
-    checksum: String,
+/// Asociază acest receiver cu alt receiver folosind PIN-ul afișat pe acela.
+/// Returnează numele receiver-ului asociat.
+pub async fn pair_with_receiver(
+    addr: String,
+    sender_name: String,
+    pin: String,
+    pairing: SharedPairing,
+) -> Result<String, String> {
+    run_blocking(move || pair_blocking(&addr, &sender_name, &pin, &pairing)).await
 }
 
-#[derive(Serialize, Deserialize)]
-struct AckResponse {
-    status: String,
-    folder: Option<String>,
+fn pair_blocking(addr: &str, sender_name: &str, pin: &str, pairing: &SharedPairing) -> Result<String, String> {
+    let mut stream = connect(addr)?;
+
+    let negotiated = match client_handshake(&mut stream, &Hello::new(HelloIntent::Pair).with_name(sender_name))? {
+        ClientHandshake::Accepted(reply) => reply.negotiated(),
+        ClientHandshake::Legacy => return Err("Receiver-ul folosește o versiune veche, fără asociere".to_string()),
+    };
+    if !negotiated.has(CAP_ENCRYPTION) {
+        return Err("Receiver-ul nu suportă criptarea".to_string());
+    }
+
+    // Handshake-ul rulează pe o copie - serverul propriu are nevoie de chei între timp
+    let mut keys = pairing.lock().map_err(|e| e.to_string())?.keys.clone();
+    let (secure, reply) = secure_connect(stream, &mut keys, SecureMode::Pin(pin), &negotiated)?;
+    pairing.lock().map_err(|e| e.to_string())?.trust(secure.remote_key(), &reply.name)?;
+
+    println!("Asociat cu {} ({})", reply.name, addr);
+    Ok(reply.name)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn send_files_to_editor(
+    service: DiscoveredService,
+    sender_name: String,
+    sender_role: String,
+    files: Vec<FileInfo>,
+    folder_name: Option<String>,  // Numele original al folderului (pentru receiver→receiver)
+    event_passphrase: String,
+    allow_legacy: bool, // Editorul e o versiune veche, fără criptare, permisă explicit
+    window: tauri::Window,
+    is_cancelled: Arc<AtomicBool>,
+    pairing: SharedPairing,
+) -> Result<String, String> {
+    run_blocking(move || {
+        send_to_editor_blocking(
+            &service,
+            &sender_name,
+            &sender_role,
+            &files,
+            folder_name,
+            &event_passphrase,
+            allow_legacy,
+            window,
+            is_cancelled,
+            pairing,
+        )
+    })
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+fn send_to_editor_blocking(
     service: &DiscoveredService,
     sender_name: &str,
     sender_role: &str,
     files: &[FileInfo],
-    folder_name: Option<String>,  // Numele original al folderului (pentru receiver→receiver)
+    folder_name: Option<String>,
+    event_passphrase: &str,
+    allow_legacy: bool,
     window: tauri::Window,
     is_cancelled: Arc<AtomicBool>,
+    pairing: SharedPairing,
 ) -> Result<String, String> {
     // Generează send_id unic
     let send_id = format!(
@@ -133,22 +277,12 @@ pub async fn send_files_to_editor(
     let addr = format!("{}:{}", service.host, service.port);
     println!("Conectare la editor: {} (send_id: {})", addr, send_id);
 
-    let mut stream = match TcpStream::connect(&addr) {
-        Ok(s) => s,
-        Err(e) => emit_error!(format!("Nu m-am putut conecta la {}: {}", addr, e)),
+    let connected = connect_with_handshake(&addr, sender_name, &service.name, allow_legacy, &pairing);
+    let (mut stream, negotiated) = match connected {
+        Ok(connected) => connected,
+        Err(e) => emit_error!(e),
     };
 
-    // Setează timeout pentru operațiuni
-    if let Err(e) = stream.set_read_timeout(Some(std::time::Duration::from_secs(60))) {
-        emit_error!(format!("Eroare setare read timeout: {}", e));
-    }
-    if let Err(e) = stream.set_write_timeout(Some(std::time::Duration::from_secs(60))) {
-        emit_error!(format!("Eroare setare write timeout: {}", e));
-    }
-    if let Err(e) = stream.set_nodelay(true) {
-        emit_error!(format!("Eroare setare TCP nodelay: {}", e));
-    }
-
     println!("Conectat cu succes la {}", addr);
 
     // Construiește metadata FĂRĂ checksum
@@ -159,6 +293,7 @@ pub async fn send_files_to_editor(
             relative_path: f.relative_path.clone(), // Include calea relativă pentru subfoldere
             size: f.size,
             checksum: String::new(), // Fără checksum
+            attributes: FileAttributes::read(std::path::Path::new(&f.path)), // Păstrate la primire
         })
         .collect();
 
@@ -171,72 +306,58 @@ pub async fn send_files_to_editor(
         is_folder_transfer: folder_name.is_some(),
         folder_name,  // Trimite numele original al folderului (pentru receiver→receiver)
         sender_role: Some(sender_role.to_string()),
+        session: None,
+        // Aceeași parolă de eveniment ca pentru serverul propriu
+        event_key: (!event_passphrase.trim().is_empty()).then(|| event_key(event_passphrase)),
     };
 
-    let header_json = match serde_json::to_string(&header) {
-        Ok(j) => j,
-        Err(e) => emit_error!(format!("Eroare serializare header: {}", e)),
-    };
-    let header_bytes = header_json.as_bytes();
-
-    // Trimite lungimea header-ului (4 bytes, big endian)
-    println!("Trimit header ({} bytes)...", header_bytes.len());
-    if let Err(e) = stream.write_all(&(header_bytes.len() as u32).to_be_bytes()) {
-        emit_error!(format!("Eroare trimitere lungime header: {}", e));
-    }
-
-    // Trimite header-ul
-    if let Err(e) = stream.write_all(header_bytes) {
+    println!("Trimit header ({} fișiere)...", header.files.len());
+    if let Err(e) = write_json(&mut stream, &header) {
         emit_error!(format!("Eroare trimitere header: {}", e));
     }
-    if let Err(e) = stream.flush() {
-        emit_error!(format!("Eroare flush header: {}", e));
-    }
 
     println!("Header trimis, aștept ACK...");
 
     // Așteaptă ACK
-    let mut ack_len_buf = [0u8; 4];
-    if let Err(e) = stream.read_exact(&mut ack_len_buf) {
-        emit_error!(format!("Eroare citire lungime ACK: {}", e));
-    }
-    let ack_len = u32::from_be_bytes(ack_len_buf) as usize;
-
-    let mut ack_buf = vec![0u8; ack_len];
-    if let Err(e) = stream.read_exact(&mut ack_buf) {
-        emit_error!(format!("Eroare citire ACK: {}", e));
-    }
-
-    let ack: AckResponse = match serde_json::from_slice(&ack_buf) {
+    let ack: AckResponse = match read_json(&mut stream, MAX_HEADER_LEN) {
         Ok(a) => a,
-        Err(e) => emit_error!(format!("Eroare parsare ACK: {}", e)),
+        Err(e) => emit_error!(format!("Eroare citire ACK: {}", e)),
     };
 
     println!("ACK primit: status={}", ack.status);
 
-    if ack.status != "ready" {
-        emit_error!(format!("Receiver nu e gata: {}", ack.status));
+    if !ack.is_ready() {
+        emit_error!(ack.refusal());
     }
 
     // Trimite lista de fișiere de transferat (serverul așteaptă această listă)
     // Folosim relative_path pentru a identifica corect fișierele cu subfoldere
     println!("Trimit lista de {} fișiere...", files.len());
     let files_to_send: Vec<String> = files.iter().map(|f| f.relative_path.clone()).collect();
-    let decision_json = match serde_json::to_string(&files_to_send) {
-        Ok(j) => j,
-        Err(e) => emit_error!(format!("Eroare serializare lista fișiere: {}", e)),
+
+    // Fișierele întrerupte anterior se reiau de unde au rămas (verificat prin hash)
+    let use_checksum = negotiated.has(CAP_CHECKSUM);
+    let use_file_result = negotiated.has(CAP_FILE_RESULT);
+    let mut resume_points = if use_checksum && negotiated.has(CAP_RESUME) {
+        find_resume_points(files, &ack.partial_files)
+    } else {
+        HashMap::new()
     };
-    let decision_bytes = decision_json.as_bytes();
 
-    if let Err(e) = stream.write_all(&(decision_bytes.len() as u32).to_be_bytes()) {
-        emit_error!(format!("Eroare trimitere decizie len: {}", e));
-    }
-    if let Err(e) = stream.write_all(decision_bytes) {
+    // Offset-urile se trimit doar dacă există reluări - receiver-ele vechi înțeleg doar lista simplă.
+    // Fără acțiuni pentru duplicate: decide politica receiver-ului destinație.
+    let decision = SendDecision::new(
+        files_to_send,
+        resume_points.iter().map(|(name, (offset, _))| (name.clone(), *offset)).collect(),
+        HashMap::new(),
+    );
+    if let Err(e) = write_json(&mut stream, &decision) {
         emit_error!(format!("Eroare trimitere decizie: {}", e));
     }
 
     // Trimite fișierele
     let mut total_sent: u64 = 0;
+    let mut total_resumed: u64 = 0; // Bytes aflați deja pe receiver (nu intră în calculul vitezei)
     let start_time = Instant::now();
 
     for (index, file) in files.iter().enumerate() {
@@ -258,75 +379,123 @@ pub async fn send_files_to_editor(
             }
         }
 
-        let mut file_handle = match open_file_for_read(&file.path) {
-            Ok(f) => f,
-            Err(e) => emit_error!(format!("Nu pot deschide {}: {} (path: {})", file.name, e, file.path)),
-        };
+        let mut attempt: u32 = 1;
 
-        let mut buffer = vec![0u8; CHUNK_SIZE];
-        let mut file_sent: u64 = 0;
+        loop {
+            let mut file_handle = match open_file_for_read(&file.path) {
+                Ok(f) => f,
+                Err(e) => emit_error!(format!("Nu pot deschide {}: {} (path: {})", file.name, e, file.path)),
+            };
 
-        while file_sent < file.size {
-            // Verifică dacă transferul a fost anulat
-            if is_cancelled.load(Ordering::Relaxed) {
-                println!("Transfer anulat de utilizator (send_id: {})", send_id);
-                let _ = window.emit("send-cancelled", SendResult {
-                    send_id: send_id.clone(),
-                    target_name: target_name.clone(),
-                    file_count: files.len(),
-                });
-                return Err("Transfer anulat".to_string());
+            // Reluarea se aplică doar la prima încercare; după un checksum greșit se trimite tot
+            let (resume_offset, mut hasher) = match resume_points.remove(&file.relative_path) {
+                Some(point) if attempt == 1 => point,
+                _ => (0, blake3::Hasher::new()),
+            };
+            if resume_offset > 0 {
+                if let Err(e) = file_handle.seek(SeekFrom::Start(resume_offset)) {
+                    emit_error!(format!("Eroare poziționare {}: {}", file.name, e));
+                }
+                println!("Reiau {} de la byte-ul {}", file.relative_path, resume_offset);
             }
 
-            let bytes_read = match file_handle.read(&mut buffer) {
-                Ok(n) => n,
-                Err(e) => emit_error!(format!("Eroare citire {}: {}", file.name, e)),
-            };
+            let mut buffer = vec![0u8; CHUNK_SIZE];
+            let mut file_sent: u64 = resume_offset;
+            total_sent += resume_offset;
+            total_resumed += resume_offset;
+
+            while file_sent < file.size {
+                // Verifică dacă transferul a fost anulat
+                if is_cancelled.load(Ordering::Relaxed) {
+                    println!("Transfer anulat de utilizator (send_id: {})", send_id);
+                    let _ = window.emit("send-cancelled", SendResult {
+                        send_id: send_id.clone(),
+                        target_name: target_name.clone(),
+                        file_count: files.len(),
+                    });
+                    return Err("Transfer anulat".to_string());
+                }
 
-            if bytes_read == 0 {
-                break;
-            }
+                let bytes_read = match file_handle.read(&mut buffer) {
+                    Ok(n) => n,
+                    Err(e) => emit_error!(format!("Eroare citire {}: {}", file.name, e)),
+                };
 
-            if let Err(e) = stream.write_all(&buffer[..bytes_read]) {
-                emit_error!(format!("Eroare trimitere {}: {}", file.name, e));
-            }
+                if bytes_read == 0 {
+                    break;
+                }
 
-            file_sent += bytes_read as u64;
-            total_sent += bytes_read as u64;
+                hasher.update(&buffer[..bytes_read]);
 
-            // Calculează viteza
-            let elapsed = start_time.elapsed().as_secs_f64();
-            let speed_mbps = if elapsed > 0.0 {
-                (total_sent as f64 / elapsed) / (1024.0 * 1024.0)
-            } else {
-                0.0
-            };
+                if let Err(e) = stream.write_all(&buffer[..bytes_read]) {
+                    emit_error!(format!("Eroare trimitere {}: {}", file.name, e));
+                }
 
-            // Trimite progress la UI
-            let progress = SendProgress {
-                send_id: send_id.clone(),
-                file_name: file.name.clone(),
-                file_index: index,
-                total_files: files.len(),
-                bytes_sent: total_sent,
-                total_bytes,
-                speed_mbps,
-                target_name: target_name.clone(),
-            };
+                file_sent += bytes_read as u64;
+                total_sent += bytes_read as u64;
 
-            let _ = window.emit("send-progress", &progress);
-        }
+                // Calculează viteza
+                let elapsed = start_time.elapsed().as_secs_f64();
+                let speed_mbps = if elapsed > 0.0 {
+                    ((total_sent - total_resumed) as f64 / elapsed) / (1024.0 * 1024.0)
+                } else {
+                    0.0
+                };
 
-        // Așteaptă confirmare pentru fișier (fără checksum)
-        let mut response = [0u8; 32];
-        let n = match stream.read(&mut response) {
-            Ok(n) => n,
-            Err(e) => emit_error!(format!("Eroare citire confirmare: {}", e)),
-        };
+                // Trimite progress la UI
+                let progress = SendProgress {
+                    send_id: send_id.clone(),
+                    file_name: file.name.clone(),
+                    file_index: index,
+                    total_files: files.len(),
+                    bytes_sent: total_sent,
+                    total_bytes,
+                    speed_mbps,
+                    target_name: target_name.clone(),
+                };
 
-        let response_str = String::from_utf8_lossy(&response[..n]);
-        if !response_str.contains("OK") {
-            emit_error!(format!("Eroare la fișierul {}: {}", file.name, response_str));
+                let _ = window.emit("send-progress", &progress);
+            }
+
+            // Trimite hash-ul fișierului, dacă receiver-ul l-a acceptat
+            if use_checksum {
+                if let Err(e) = write_checksum(&mut stream, &hasher.finalize()) {
+                    emit_error!(format!("Eroare trimitere checksum: {}", e));
+                }
+            }
+
+            // Așteaptă confirmare pentru fișier
+            let response = match FileAck::read_from(&mut stream, use_file_result) {
+                Ok(r) => r,
+                Err(e) => emit_error!(format!("Eroare citire confirmare: {}", e)),
+            };
+
+            match response.status {
+                // Receiver-ele noi confirmă și câți bytes au ajuns pe disc
+                FileStatus::Ok if use_file_result && response.bytes_written != file.size => {
+                    emit_error!(format!(
+                        "Receiver-ul a salvat {} din {} bytes pentru {}",
+                        response.bytes_written, file.size, file.name
+                    ));
+                }
+                FileStatus::Ok => break,
+                FileStatus::ChecksumMismatch if attempt >= MAX_CHECKSUM_RETRIES => {
+                    emit_error!(format!(
+                        "Checksum diferit pentru {} după {} încercări",
+                        file.name, MAX_CHECKSUM_RETRIES
+                    ));
+                }
+                FileStatus::ChecksumMismatch => {
+                    println!(
+                        "Checksum diferit pentru {} (încercarea {}/{}), retrimit...",
+                        file.name, attempt, MAX_CHECKSUM_RETRIES
+                    );
+                    total_sent -= file_sent;
+                    total_resumed -= resume_offset;
+                    attempt += 1;
+                }
+                FileStatus::Failed => emit_error!(response.reason(&file.name)),
+            }
         }
     }
 
@@ -16,6 +16,43 @@ interface ReceiverConfig {
   current_day: string;
   reset_numbering_daily: boolean;
   port: number;
+  allow_legacy_senders: boolean;
+  legacy_receivers: string[];
+  event_passphrase: string;
+  allowed_senders: string[];
+  blocked_senders: string[];
+  disk_reserve_mb: number;
+  backup_paths: string[];
+  backup_required: boolean;
+  duplicate_policy: "skip" | "overwrite" | "keep_both";
+  event_days: EventDay[];
+  day_cutoff_hour: number;
+}
+
+interface EventDay {
+  date: string; // YYYY-MM-DD
+  label: string;
+}
+
+interface ProfileInfo {
+  id: string;
+  name: string;
+  base_path: string;
+  current_day: string;
+  archived: boolean;
+  active: boolean;
+}
+
+interface TrustedPeer {
+  public_key: string;
+  name: string;
+  paired_at: number;
+}
+
+interface PairingInfo {
+  pin: string;
+  fingerprint: string;
+  devices: TrustedPeer[];
 }
 
 interface TransferProgress {
@@ -39,6 +76,16 @@ interface TransferRecord {
   day: string | null;
   status?: "Complete" | "Partial" | "Error";
   source_role?: string | null; // "tagger", "editor", or null (fotograf)
+  backups?: BackupCopy[];
+}
+
+interface BackupCopy {
+  root: string;
+  folder: string;
+  file_count: number;
+  total_size: number;
+  status: "Complete" | "Partial" | "Error";
+  error?: string | null;
 }
 
 interface DiscoveredEditor {
@@ -152,6 +199,7 @@ async function loadConfig() {
       if (isRunning) {
         statusIndicator.classList.add("online");
         statusText.textContent = "Online";
+        await restoreActiveTransfers();
       } else {
         await startServer();
       }
@@ -216,6 +264,17 @@ function setupEventListeners() {
       current_day: "DAY 1",
       reset_numbering_daily: true,
       port: 45678,
+      allow_legacy_senders: false,
+      legacy_receivers: [],
+      event_passphrase: "",
+      allowed_senders: [],
+      blocked_senders: [],
+      disk_reserve_mb: 1024,
+      backup_paths: [],
+      backup_required: false,
+      duplicate_policy: "overwrite",
+      event_days: [],
+      day_cutoff_hour: 4,
     };
 
     try {
@@ -238,6 +297,38 @@ function setupEventListeners() {
     }
   });
 
+  // Rapoarte (CSV, JSON și HTML) în folderul ales
+  document.getElementById("btn-report-event")!.addEventListener("click", () => exportReport(null));
+  document.getElementById("btn-report-day")!.addEventListener("click", () => exportReport(config?.current_day ?? null));
+
+  // Profiluri de eveniment
+  document.getElementById("profile-new-browse")!.addEventListener("click", async () => {
+    const folder = await selectFolder();
+    if (folder) {
+      (document.getElementById("profile-new-folder") as HTMLInputElement).value = folder;
+    }
+  });
+
+  document.getElementById("profile-create")!.addEventListener("click", async () => {
+    const nameInput = document.getElementById("profile-new-name") as HTMLInputElement;
+    const folderInput = document.getElementById("profile-new-folder") as HTMLInputElement;
+    try {
+      const profile = await invoke<ProfileInfo>("create_profile", {
+        name: nameInput.value,
+        basePath: folderInput.value,
+      });
+      nameInput.value = "";
+      folderInput.value = "";
+      await switchProfile(profile);
+    } catch (e) {
+      showToast(`Eroare: ${e}`, "error");
+    }
+  });
+
+  // Previzualizarea numelui de folder se actualizează la fiecare modificare a template-ului
+  document.getElementById("settings-template")!.addEventListener("input", updateTemplatePreview);
+  document.getElementById("settings-template-sender")!.addEventListener("input", updateTemplatePreview);
+
   settingsForm.addEventListener("submit", async (e) => {
     e.preventDefault();
 
@@ -251,13 +342,33 @@ function setupEventListeners() {
     config.role = newRole;
     config.name = (document.getElementById("settings-name") as HTMLInputElement).value.trim();
     config.base_path = (document.getElementById("settings-folder") as HTMLInputElement).value;
-    config.folder_template = (document.getElementById("settings-template") as HTMLSelectElement).value;
+    config.folder_template = (document.getElementById("settings-template") as HTMLInputElement).value.trim();
     config.use_day_folders = (document.getElementById("settings-day-folders") as HTMLInputElement).checked;
     config.reset_numbering_daily = (document.getElementById("settings-reset-daily") as HTMLInputElement).checked;
     config.port = newPort;
+    config.allow_legacy_senders = (document.getElementById("settings-allow-legacy-senders") as HTMLInputElement).checked;
+    config.event_passphrase = (document.getElementById("settings-event-passphrase") as HTMLInputElement).value.trim();
+    config.allowed_senders = parseSenderList("settings-allowed-senders");
+    config.blocked_senders = parseSenderList("settings-blocked-senders");
+    const reserveGb = parseFloat((document.getElementById("settings-disk-reserve") as HTMLInputElement).value);
+    config.disk_reserve_mb = Math.max(0, Math.round((isNaN(reserveGb) ? 1 : reserveGb) * 1024));
+    config.backup_paths = parseSenderList("settings-backup-paths");
+    config.backup_required = (document.getElementById("settings-backup-required") as HTMLInputElement).checked;
+    config.duplicate_policy = (document.getElementById("settings-duplicate-policy") as HTMLSelectElement)
+      .value as ReceiverConfig["duplicate_policy"];
+    const cutoff = parseInt((document.getElementById("settings-day-cutoff") as HTMLInputElement).value, 10);
+    config.day_cutoff_hour = isNaN(cutoff) ? 4 : cutoff;
+    try {
+      config.event_days = parseEventDays();
+    } catch (e) {
+      showToast(`${e}`, "error");
+      return;
+    }
 
     try {
       await invoke("save_config", { config });
+      // Programul nou poate schimba ziua curentă
+      showCurrentDay((await invoke<ReceiverConfig>("get_config")).current_day);
 
       // Dacă portul sau rolul s-au schimbat, restartează serverul automat
       if (roleChanged || portChanged) {
@@ -293,16 +404,8 @@ function setupEventListeners() {
   const dayPrev = document.getElementById("day-prev")!;
   const dayNext = document.getElementById("day-next")!;
 
-  currentDayInput.addEventListener("change", async () => {
-    if (!config) return;
-    config.current_day = currentDayInput.value;
-    try {
-      await invoke("save_config", { config });
-      await loadDayCounter(); // Reload counter for new day
-    } catch (e) {
-      console.error("Error saving day:", e);
-    }
-  });
+  currentDayInput.addEventListener("change", () => setCurrentDay(currentDayInput.value));
+  document.getElementById("day-auto")!.addEventListener("click", () => setCurrentDay(null));
 
   // Day counter input
   const dayCounterInput = document.getElementById("day-counter") as HTMLInputElement;
@@ -326,28 +429,14 @@ function setupEventListeners() {
     if (!config) return;
     const { prefix, num } = extractDayParts(config.current_day);
     if (num > 1) {
-      config.current_day = `${prefix}${num - 1}`;
-      currentDayInput.value = config.current_day;
-      try {
-        await invoke("save_config", { config });
-        await loadDayCounter();
-      } catch (e) {
-        console.error("Error saving day:", e);
-      }
+      await setCurrentDay(`${prefix}${num - 1}`);
     }
   });
 
   dayNext.addEventListener("click", async () => {
     if (!config) return;
     const { prefix, num } = extractDayParts(config.current_day);
-    config.current_day = `${prefix}${num + 1}`;
-    currentDayInput.value = config.current_day;
-    try {
-      await invoke("save_config", { config });
-      await loadDayCounter();
-    } catch (e) {
-      console.error("Error saving day:", e);
-    }
+    await setCurrentDay(`${prefix}${num + 1}`);
   });
 
   // Tabs
@@ -395,6 +484,13 @@ async function setupTauriListeners() {
     statusText.textContent = "Oprit";
   });
 
+  // Programul evenimentului a trecut la ziua următoare
+  await listen<string>("day-changed", (event) => {
+    if (!config || config.current_day === event.payload) return;
+    showCurrentDay(event.payload);
+    showToast(`Ziua curenta: ${event.payload}`, "success");
+  });
+
   await listen<{transfer_id: string, photographer: string}>("transfer-started", (event) => {
     const { photographer } = event.payload;
     showToast(`Transfer de la ${photographer}...`, "success");
@@ -460,10 +556,130 @@ async function setupTauriListeners() {
     loadHistory();
   });
 
+  // PIN-ul se schimbă după fiecare încercare de asociere
+  await listen<string>("pairing-pin", (event) => {
+    document.getElementById("pairing-pin")!.textContent = event.payload;
+  });
+
+  await listen<string>("pairing-requested", async (event) => {
+    const pin = document.getElementById("pairing-pin")!.textContent;
+    showToast(`${event.payload} cere asociere - PIN: ${pin}`, "error");
+  });
+
+  await listen<string>("transfer-rejected", (event) => {
+    showToast(`Transfer refuzat - ${event.payload}`, "error");
+  });
+
+  await listen<string>("device-paired", (event) => {
+    showToast(`Dispozitiv asociat: ${event.payload}`, "success");
+    loadPairingInfo();
+  });
+
   // Send listeners
   await setupSendListeners();
 }
 
+async function loadPairingInfo() {
+  try {
+    const info = await invoke<PairingInfo>("get_pairing_info");
+    document.getElementById("pairing-pin")!.textContent = info.pin;
+    document.getElementById("pairing-fingerprint")!.textContent = info.fingerprint;
+
+    const list = document.getElementById("paired-devices")!;
+    list.innerHTML = "";
+    for (const device of info.devices) {
+      const row = document.createElement("div");
+      row.className = "paired-device";
+      const name = document.createElement("span");
+      name.textContent = device.name || device.public_key.slice(0, 12);
+      const btn = document.createElement("button");
+      btn.type = "button";
+      btn.className = "btn btn-secondary";
+      btn.textContent = "Uita";
+      btn.addEventListener("click", async () => {
+        await invoke("forget_paired_device", { publicKey: device.public_key });
+        loadPairingInfo();
+      });
+      row.append(name, btn);
+      list.appendChild(row);
+    }
+  } catch (e) {
+    console.error("Eroare încărcare asocieri:", e);
+  }
+}
+
+function isPairingRequired(error: unknown): boolean {
+  return String(error).includes("pairing_required");
+}
+
+function isLegacyReceiver(error: unknown): boolean {
+  return String(error).includes("legacy_receiver");
+}
+
+// Editor vechi, fără criptare: fișierele pleacă în clar doar cu acordul explicit pentru el
+async function allowLegacyEditor(editor: DiscoveredEditor): Promise<boolean> {
+  const allowed = confirm(
+    `${editor.name} folosește o versiune veche, fără criptare.\n\n` +
+    "Fișierele vor circula necriptat prin rețea. Permiți trimiterea necriptată către acest editor?"
+  );
+  if (allowed) {
+    await invoke("set_legacy_receiver", { targetName: editor.name, allowed: true });
+  }
+  return allowed;
+}
+
+// Cere PIN-ul afișat pe editor și asociază cele două computere
+function pairWithEditor(editor: DiscoveredEditor): Promise<boolean> {
+  const modal = document.getElementById("pairing-modal")!;
+  const input = document.getElementById("pairing-pin-input") as HTMLInputElement;
+  const confirmBtn = document.getElementById("pairing-confirm")!;
+  const cancelBtn = document.getElementById("pairing-cancel")!;
+
+  document.getElementById("pairing-target")!.textContent = editor.name;
+  input.value = "";
+  modal.style.display = "flex";
+  input.focus();
+
+  return new Promise((resolve) => {
+    const close = (paired: boolean) => {
+      modal.style.display = "none";
+      confirmBtn.onclick = null;
+      cancelBtn.onclick = null;
+      resolve(paired);
+    };
+
+    confirmBtn.onclick = async () => {
+      try {
+        const name = await invoke<string>("pair_with_editor", {
+          targetHost: editor.host,
+          targetPort: editor.port,
+          pin: input.value.trim(),
+        });
+        showToast(`Asociat cu ${name}`, "success");
+        close(true);
+      } catch (e) {
+        showToast(`${e}`, "error");
+        close(false);
+      }
+    };
+    cancelBtn.onclick = () => close(false);
+  });
+}
+
+async function updateTemplatePreview() {
+  const preview = document.getElementById("settings-template-preview")!;
+  const template = (document.getElementById("settings-template") as HTMLInputElement).value.trim();
+  const photographer = (document.getElementById("settings-template-sender") as HTMLInputElement).value.trim();
+  try {
+    const name = await invoke<string>("preview_folder_name", { photographer: photographer || "Ana Pop", template });
+    preview.textContent = `Urmatorul folder: ${name}`;
+    preview.classList.remove("template-error");
+  } catch (e) {
+    preview.textContent = `${e}`;
+    preview.classList.add("template-error");
+  }
+}
+
 function showMainContent() {
   setupScreen.style.display = "none";
   mainContent.style.display = "flex";
@@ -472,11 +688,25 @@ function showMainContent() {
     // Populate settings
     (document.getElementById("settings-name") as HTMLInputElement).value = config.name;
     (document.getElementById("settings-folder") as HTMLInputElement).value = config.base_path;
-    (document.getElementById("settings-template") as HTMLSelectElement).value = config.folder_template;
+    (document.getElementById("settings-template") as HTMLInputElement).value = config.folder_template;
+    updateTemplatePreview();
     (document.getElementById("settings-day-folders") as HTMLInputElement).checked = config.use_day_folders;
     (document.getElementById("settings-reset-daily") as HTMLInputElement).checked = config.reset_numbering_daily;
     (document.getElementById("current-day") as HTMLInputElement).value = config.current_day;
     (document.getElementById("settings-port") as HTMLInputElement).value = (config.port || 45678).toString();
+    (document.getElementById("settings-allow-legacy-senders") as HTMLInputElement).checked = config.allow_legacy_senders === true;
+    (document.getElementById("settings-event-passphrase") as HTMLInputElement).value = config.event_passphrase || "";
+    (document.getElementById("settings-allowed-senders") as HTMLTextAreaElement).value = (config.allowed_senders || []).join("\n");
+    (document.getElementById("settings-blocked-senders") as HTMLTextAreaElement).value = (config.blocked_senders || []).join("\n");
+    (document.getElementById("settings-disk-reserve") as HTMLInputElement).value = ((config.disk_reserve_mb ?? 1024) / 1024).toString();
+    (document.getElementById("settings-backup-paths") as HTMLTextAreaElement).value = (config.backup_paths || []).join("\n");
+    (document.getElementById("settings-backup-required") as HTMLInputElement).checked = config.backup_required ?? false;
+    (document.getElementById("settings-duplicate-policy") as HTMLSelectElement).value = config.duplicate_policy || "overwrite";
+    (document.getElementById("settings-event-days") as HTMLTextAreaElement).value = (config.event_days || [])
+      .map((d) => `${d.date} = ${d.label}`)
+      .join("\n");
+    (document.getElementById("settings-day-cutoff") as HTMLInputElement).value = (config.day_cutoff_hour ?? 4).toString();
+    updateDayAutoButton();
 
     // Set role radio button
     if (config.role === "tagger") {
@@ -488,12 +718,117 @@ function showMainContent() {
     updateUIForRole();
     loadHistory();
     loadSentHistory();
+    loadPairingInfo();
+    loadProfiles();
+  }
+}
+
+async function loadProfiles() {
+  try {
+    const profiles = await invoke<ProfileInfo[]>("list_profiles");
+    const list = document.getElementById("profile-list")!;
+    list.innerHTML = "";
+    for (const profile of profiles) {
+      const row = document.createElement("div");
+      row.className = `profile-row${profile.active ? " active" : ""}${profile.archived ? " archived" : ""}`;
+      const name = document.createElement("span");
+      name.className = "profile-name";
+      name.textContent = profile.name;
+      name.title = profile.base_path;
+      row.appendChild(name);
+
+      if (!profile.active && !profile.archived) {
+        const activate = document.createElement("button");
+        activate.type = "button";
+        activate.className = "btn btn-secondary";
+        activate.textContent = "Activeaza";
+        activate.addEventListener("click", () => switchProfile(profile));
+        row.appendChild(activate);
+      }
+      if (!profile.active) {
+        const archive = document.createElement("button");
+        archive.type = "button";
+        archive.className = "btn btn-secondary";
+        archive.textContent = profile.archived ? "Dezarhiveaza" : "Arhiveaza";
+        archive.addEventListener("click", async () => {
+          try {
+            await invoke("archive_profile", { id: profile.id, archived: !profile.archived });
+            loadProfiles();
+          } catch (e) {
+            showToast(`Eroare: ${e}`, "error");
+          }
+        });
+        row.appendChild(archive);
+      }
+      list.appendChild(row);
+    }
+  } catch (e) {
+    console.error("Eroare încărcare evenimente:", e);
+  }
+}
+
+// Serverul rămâne pornit; transferurile noi intră în evenimentul activat
+async function switchProfile(profile: ProfileInfo) {
+  try {
+    config = await invoke<ReceiverConfig>("switch_profile", { id: profile.id });
+    showMainContent();
+    showToast(`Eveniment activ: ${profile.name}`, "success");
+  } catch (e) {
+    showToast(`Eroare: ${e}`, "error");
+  }
+}
+
+// "2026-06-12 = DAY 1", câte o zi pe linie
+function parseEventDays(): EventDay[] {
+  return parseSenderList("settings-event-days").map((line) => {
+    const [date, ...label] = line.split("=");
+    if (!/^\d{4}-\d{2}-\d{2}$/.test(date.trim()) || label.join("=").trim() === "") {
+      throw new Error(`Linie invalida in program: "${line}" (ex. 2026-06-12 = DAY 1)`);
+    }
+    return { date: date.trim(), label: label.join("=").trim() };
+  });
+}
+
+// Ziua manuală sau, cu null, cea din programul evenimentului
+async function setCurrentDay(day: string | null) {
+  if (!config) return;
+  try {
+    showCurrentDay(await invoke<string>("set_current_day", { day }));
+  } catch (e) {
+    showToast(`Eroare: ${e}`, "error");
+    showCurrentDay(config.current_day);
   }
 }
 
+function showCurrentDay(day: string) {
+  if (!config) return;
+  config.current_day = day;
+  (document.getElementById("current-day") as HTMLInputElement).value = day;
+  updateDayAutoButton();
+  loadDayCounter();
+}
+
+// Butonul de revenire la program are sens doar când există un program
+function updateDayAutoButton() {
+  const hasSchedule = (config?.event_days || []).length > 0;
+  document.getElementById("day-auto")!.style.display = hasSchedule ? "block" : "none";
+}
+
+// O valoare pe linie (nume/amprenta sender, cale backup)
+function parseSenderList(id: string): string[] {
+  return (document.getElementById(id) as HTMLTextAreaElement).value
+    .split("\n")
+    .map((line) => line.trim())
+    .filter((line) => line.length > 0);
+}
+
 function updateUIForRole() {
   if (!config) return;
 
+  // Raportul pe zi are sens doar cu foldere pe zile
+  document.getElementById("btn-report-day")!.style.display =
+    config.role === "tagger" && config.use_day_folders ? "block" : "none";
+
   if (config.role === "tagger") {
     daySelector.style.display = "flex";
     taggerSettings.style.display = "block";
@@ -551,6 +886,17 @@ function switchTab(tabName: string) {
   }
 }
 
+// Transferurile în curs pe server (ex. după reîncărcarea ferestrei)
+async function restoreActiveTransfers() {
+  try {
+    const transfers = await invoke<TransferProgress[]>("list_active_transfers");
+    transfers.forEach((p) => activeTransfers.set(p.transfer_id, p));
+    updateTransfersUI();
+  } catch (e) {
+    console.error("Error loading active transfers:", e);
+  }
+}
+
 function updateTransfersUI() {
   const list = document.getElementById("transfers-list")!;
   const empty = document.getElementById("transfers-empty")!;
@@ -564,6 +910,23 @@ function updateTransfersUI() {
   empty.style.display = "none";
   list.innerHTML = "";
 
+  if (activeTransfers.size > 1) {
+    const cancelAll = document.createElement("button");
+    cancelAll.className = "btn-cancel-transfer btn-cancel-all";
+    cancelAll.textContent = "Anuleaza toate";
+    cancelAll.addEventListener("click", async () => {
+      try {
+        const count = await invoke<number>("cancel_all_transfers");
+        activeTransfers.clear();
+        updateTransfersUI();
+        showToast(`${count} transferuri anulate`, "error");
+      } catch (e) {
+        console.error("Error cancelling transfers:", e);
+      }
+    });
+    list.appendChild(cancelAll);
+  }
+
   activeTransfers.forEach((p, transferId) => {
     const percent = (p.bytes_received / p.total_bytes) * 100;
     const item = document.createElement("div");
@@ -587,7 +950,7 @@ function updateTransfersUI() {
     const cancelBtn = item.querySelector('.btn-cancel-transfer')!;
     cancelBtn.addEventListener('click', async () => {
       try {
-        await invoke("cancel_current_transfer");
+        await invoke("cancel_transfer", { transferId });
         activeTransfers.delete(transferId);
         updateTransfersUI();
         showToast(`Transfer de la ${p.photographer} anulat`, "error");
@@ -802,6 +1165,7 @@ async function loadHistory() {
                   <span class="history-name">${record.photographer}</span>
                   <span class="history-folder">${record.folder}</span>
                   <span class="history-details">${record.file_count} fisiere - ${formatSize(record.total_size)}</span>
+                  ${(record.backups || []).map(renderBackupCopy).join('')}
                 </div>
                 <div class="history-right">
                   <div class="history-time">${timeStr}</div>
@@ -819,6 +1183,12 @@ async function loadHistory() {
   }
 }
 
+function renderBackupCopy(copy: BackupCopy): string {
+  const state = copy.status === "Complete" ? "OK" : copy.status === "Partial" ? "incomplet" : "eroare";
+  const title = copy.error ? ` title="${copy.error.replace(/"/g, "&quot;")}"` : "";
+  return `<span class="history-backup backup-${copy.status.toLowerCase()}"${title}>Backup ${copy.root}: ${copy.file_count} fisiere - ${state}</span>`;
+}
+
 function extractDayParts(day: string): { prefix: string; num: number } {
   const match = day.match(/^(.*?)(\d+)\s*$/);
   if (match) {
@@ -891,6 +1261,17 @@ function handleDayResetClick(day: string) {
   }
 }
 
+async function exportReport(day: string | null) {
+  const folder = await selectFolder();
+  if (!folder) return;
+  try {
+    const files = await invoke<string[]>("export_report", { folder, filter: day ? { day } : null });
+    showToast(`Raport salvat: ${files.length} fisiere in ${folder}`, "success");
+  } catch (e) {
+    showToast(`Eroare raport: ${e}`, "error");
+  }
+}
+
 async function clearDayHistory(day: string) {
   try {
     await invoke("clear_history", { day });
@@ -1217,7 +1598,14 @@ async function sendFilesToEditor(paths: string[]) {
       }
 
       // Așteaptă toate transferurile (erorile sunt gestionate individual prin events)
-      await Promise.allSettled(promises);
+      const results = await Promise.allSettled(promises);
+      const needsPairing = results.some((r) => r.status === "rejected" && isPairingRequired(r.reason));
+      const isLegacy = results.some((r) => r.status === "rejected" && isLegacyReceiver(r.reason));
+      if (needsPairing && (await pairWithEditor(editor))) {
+        await sendFilesToEditor(paths);
+      } else if (isLegacy && (await allowLegacyEditor(editor))) {
+        await sendFilesToEditor(paths);
+      }
     } else {
       // Caz normal: un singur folder sau fișiere
       let folderName: string | null = null;
@@ -1236,6 +1624,13 @@ async function sendFilesToEditor(paths: string[]) {
   } catch (e) {
     // Eroarea este deja gestionată prin send-error event
     console.error("Send error:", e);
+
+    // Prima trimitere către un editor nou: asociere cu PIN, apoi reluăm trimiterea
+    if (isPairingRequired(e) && (await pairWithEditor(editor))) {
+      await sendFilesToEditor(paths);
+    } else if (isLegacyReceiver(e) && (await allowLegacyEditor(editor))) {
+      await sendFilesToEditor(paths);
+    }
   }
 }
 
@@ -0,0 +1,290 @@
+use crate::frame::{read_frame, read_json, write_frame, write_json};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// De câte ori se retrimite un fișier cu checksum greșit înainte de a renunța
+pub const MAX_CHECKSUM_RETRIES: u32 = 3;
+
+const ACK_OK: &[u8] = b"OK";
+const ACK_CHECKSUM_MISMATCH: &[u8] = b"CHECKSUM_MISMATCH";
+const MAX_CHECKSUM_LEN: usize = 128;
+const MAX_ACK_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Ok,
+    ChecksumMismatch, // Senderul trebuie să retrimită fișierul de la început
+    Failed,           // Receiver-ul n-a putut salva fișierul, transferul se oprește
+}
+
+/// De ce n-a putut receiver-ul salva un fișier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileErrorCode {
+    DiskFull,
+    PermissionDenied,
+    NameRejected, // Numele/calea nu poate fi creată pe receiver
+    Io,
+    #[serde(other)]
+    Unknown, // Cod adăugat de o versiune mai nouă
+}
+
+impl FileErrorCode {
+    pub fn from_io(error: &std::io::Error) -> Self {
+        use std::io::ErrorKind;
+
+        match error.kind() {
+            ErrorKind::StorageFull | ErrorKind::QuotaExceeded => FileErrorCode::DiskFull,
+            ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => FileErrorCode::PermissionDenied,
+            ErrorKind::InvalidFilename => FileErrorCode::NameRejected,
+            _ => FileErrorCode::Io,
+        }
+    }
+}
+
+/// Confirmarea receiver-ului pentru un fișier. Cu "file-result" negociat e un frame JSON;
+/// altfel bytes bruți ("OK", "CHECKSUM_MISMATCH" sau textul erorii), ca la versiunile vechi.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileAck {
+    pub status: FileStatus,
+    #[serde(default)]
+    pub bytes_written: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>, // Hash-ul BLAKE3 calculat de receiver
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<FileErrorCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Receiver-ul a citit datele fără să le salveze (duplicat sărit). Status `Ok` ca senderii
+    // vechi să continue; cei noi nu-l trec printre fișierele trimise.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skipped: bool,
+}
+
+impl FileAck {
+    pub fn ok(bytes_written: u64, checksum: &blake3::Hash) -> Self {
+        Self::with_status(FileStatus::Ok, bytes_written, checksum)
+    }
+
+    pub fn skipped(bytes_written: u64, checksum: &blake3::Hash) -> Self {
+        Self {
+            skipped: true,
+            ..Self::ok(bytes_written, checksum)
+        }
+    }
+
+    pub fn checksum_mismatch(bytes_written: u64, checksum: &blake3::Hash) -> Self {
+        Self::with_status(FileStatus::ChecksumMismatch, bytes_written, checksum)
+    }
+
+    pub fn failed(code: FileErrorCode, error: String) -> Self {
+        Self {
+            status: FileStatus::Failed,
+            bytes_written: 0,
+            checksum: None,
+            error_code: Some(code),
+            error: Some(error),
+            skipped: false,
+        }
+    }
+
+    fn with_status(status: FileStatus, bytes_written: u64, checksum: &blake3::Hash) -> Self {
+        Self {
+            status,
+            bytes_written,
+            checksum: Some(checksum.to_hex().to_string()),
+            error_code: None,
+            error: None,
+            skipped: false,
+        }
+    }
+
+    /// Răspunsul brut al unui receiver vechi
+    pub fn parse(response: &[u8]) -> Self {
+        let status = if response == ACK_CHECKSUM_MISMATCH {
+            FileStatus::ChecksumMismatch
+        } else if response == ACK_OK {
+            FileStatus::Ok
+        } else {
+            FileStatus::Failed
+        };
+        Self {
+            status,
+            bytes_written: 0,
+            checksum: None,
+            error_code: None,
+            error: (status == FileStatus::Failed).then(|| String::from_utf8_lossy(response).to_string()),
+            skipped: false,
+        }
+    }
+
+    /// Mesaj pentru UI-ul senderului când receiver-ul n-a putut salva fișierul
+    pub fn reason(&self, file_name: &str) -> String {
+        let what = match self.error_code {
+            Some(FileErrorCode::DiskFull) => format!("Disc plin pe receiver la fișierul {}", file_name),
+            Some(FileErrorCode::PermissionDenied) => {
+                format!("Receiver-ul nu are permisiunea să scrie fișierul {}", file_name)
+            }
+            Some(FileErrorCode::NameRejected) => format!("Receiver-ul a refuzat numele fișierului {}", file_name),
+            _ => format!("Eroare la fișierul {}", file_name),
+        };
+        match self.error {
+            Some(ref error) if !error.is_empty() => format!("{}: {}", what, error),
+            _ => what,
+        }
+    }
+
+    /// Trimite confirmarea: frame JSON dacă s-a negociat "file-result", altfel bytes bruți
+    pub fn write_to<W: Write>(&self, writer: &mut W, framed: bool) -> std::io::Result<()> {
+        if framed {
+            return write_json(writer, self);
+        }
+        match self.status {
+            FileStatus::Ok => writer.write_all(ACK_OK),
+            FileStatus::ChecksumMismatch => writer.write_all(ACK_CHECKSUM_MISMATCH),
+            FileStatus::Failed => writer.write_all(self.error.as_deref().unwrap_or_default().as_bytes()),
+        }
+    }
+
+    /// Citește confirmarea. Fără frame, receiver-ul vechi trimite un singur răspuns și
+    /// așteaptă următorul fișier, deci un singur read primește tot mesajul.
+    pub fn read_from<R: Read>(reader: &mut R, framed: bool) -> std::io::Result<Self> {
+        if framed {
+            return read_json(reader, MAX_ACK_LEN);
+        }
+
+        let mut response = [0u8; 32];
+        let n = reader.read(&mut response)?;
+        if n == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Conexiune închisă înainte de confirmare",
+            ));
+        }
+        Ok(Self::parse(&response[..n]))
+    }
+}
+
+/// Trimite hash-ul fișierului după datele lui (frame cu hex-ul BLAKE3)
+pub fn write_checksum<W: Write>(writer: &mut W, hash: &blake3::Hash) -> std::io::Result<()> {
+    write_frame(writer, hash.to_hex().as_bytes())
+}
+
+/// Citește hash-ul trimis de sender după datele unui fișier
+pub fn read_checksum<R: Read>(reader: &mut R) -> std::io::Result<String> {
+    let buf = read_frame(reader, MAX_CHECKSUM_LEN)?;
+    String::from_utf8(buf).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn file_ack_round_trip() {
+        let hash = blake3::hash(b"RAW data");
+        for ack in [
+            FileAck::ok(8, &hash),
+            FileAck::skipped(8, &hash),
+            FileAck::checksum_mismatch(8, &hash),
+            FileAck::failed(FileErrorCode::DiskFull, "No space left on device".to_string()),
+        ] {
+            let mut wire = Vec::new();
+            ack.write_to(&mut wire, true).unwrap();
+            assert_eq!(FileAck::read_from(&mut Cursor::new(wire), true).unwrap(), ack);
+        }
+    }
+
+    #[test]
+    fn coalesced_acks_are_read_separately() {
+        let hash = blake3::hash(b"RAW data");
+        let mut wire = Vec::new();
+        FileAck::ok(8, &hash).write_to(&mut wire, true).unwrap();
+        FileAck::failed(FileErrorCode::NameRejected, String::new()).write_to(&mut wire, true).unwrap();
+
+        let mut reader = Cursor::new(wire);
+        assert_eq!(FileAck::read_from(&mut reader, true).unwrap().status, FileStatus::Ok);
+        let second = FileAck::read_from(&mut reader, true).unwrap();
+        assert_eq!(second.error_code, Some(FileErrorCode::NameRejected));
+        assert_eq!(second.reason("a.jpg"), "Receiver-ul a refuzat numele fișierului a.jpg");
+    }
+
+    #[test]
+    fn legacy_ack_round_trip() {
+        let hash = blake3::hash(b"RAW data");
+        for (ack, wire) in [
+            (FileAck::ok(8, &hash), b"OK".as_slice()),
+            (FileAck::checksum_mismatch(8, &hash), b"CHECKSUM_MISMATCH".as_slice()),
+            (FileAck::failed(FileErrorCode::Io, "ERR".to_string()), b"ERR".as_slice()),
+        ] {
+            let mut written = Vec::new();
+            ack.write_to(&mut written, false).unwrap();
+            assert_eq!(written, wire);
+            assert_eq!(FileAck::read_from(&mut Cursor::new(written), false).unwrap().status, ack.status);
+        }
+    }
+
+    #[test]
+    fn legacy_ok_is_understood() {
+        assert_eq!(FileAck::parse(b"OK").status, FileStatus::Ok);
+        let failed = FileAck::parse(b"ERR");
+        assert_eq!(failed.status, FileStatus::Failed);
+        assert_eq!(failed.reason("a.jpg"), "Eroare la fișierul a.jpg: ERR");
+    }
+
+    #[test]
+    fn skipped_ack_stays_ok_for_old_senders() {
+        let hash = blake3::hash(b"RAW data");
+        let json = serde_json::to_value(FileAck::skipped(8, &hash)).unwrap();
+        assert_eq!(json["status"], "ok");
+        assert_eq!(json["skipped"], true);
+        assert!(serde_json::to_value(FileAck::ok(8, &hash)).unwrap().get("skipped").is_none());
+
+        let old: FileAck = serde_json::from_str(r#"{"status":"ok","bytes_written":8}"#).unwrap();
+        assert!(!old.skipped);
+
+        let mut legacy = Vec::new();
+        FileAck::skipped(8, &hash).write_to(&mut legacy, false).unwrap();
+        assert_eq!(legacy, b"OK");
+    }
+
+    #[test]
+    fn unknown_error_code_is_tolerated() {
+        let ack: FileAck = serde_json::from_str(r#"{"status":"failed","error_code":"quota_locked"}"#).unwrap();
+        assert_eq!(ack.error_code, Some(FileErrorCode::Unknown));
+        assert_eq!(ack.bytes_written, 0);
+    }
+
+    #[test]
+    fn io_errors_map_to_codes() {
+        use std::io::{Error, ErrorKind};
+
+        assert_eq!(FileErrorCode::from_io(&Error::from(ErrorKind::StorageFull)), FileErrorCode::DiskFull);
+        assert_eq!(
+            FileErrorCode::from_io(&Error::from(ErrorKind::PermissionDenied)),
+            FileErrorCode::PermissionDenied
+        );
+        assert_eq!(FileErrorCode::from_io(&Error::from(ErrorKind::InvalidFilename)), FileErrorCode::NameRejected);
+        assert_eq!(FileErrorCode::from_io(&Error::from(ErrorKind::BrokenPipe)), FileErrorCode::Io);
+    }
+
+    #[test]
+    fn closed_connection_is_an_error() {
+        for framed in [false, true] {
+            let err = FileAck::read_from(&mut Cursor::new(Vec::new()), framed).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[test]
+    fn checksum_round_trip() {
+        let hash = blake3::hash(b"RAW data");
+        let mut wire = Vec::new();
+        write_checksum(&mut wire, &hash).unwrap();
+
+        let received = read_checksum(&mut Cursor::new(wire)).unwrap();
+        assert_eq!(received, hash.to_hex().as_str());
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{File, FileTimes};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Timpii și flag-urile fișierului sursă. Receiver-ul le aplică după scriere, ca fișierul să
+/// nu pară nou (sortare după dată la taggeri/editori, backup-uri incrementale).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileAttributes {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_ms: Option<u64>, // Milisecunde de la epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub readonly: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<u8>, // Eticheta de culoare din Finder (1-7), doar pe macOS
+}
+
+impl FileAttributes {
+    /// Atributele unui fișier local. Ce nu poate fi citit rămâne necompletat.
+    pub fn read(path: &Path) -> Self {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return Self::default();
+        };
+        Self {
+            modified_ms: metadata.modified().ok().and_then(to_millis),
+            created_ms: metadata.created().ok().and_then(to_millis),
+            readonly: metadata.permissions().readonly(),
+            label: read_label(path),
+        }
+    }
+
+    /// Setează timpii pe fișierul deschis, după ultima scriere (altfel scrierea îi suprascrie).
+    /// Data creării se poate seta doar pe Windows și macOS.
+    pub fn apply_times(&self, file: &File) -> std::io::Result<()> {
+        let mut times = FileTimes::new();
+        if let Some(modified) = self.modified_ms {
+            times = times.set_modified(from_millis(modified));
+        }
+        #[cfg(any(windows, target_os = "macos"))]
+        if let Some(created) = self.created_ms {
+            #[cfg(target_os = "macos")]
+            use std::os::macos::fs::FileTimesExt;
+            #[cfg(windows)]
+            use std::os::windows::fs::FileTimesExt;
+
+            times = times.set_created(from_millis(created));
+        }
+
+        if self.modified_ms.is_none() && self.created_ms.is_none() {
+            return Ok(());
+        }
+        file.set_times(times)
+    }
+
+    /// Aplică flag-urile pe fișierul cu numele final (read-only ultimul - blochează restul)
+    pub fn apply_flags(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(label) = self.label {
+            write_label(path, label)?;
+        }
+        if self.readonly {
+            let mut permissions = std::fs::metadata(path)?.permissions();
+            permissions.set_readonly(true);
+            std::fs::set_permissions(path, permissions)?;
+        }
+        Ok(())
+    }
+}
+
+fn to_millis(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_millis() as u64)
+}
+
+fn from_millis(ms: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(ms)
+}
+
+// Eticheta Finder stă în biții 1-3 ai octetului 9 din FinderInfo (fdFlags, big endian)
+#[cfg(target_os = "macos")]
+const FINDER_INFO: &str = "com.apple.FinderInfo";
+#[cfg(target_os = "macos")]
+const FINDER_INFO_LEN: usize = 32;
+
+#[cfg(target_os = "macos")]
+fn read_label(path: &Path) -> Option<u8> {
+    let info = xattr::get(path, FINDER_INFO).ok()??;
+    let label = (info.get(9)? >> 1) & 0x07;
+    (label != 0).then_some(label)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn read_label(_path: &Path) -> Option<u8> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn write_label(path: &Path, label: u8) -> std::io::Result<()> {
+    let mut info = xattr::get(path, FINDER_INFO)?.unwrap_or_default();
+    if info.len() < FINDER_INFO_LEN {
+        info.resize(FINDER_INFO_LEN, 0);
+    }
+    info[9] = (info[9] & !0x0E) | ((label & 0x07) << 1);
+    xattr::set(path, FINDER_INFO, &info)
+}
+
+// Pe alte sisteme nu există etichete Finder - se ignoră
+#[cfg(not(target_os = "macos"))]
+fn write_label(_path: &Path, _label: u8) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("tp_attributes_{}_{}", std::process::id(), name));
+        std::fs::write(&path, b"RAW data").unwrap();
+        path
+    }
+
+    #[test]
+    fn modified_time_is_copied() {
+        let source = temp_file("source");
+        let target = temp_file("target");
+        let modified = from_millis(1_500_000_000_123);
+        File::options().write(true).open(&source).unwrap().set_modified(modified).unwrap();
+
+        let attributes = FileAttributes::read(&source);
+        assert_eq!(attributes.modified_ms, Some(1_500_000_000_123));
+        attributes.apply_times(&File::options().write(true).open(&target).unwrap()).unwrap();
+        assert_eq!(std::fs::metadata(&target).unwrap().modified().unwrap(), modified);
+
+        let _ = std::fs::remove_file(source);
+        let _ = std::fs::remove_file(target);
+    }
+
+    #[test]
+    fn readonly_flag_is_applied() {
+        let target = temp_file("readonly");
+        let attributes = FileAttributes { readonly: true, ..Default::default() };
+        attributes.apply_flags(&target).unwrap();
+        assert!(std::fs::metadata(&target).unwrap().permissions().readonly());
+
+        #[allow(clippy::permissions_set_readonly_false)]
+        {
+            let mut permissions = std::fs::metadata(&target).unwrap().permissions();
+            permissions.set_readonly(false);
+            std::fs::set_permissions(&target, permissions).unwrap();
+        }
+        let _ = std::fs::remove_file(target);
+    }
+
+    #[test]
+    fn empty_attributes_are_not_serialized() {
+        assert_eq!(serde_json::to_string(&FileAttributes::default()).unwrap(), "{}");
+        let attributes: FileAttributes = serde_json::from_str(r#"{"modified_ms":5,"readonly":true}"#).unwrap();
+        assert_eq!(attributes.modified_ms, Some(5));
+        assert!(attributes.readonly);
+    }
+}
@@ -0,0 +1,94 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{Read, Write};
+
+/// Limita pentru header-ul de transfer (lista de fișiere poate fi mare)
+pub const MAX_HEADER_LEN: usize = 64 * 1024 * 1024;
+
+/// Scrie un frame: lungime u32 big endian + payload
+pub fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> std::io::Result<()> {
+    let len = u32::try_from(payload.len()).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Frame prea mare: {} bytes", payload.len()),
+        )
+    })?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// Citește un frame, refuzând lungimi peste `max_len`
+pub fn read_frame<R: Read>(reader: &mut R, max_len: usize) -> std::io::Result<Vec<u8>> {
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf)?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len > max_len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Frame prea mare: {} bytes", len),
+        ));
+    }
+
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Serializează `value` ca JSON și îl trimite într-un frame
+pub fn write_json<W: Write, T: Serialize>(writer: &mut W, value: &T) -> std::io::Result<()> {
+    let json = serde_json::to_vec(value).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    write_frame(writer, &json)
+}
+
+/// Citește un frame și îl deserializează din JSON
+pub fn read_json<R: Read, T: DeserializeOwned>(reader: &mut R, max_len: usize) -> std::io::Result<T> {
+    let buf = read_frame(reader, max_len)?;
+    serde_json::from_slice(&buf).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn frame_round_trip() {
+        let mut wire = Vec::new();
+        write_frame(&mut wire, b"hello").unwrap();
+        write_frame(&mut wire, b"").unwrap();
+        assert_eq!(&wire[..4], &5u32.to_be_bytes());
+
+        let mut reader = Cursor::new(wire);
+        assert_eq!(read_frame(&mut reader, 16).unwrap(), b"hello");
+        assert!(read_frame(&mut reader, 16).unwrap().is_empty());
+    }
+
+    #[test]
+    fn frame_over_limit_is_rejected() {
+        let mut wire = Vec::new();
+        write_frame(&mut wire, &[0u8; 32]).unwrap();
+
+        let err = read_frame(&mut Cursor::new(wire), 16).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_frame_is_eof() {
+        let mut wire = Vec::new();
+        write_frame(&mut wire, b"hello").unwrap();
+        wire.truncate(6);
+
+        let err = read_frame(&mut Cursor::new(wire), 16).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut wire = Vec::new();
+        write_json(&mut wire, &vec!["a.jpg".to_string(), "b.cr3".to_string()]).unwrap();
+
+        let files: Vec<String> = read_json(&mut Cursor::new(wire), 1024).unwrap();
+        assert_eq!(files, vec!["a.jpg", "b.cr3"]);
+    }
+}
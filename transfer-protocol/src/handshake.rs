@@ -0,0 +1,312 @@
+use crate::frame::{read_frame, write_frame};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+// Handshake versionat. Primul frame al unei conexiuni noi este PROTOCOL_MAGIC urmat de
+// JSON-ul `Hello`. Un receiver vechi încearcă să-l parseze ca TransferHeader, eșuează
+// și închide conexiunea - senderul nou recunoaște asta și revine la protocolul vechi.
+pub const PROTOCOL_MAGIC: &[u8; 4] = b"PTXF";
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+// Capabilități negociabile. Nume rezervat pentru versiuni viitoare: "compression"
+pub const CAP_CHECKSUM: &str = "checksum-blake3";
+pub const CAP_RESUME: &str = "resume";
+pub const CAP_PARALLEL: &str = "parallel"; // Mai multe conexiuni pentru același transfer
+pub const CAP_ENCRYPTION: &str = "encryption"; // Canal Noise după handshake (vezi `secure`)
+pub const CAP_FILE_RESULT: &str = "file-result"; // Confirmare per fișier ca frame JSON (vezi `FileAck`)
+
+/// Capabilitățile implementate de această versiune a aplicației
+pub const SUPPORTED_CAPABILITIES: &[&str] =
+    &[CAP_CHECKSUM, CAP_RESUME, CAP_PARALLEL, CAP_ENCRYPTION, CAP_FILE_RESULT];
+
+const MAX_HANDSHAKE_FRAME: usize = 64 * 1024;
+
+/// Limita primului frame al unei conexiuni (preambul + `Hello`), citit înainte de orice autentificare.
+/// Header-ul transferului vine după handshake, cu limita lui mare.
+pub const MAX_HELLO_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HelloIntent {
+    #[default]
+    Transfer,
+    Info, // Doar nume + rol, conexiunea se închide după răspuns
+    Pair, // Asociere cu PIN-ul afișat pe receiver, conexiunea se închide după asociere
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hello {
+    pub version: u32,     // Cea mai nouă versiune suportată de client
+    pub min_version: u32, // Cea mai veche versiune acceptată de client
+    pub capabilities: Vec<String>,
+    #[serde(default)]
+    pub intent: HelloIntent,
+    #[serde(default)]
+    pub name: String, // Numele clientului (fotograf sau receiver), salvat la asociere
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HelloReply {
+    pub status: String, // "ok" sau "unsupported_version"
+    pub version: u32,   // Versiunea aleasă (sau cea a receiver-ului, la refuz)
+    pub capabilities: Vec<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub role: String,
+}
+
+/// Rezultatul negocierii: versiunea comună și capabilitățile pe care le au ambele părți
+#[derive(Debug, Clone, Default)]
+pub struct Negotiated {
+    pub version: u32, // 0 = protocolul vechi, fără handshake
+    pub capabilities: Vec<String>,
+}
+
+impl Negotiated {
+    pub fn legacy() -> Self {
+        Self::default()
+    }
+
+    pub fn has(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+impl Hello {
+    pub fn new(intent: HelloIntent) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            min_version: MIN_PROTOCOL_VERSION,
+            capabilities: SUPPORTED_CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+            intent,
+            name: String::new(),
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Răspunsul receiver-ului: alege cea mai nouă versiune comună sau refuză cu un mesaj lizibil
+    pub fn answer(&self, name: &str, role: &str) -> HelloReply {
+        let version = self.version.min(PROTOCOL_VERSION);
+        let compatible = version >= self.min_version.max(MIN_PROTOCOL_VERSION);
+
+        if !compatible {
+            let error = if self.min_version > PROTOCOL_VERSION {
+                format!(
+                    "Receiver-ul {} folosește protocolul v{}, senderul cere minim v{}. Actualizați aplicația receiver.",
+                    name, PROTOCOL_VERSION, self.min_version
+                )
+            } else {
+                format!(
+                    "Senderul folosește protocolul v{}, receiver-ul {} cere minim v{}. Actualizați aplicația sender.",
+                    self.version, name, MIN_PROTOCOL_VERSION
+                )
+            };
+            return HelloReply {
+                status: "unsupported_version".to_string(),
+                version: PROTOCOL_VERSION,
+                capabilities: Vec::new(),
+                error: Some(error),
+                name: name.to_string(),
+                role: role.to_string(),
+            };
+        }
+
+        HelloReply {
+            status: "ok".to_string(),
+            version,
+            capabilities: self
+                .capabilities
+                .iter()
+                .filter(|c| SUPPORTED_CAPABILITIES.contains(&c.as_str()))
+                .cloned()
+                .collect(),
+            error: None,
+            name: name.to_string(),
+            role: role.to_string(),
+        }
+    }
+}
+
+impl HelloReply {
+    pub fn negotiated(&self) -> Negotiated {
+        Negotiated {
+            version: self.version,
+            capabilities: self.capabilities.clone(),
+        }
+    }
+}
+
+/// Rezultatul handshake-ului din partea clientului
+pub enum ClientHandshake {
+    Accepted(HelloReply),
+    Legacy, // Receiver vechi - a închis conexiunea la vederea preambulului
+}
+
+/// Trimite preambulul și așteaptă răspunsul. Un refuz devine o eroare lizibilă.
+pub fn client_handshake<S: Read + Write>(stream: &mut S, hello: &Hello) -> Result<ClientHandshake, String> {
+    let hello = serde_json::to_vec(hello).map_err(|e| e.to_string())?;
+    let mut payload = PROTOCOL_MAGIC.to_vec();
+    payload.extend_from_slice(&hello);
+    write_frame(stream, &payload).map_err(|e| format!("Eroare trimitere handshake: {}", e))?;
+
+    let reply_buf = match read_frame(stream, MAX_HANDSHAKE_FRAME) {
+        Ok(buf) => buf,
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::UnexpectedEof
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+            ) =>
+        {
+            return Ok(ClientHandshake::Legacy);
+        }
+        Err(e) => return Err(format!("Eroare citire handshake: {}", e)),
+    };
+
+    let reply: HelloReply =
+        serde_json::from_slice(&reply_buf).map_err(|e| format!("Eroare parsare handshake: {}", e))?;
+
+    if reply.status != "ok" {
+        return Err(reply
+            .error
+            .unwrap_or_else(|| format!("Receiver-ul a refuzat conexiunea: {}", reply.status)));
+    }
+
+    Ok(ClientHandshake::Accepted(reply))
+}
+
+/// Dacă primul frame al conexiunii e un preambul, returnează Hello-ul din el
+pub fn parse_hello(frame: &[u8]) -> Option<Result<Hello, String>> {
+    let json = frame.strip_prefix(PROTOCOL_MAGIC.as_slice())?;
+    Some(serde_json::from_slice(json).map_err(|e| format!("Eroare parsare handshake: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Stream de test: citește din `input`, scrie în `output`
+    struct Duplex {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn duplex_with_reply(reply: &HelloReply) -> Duplex {
+        let mut input = Vec::new();
+        write_frame(&mut input, &serde_json::to_vec(reply).unwrap()).unwrap();
+        Duplex {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        }
+    }
+
+    fn hello(version: u32, min_version: u32, capabilities: &[&str]) -> Hello {
+        Hello {
+            version,
+            min_version,
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            intent: HelloIntent::Transfer,
+            name: String::new(),
+        }
+    }
+
+    #[test]
+    fn preamble_round_trip() {
+        let mut stream = duplex_with_reply(&Hello::new(HelloIntent::Info).answer("Editor 1", "editor"));
+        client_handshake(&mut stream, &Hello::new(HelloIntent::Info).with_name("Ion")).unwrap();
+
+        let frame = read_frame(&mut Cursor::new(stream.output), 1024).unwrap();
+        let parsed = parse_hello(&frame).unwrap().unwrap();
+        assert_eq!(parsed.version, PROTOCOL_VERSION);
+        assert_eq!(parsed.intent, HelloIntent::Info);
+        assert_eq!(parsed.name, "Ion");
+        assert_eq!(parsed.capabilities.len(), SUPPORTED_CAPABILITIES.len());
+    }
+
+    #[test]
+    fn preamble_fits_hello_limit() {
+        let mut stream = duplex_with_reply(&Hello::new(HelloIntent::Transfer).answer("Editor 1", "editor"));
+        let hello = Hello::new(HelloIntent::Transfer).with_name(&"Ana Maria Popescu ".repeat(14));
+        client_handshake(&mut stream, &hello).unwrap();
+
+        assert!(read_frame(&mut Cursor::new(stream.output), MAX_HELLO_LEN).is_ok());
+    }
+
+    #[test]
+    fn legacy_header_is_not_a_preamble() {
+        assert!(parse_hello(br#"{"photographer":"Ion","files":[]}"#).is_none());
+    }
+
+    #[test]
+    fn capabilities_are_intersected() {
+        let reply = hello(PROTOCOL_VERSION, 1, &[CAP_CHECKSUM, "compression"]).answer("Tagger", "tagger");
+        assert_eq!(reply.status, "ok");
+        assert_eq!(reply.capabilities, vec![CAP_CHECKSUM.to_string()]);
+
+        let negotiated = reply.negotiated();
+        assert!(negotiated.has(CAP_CHECKSUM));
+        assert!(!negotiated.has(CAP_RESUME));
+        assert!(!negotiated.has("compression"));
+    }
+
+    #[test]
+    fn newer_client_falls_back_to_common_version() {
+        let reply = hello(PROTOCOL_VERSION + 3, 1, &[]).answer("Tagger", "tagger");
+        assert_eq!(reply.status, "ok");
+        assert_eq!(reply.version, PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn incompatible_client_is_refused_with_readable_error() {
+        let reply = hello(PROTOCOL_VERSION + 2, PROTOCOL_VERSION + 1, &[]).answer("Tagger", "tagger");
+        assert_eq!(reply.status, "unsupported_version");
+        assert!(reply.error.as_deref().unwrap().contains("Actualizați aplicația receiver"));
+
+        let mut stream = duplex_with_reply(&reply);
+        let err = client_handshake(&mut stream, &Hello::new(HelloIntent::Transfer)).err().unwrap();
+        assert!(err.contains("Actualizați aplicația receiver"));
+    }
+
+    #[test]
+    fn closed_connection_means_legacy_receiver() {
+        let mut stream = Duplex {
+            input: Cursor::new(Vec::new()),
+            output: Vec::new(),
+        };
+        let result = client_handshake(&mut stream, &Hello::new(HelloIntent::Transfer)).unwrap();
+        assert!(matches!(result, ClientHandshake::Legacy));
+    }
+
+    #[test]
+    fn legacy_negotiation_has_no_capabilities() {
+        let negotiated = Negotiated::legacy();
+        assert_eq!(negotiated.version, 0);
+        assert!(!negotiated.has(CAP_CHECKSUM));
+    }
+}
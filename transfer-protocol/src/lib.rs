@@ -0,0 +1,40 @@
+//! Protocolul de transfer comun pentru sender (photo-transfer), receiver (porn-receiver)
+//! și trimiterile receiver→receiver.
+//!
+//! Pe fir, fiecare mesaj de control este un frame: lungime u32 big endian + payload JSON.
+//! Datele fișierelor circulă brute, urmate (dacă s-a negociat) de hash-ul fișierului
+//! și de confirmarea receiver-ului (`FileAck`). Dacă ambele părți suportă "encryption", tot ce urmează
+//! după handshake trece printr-un canal Noise (vezi `secure`).
+//! `stats` adună istoricul de transferuri în statisticile afișate de ambele aplicații.
+
+mod ack;
+mod attributes;
+mod frame;
+mod handshake;
+mod messages;
+mod paths;
+mod secure;
+mod stats;
+
+pub use ack::{read_checksum, write_checksum, FileAck, FileErrorCode, FileStatus, MAX_CHECKSUM_RETRIES};
+pub use attributes::FileAttributes;
+pub use frame::{read_frame, read_json, write_frame, write_json, MAX_HEADER_LEN};
+pub use handshake::{
+    client_handshake, parse_hello, ClientHandshake, Hello, HelloIntent, HelloReply, Negotiated, CAP_CHECKSUM,
+    CAP_ENCRYPTION, CAP_FILE_RESULT, CAP_PARALLEL, CAP_RESUME, MAX_HELLO_LEN, MIN_PROTOCOL_VERSION,
+    PROTOCOL_MAGIC, PROTOCOL_VERSION, SUPPORTED_CAPABILITIES,
+};
+pub use messages::{
+    event_key, event_key_matches, hash_prefix, AckResponse, DuplicateAction, DuplicateInfo, FileMetadata,
+    PartialFileInfo, ReceiverInfo, SendDecision, TransferHeader, TransferSession, STATUS_INSUFFICIENT_SPACE,
+};
+pub use paths::{
+    check_folder_name, check_name, is_part_file_name, keep_both_path, part_file_name, safe_relative_path,
+    sanitize_name,
+};
+pub use secure::{
+    check_plaintext, fingerprint, open_channel, pairing_required_error, secure_accept, secure_connect,
+    send_auth_reply, AuthReply, Channel, Identity, KeyStore, SecureMode, SecureStream, TrustedPeer, AUTH_OK,
+    AUTH_PAIRED, AUTH_PAIRING_REQUIRED, LEGACY_RECEIVER,
+};
+pub use stats::{Accumulator, HourLoad, HourLoads, Outcome, Summary};
@@ -0,0 +1,441 @@
+use crate::attributes::FileAttributes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Primul mesaj al unui transfer: cine trimite și ce fișiere
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferHeader {
+    pub photographer: String,
+    pub files: Vec<FileMetadata>,
+    pub is_folder_transfer: bool,
+    pub folder_name: Option<String>,
+    #[serde(default)]
+    pub sender_role: Option<String>, // "tagger", "editor", sau None pentru fotografi
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<TransferSession>, // Doar la transferurile pe mai multe conexiuni
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_key: Option<String>, // Derivat din parola evenimentului (vezi `event_key`)
+}
+
+/// Un transfer logic împărțit pe mai multe conexiuni paralele. Fiecare conexiune trimite
+/// propriul header cu o parte din fișiere; receiver-ul le adună în același folder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferSession {
+    pub id: String,
+    pub connections: u32, // Câte conexiuni aparțin transferului
+    pub total_files: usize,
+    pub total_bytes: u64, // Pentru progresul combinat
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    pub name: String,
+    #[serde(default)]
+    pub relative_path: String, // Calea relativă pentru structura subfolder (gol = doar name)
+    pub size: u64,
+    #[serde(default)]
+    pub checksum: String, // Gol - hash-ul se trimite după datele fișierului (calculat streaming)
+    #[serde(flatten)]
+    pub attributes: FileAttributes, // Timpii/flag-urile sursei (lipsesc la senderii vechi)
+}
+
+impl FileMetadata {
+    /// Cheia fișierului în decizie / offset-uri: relative_path, sau name dacă e gol
+    pub fn key(&self) -> &str {
+        if self.relative_path.is_empty() {
+            &self.name
+        } else {
+            &self.relative_path
+        }
+    }
+}
+
+/// Răspunsul receiver-ului la header
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AckResponse {
+    pub status: String,
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub duplicates: Vec<DuplicateInfo>,
+    #[serde(default)]
+    pub resume_folder: Option<String>, // Folderul existent pentru reluare transfer
+    #[serde(default)]
+    pub partial_files: Vec<PartialFileInfo>, // Fișiere primite parțial în folderul de reluare
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>, // Motivul refuzului, lizibil pentru utilizator
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing_bytes: Option<u64>, // Cu statusul "insufficient_space": cât spațiu lipsește pe receiver
+}
+
+impl AckResponse {
+    pub fn is_ready(&self) -> bool {
+        self.status == "ready"
+    }
+
+    /// Refuz cu motiv: "encryption_required", "passphrase_required", "wrong_passphrase", "rejected"
+    pub fn refused(status: &str, error: &str) -> Self {
+        Self {
+            status: status.to_string(),
+            folder: None,
+            duplicates: Vec::new(),
+            resume_folder: None,
+            partial_files: Vec::new(),
+            error: Some(error.to_string()),
+            missing_bytes: None,
+        }
+    }
+
+    /// Refuz înainte de transfer: fișierele (plus rezerva receiver-ului) nu încap pe disc
+    pub fn insufficient_space(missing_bytes: u64) -> Self {
+        Self {
+            missing_bytes: Some(missing_bytes),
+            ..Self::refused(STATUS_INSUFFICIENT_SPACE, &disk_full_message(missing_bytes))
+        }
+    }
+
+    /// Mesajul afișat senderului când ACK-ul nu e "ready"
+    pub fn refusal(&self) -> String {
+        // Mesajul e construit local - nu depinde de textul trimis de receiver
+        if let (STATUS_INSUFFICIENT_SPACE, Some(missing)) = (self.status.as_str(), self.missing_bytes) {
+            return disk_full_message(missing);
+        }
+        self.error
+            .clone()
+            .unwrap_or_else(|| format!("Receiver nu e gata: {}", self.status))
+    }
+}
+
+pub const STATUS_INSUFFICIENT_SPACE: &str = "insufficient_space";
+
+fn disk_full_message(missing_bytes: u64) -> String {
+    format!(
+        "Disc plin pe receiver: lipsesc {:.2} GB ({} bytes)",
+        missing_bytes as f64 / (1024.0 * 1024.0 * 1024.0),
+        missing_bytes
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateInfo {
+    pub file_name: String,
+    pub existing_path: String, // Unde există deja fișierul
+    pub existing_size: u64,
+    pub new_size: u64,
+    pub same_checksum: bool, // True dacă checksumul e identic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub existing_name: Option<String>, // Numele copiei existente, dacă diferă (fișier redenumit)
+}
+
+/// Fișier primit parțial de receiver (transfer întrerupt)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialFileInfo {
+    pub file_name: String,       // Cheia fișierului (vezi `FileMetadata::key`)
+    pub bytes_received: u64,     // Câți bytes există deja pe disc
+    pub prefix_checksum: String, // Hash-ul primilor bytes_received bytes
+}
+
+/// Ce face receiver-ul cu un fișier care există deja
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateAction {
+    Skip, // Fișierul nu se trimite
+    #[default]
+    Overwrite, // Înlocuiește fișierul cu același nume (comportamentul versiunilor vechi)
+    KeepBoth,  // Fișierul nou primește un sufix liber ("IMG_0001_2.jpg")
+}
+
+/// Decizia senderului după ACK: lista simplă (înțeleasă și de receiver-ele vechi)
+/// sau lista + offset-ul de la care se reia fiecare fișier și acțiunea aleasă pentru duplicate.
+/// Receiver-ele vechi ignoră acțiunile, deci "keep_both" ajunge la ele ca suprascriere.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SendDecision {
+    Files(Vec<String>),
+    Detailed {
+        files: Vec<String>,
+        #[serde(default)]
+        offsets: HashMap<String, u64>,
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        actions: HashMap<String, DuplicateAction>, // Fișierele fără acțiune primesc politica receiver-ului
+    },
+}
+
+impl SendDecision {
+    /// Fișierele marcate "skip" nu apar în listă. Folosește forma simplă când nu există
+    /// reluări sau acțiuni.
+    pub fn new(
+        files: Vec<String>,
+        offsets: HashMap<String, u64>,
+        mut actions: HashMap<String, DuplicateAction>,
+    ) -> Self {
+        let files: Vec<String> =
+            files.into_iter().filter(|f| actions.get(f) != Some(&DuplicateAction::Skip)).collect();
+        actions.retain(|_, action| *action != DuplicateAction::Skip);
+
+        if offsets.is_empty() && actions.is_empty() {
+            SendDecision::Files(files)
+        } else {
+            SendDecision::Detailed { files, offsets, actions }
+        }
+    }
+
+    pub fn into_parts(self) -> (Vec<String>, HashMap<String, u64>, HashMap<String, DuplicateAction>) {
+        match self {
+            SendDecision::Files(files) => (files, HashMap::new(), HashMap::new()),
+            SendDecision::Detailed { files, offsets, actions } => (files, offsets, actions),
+        }
+    }
+}
+
+/// Răspunsul la un INFO request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReceiverInfo {
+    pub name: String,
+    pub role: String,
+}
+
+const EVENT_KEY_CONTEXT: &str = "porn-transfer 2025 event passphrase v1";
+
+/// Cheia trimisă în header în locul parolei evenimentului (parola nu circulă pe rețea)
+pub fn event_key(passphrase: &str) -> String {
+    blake3::Hash::from(blake3::derive_key(EVENT_KEY_CONTEXT, passphrase.trim().as_bytes()))
+        .to_hex()
+        .to_string()
+}
+
+/// Compară cheia primită cu parola configurată (comparație în timp constant)
+pub fn event_key_matches(passphrase: &str, key: &str) -> bool {
+    let expected = blake3::Hash::from(blake3::derive_key(EVENT_KEY_CONTEXT, passphrase.trim().as_bytes()));
+    blake3::Hash::from_hex(key).is_ok_and(|received| received == expected)
+}
+
+/// Calculează hash-ul primilor `len` bytes. Hasher-ul returnat poate continua cu restul fișierului.
+pub fn hash_prefix<R: Read>(reader: R, len: u64) -> std::io::Result<blake3::Hasher> {
+    let mut hasher = blake3::Hasher::new();
+    let copied = std::io::copy(&mut reader.take(len), &mut hasher)?;
+    if copied < len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("Doar {} din {} bytes disponibili", copied, len),
+        ));
+    }
+    Ok(hasher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_header() -> TransferHeader {
+        TransferHeader {
+            photographer: "Ana Pop".to_string(),
+            files: vec![
+                FileMetadata {
+                    name: "IMG_0001.CR3".to_string(),
+                    relative_path: String::new(),
+                    size: 42,
+                    checksum: String::new(),
+                    attributes: FileAttributes {
+                        modified_ms: Some(1_700_000_000_000),
+                        created_ms: Some(1_690_000_000_000),
+                        readonly: true,
+                        label: Some(2),
+                    },
+                },
+                FileMetadata {
+                    name: "photo.jpg".to_string(),
+                    relative_path: "web/photo.jpg".to_string(),
+                    size: 7,
+                    checksum: String::new(),
+                    attributes: FileAttributes::default(),
+                },
+            ],
+            is_folder_transfer: true,
+            folder_name: Some("01 - Ana Pop".to_string()),
+            sender_role: Some("tagger".to_string()),
+            session: None,
+            event_key: None,
+        }
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let header = sample_header();
+        let json = serde_json::to_vec(&header).unwrap();
+        let decoded: TransferHeader = serde_json::from_slice(&json).unwrap();
+        assert_eq!(decoded, header);
+    }
+
+    #[test]
+    fn legacy_header_without_optional_fields() {
+        // Formatul trimis de clienții Python vechi
+        let json = r#"{"photographer":"Ion","files":[{"name":"a.jpg","size":3,"checksum":"abc"}],
+                       "is_folder_transfer":false,"folder_name":null}"#;
+        let header: TransferHeader = serde_json::from_str(json).unwrap();
+        assert_eq!(header.sender_role, None);
+        assert_eq!(header.files[0].relative_path, "");
+        assert_eq!(header.files[0].key(), "a.jpg");
+    }
+
+    #[test]
+    fn session_is_omitted_unless_set() {
+        let json = serde_json::to_string(&sample_header()).unwrap();
+        assert!(!json.contains("session"));
+
+        let mut header = sample_header();
+        header.session = Some(TransferSession {
+            id: "abc".to_string(),
+            connections: 4,
+            total_files: 120,
+            total_bytes: 3_000_000_000,
+        });
+        let decoded: TransferHeader = serde_json::from_slice(&serde_json::to_vec(&header).unwrap()).unwrap();
+        assert_eq!(decoded, header);
+    }
+
+    #[test]
+    fn event_key_checks_passphrase() {
+        let key = event_key("Nunta Ana & Ion");
+        assert!(event_key_matches("Nunta Ana & Ion", &key));
+        assert!(event_key_matches(" Nunta Ana & Ion ", &key));
+        assert!(!event_key_matches("nunta ana & ion", &key));
+        assert!(!event_key_matches("Nunta Ana & Ion", "not-hex"));
+        assert!(!key.contains("Nunta"));
+    }
+
+    #[test]
+    fn refused_ack_carries_readable_reason() {
+        let ack = AckResponse::refused("rejected", "Sender blocat");
+        let decoded: AckResponse = serde_json::from_slice(&serde_json::to_vec(&ack).unwrap()).unwrap();
+        assert!(!decoded.is_ready());
+        assert_eq!(decoded.refusal(), "Sender blocat");
+
+        let legacy: AckResponse = serde_json::from_str(r#"{"status":"busy","folder":null}"#).unwrap();
+        assert_eq!(legacy.refusal(), "Receiver nu e gata: busy");
+    }
+
+    #[test]
+    fn insufficient_space_reports_missing_bytes() {
+        let ack = AckResponse::insufficient_space(3 * 1024 * 1024 * 1024);
+        let decoded: AckResponse = serde_json::from_slice(&serde_json::to_vec(&ack).unwrap()).unwrap();
+        assert_eq!(decoded.status, STATUS_INSUFFICIENT_SPACE);
+        assert_eq!(decoded.missing_bytes, Some(3_221_225_472));
+        assert_eq!(decoded.refusal(), "Disc plin pe receiver: lipsesc 3.00 GB (3221225472 bytes)");
+        assert!(!serde_json::to_string(&AckResponse::refused("rejected", "x")).unwrap().contains("missing_bytes"));
+    }
+
+    #[test]
+    fn file_key_prefers_relative_path() {
+        let header = sample_header();
+        assert_eq!(header.files[0].key(), "IMG_0001.CR3");
+        assert_eq!(header.files[1].key(), "web/photo.jpg");
+    }
+
+    #[test]
+    fn ack_round_trip_keeps_duplicates_and_partials() {
+        let ack = AckResponse {
+            status: "ready".to_string(),
+            folder: Some("/tmp/.tmp_ana".to_string()),
+            duplicates: vec![DuplicateInfo {
+                file_name: "a.jpg".to_string(),
+                existing_path: "/tmp/01 - Ana".to_string(),
+                existing_size: 10,
+                new_size: 10,
+                same_checksum: true,
+                existing_name: Some("a_copy.jpg".to_string()),
+            }],
+            resume_folder: Some("/tmp/.tmp_ana".to_string()),
+            partial_files: vec![PartialFileInfo {
+                file_name: "b.mov".to_string(),
+                bytes_received: 1024,
+                prefix_checksum: "00ff".to_string(),
+            }],
+            error: None,
+            missing_bytes: None,
+        };
+        let json = serde_json::to_vec(&ack).unwrap();
+        let decoded: AckResponse = serde_json::from_slice(&json).unwrap();
+        assert_eq!(decoded, ack);
+        assert!(decoded.is_ready());
+    }
+
+    #[test]
+    fn minimal_ack_uses_defaults() {
+        let ack: AckResponse = serde_json::from_str(r#"{"status":"ready","folder":null}"#).unwrap();
+        assert!(ack.duplicates.is_empty());
+        assert!(ack.partial_files.is_empty());
+        assert_eq!(ack.resume_folder, None);
+    }
+
+    #[test]
+    fn decision_without_offsets_is_a_plain_list() {
+        let decision = SendDecision::new(vec!["a.jpg".to_string()], HashMap::new(), HashMap::new());
+        assert_eq!(serde_json::to_string(&decision).unwrap(), r#"["a.jpg"]"#);
+
+        let decoded: SendDecision = serde_json::from_str(r#"["a.jpg","b.jpg"]"#).unwrap();
+        let (files, offsets, actions) = decoded.into_parts();
+        assert_eq!(files, vec!["a.jpg", "b.jpg"]);
+        assert!(offsets.is_empty());
+        assert!(actions.is_empty());
+    }
+
+    #[test]
+    fn decision_with_offsets_round_trip() {
+        let offsets = HashMap::from([("b.mov".to_string(), 4096u64)]);
+        let decision =
+            SendDecision::new(vec!["a.jpg".to_string(), "b.mov".to_string()], offsets.clone(), HashMap::new());
+
+        let json = serde_json::to_vec(&decision).unwrap();
+        let (files, decoded_offsets, _) = serde_json::from_slice::<SendDecision>(&json).unwrap().into_parts();
+        assert_eq!(files, vec!["a.jpg", "b.mov"]);
+        assert_eq!(decoded_offsets, offsets);
+    }
+
+    #[test]
+    fn decision_with_actions_round_trip() {
+        let actions = HashMap::from([
+            ("a.jpg".to_string(), DuplicateAction::KeepBoth),
+            ("b.jpg".to_string(), DuplicateAction::Skip),
+            ("c.jpg".to_string(), DuplicateAction::Overwrite),
+        ]);
+        let names = ["a.jpg", "b.jpg", "c.jpg", "d.jpg"].map(String::from).to_vec();
+        let json = serde_json::to_string(&SendDecision::new(names, HashMap::new(), actions)).unwrap();
+        assert!(json.contains(r#""a.jpg":"keep_both""#));
+        assert!(!json.contains("b.jpg"));
+
+        let (files, offsets, actions) = serde_json::from_str::<SendDecision>(&json).unwrap().into_parts();
+        assert_eq!(files, vec!["a.jpg", "c.jpg", "d.jpg"]);
+        assert!(offsets.is_empty());
+        assert_eq!(actions.get("a.jpg"), Some(&DuplicateAction::KeepBoth));
+        assert_eq!(actions.get("c.jpg"), Some(&DuplicateAction::Overwrite));
+        assert_eq!(actions.get("d.jpg"), None);
+    }
+
+    #[test]
+    fn decision_from_older_sender_has_no_actions() {
+        let json = r#"{"files":["a.jpg"],"offsets":{"a.jpg":10}}"#;
+        let (files, offsets, actions) = serde_json::from_str::<SendDecision>(json).unwrap().into_parts();
+        assert_eq!(files, vec!["a.jpg"]);
+        assert_eq!(offsets.get("a.jpg"), Some(&10));
+        assert!(actions.is_empty());
+    }
+
+    #[test]
+    fn hash_prefix_matches_full_hash_of_prefix() {
+        let data = b"0123456789abcdef";
+        let prefix = hash_prefix(&data[..], 10).unwrap();
+        assert_eq!(prefix.finalize(), blake3::hash(&data[..10]));
+
+        // Hasher-ul continuă corect cu restul datelor
+        let mut resumed = hash_prefix(&data[..], 10).unwrap();
+        resumed.update(&data[10..]);
+        assert_eq!(resumed.finalize(), blake3::hash(data));
+    }
+
+    #[test]
+    fn hash_prefix_fails_on_short_input() {
+        let err = hash_prefix(&b"abc"[..], 10).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,325 @@
+use crate::messages::{FileMetadata, TransferHeader};
+use std::path::{Path, PathBuf};
+
+// Limita majorității sistemelor de fișiere pentru un singur nume (bytes)
+const MAX_NAME_LEN: usize = 255;
+// Adâncimea maximă a unei căi relative primite (subfoldere)
+const MAX_DEPTH: usize = 32;
+// Lungimea maximă a unui nume curățat (bytes) - încap două în numele unui folder temporar
+const MAX_SANITIZED_LEN: usize = 64;
+
+// Nume rezervate de Windows, indiferent de extensie ("CON.jpg" e tot consola)
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7",
+    "COM8", "COM9", "COM¹", "COM²", "COM³", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8",
+    "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+// Caractere interzise pe Windows; ':' deschide și un stream alternativ NTFS ("a.jpg:ascuns")
+const FORBIDDEN_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Verifică un singur nume primit de pe rețea (fișier, folder, fotograf): fără separatori,
+/// "." / "..", caractere de control, caractere interzise pe Windows sau nume rezervate.
+pub fn check_name(name: &str) -> Result<(), String> {
+    let reason = if name.is_empty() {
+        Some("nume gol")
+    } else if name.len() > MAX_NAME_LEN {
+        Some("nume prea lung")
+    } else if name == "." || name == ".." {
+        Some("referință la folder")
+    } else if name.contains(['/', '\\']) {
+        Some("conține separator de cale")
+    } else if name.chars().any(char::is_control) {
+        Some("conține caractere de control")
+    } else if name.contains(FORBIDDEN_CHARS) {
+        Some("conține caractere nepermise")
+    } else if name.ends_with(['.', ' ']) || name.starts_with(' ') {
+        // Windows taie punctele/spațiile de la final - "a.jpg." ar ajunge peste "a.jpg"
+        Some("începe sau se termină cu spațiu sau punct")
+    } else if is_reserved(name) {
+        Some("nume rezervat de Windows")
+    } else if is_part_file_name(name) {
+        Some("nume rezervat pentru fișierele în curs de primire")
+    } else {
+        None
+    };
+
+    match reason {
+        Some(reason) => Err(format!("Nume nepermis {:?}: {}", name, reason)),
+        None => Ok(()),
+    }
+}
+
+fn is_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved))
+}
+
+/// Nume de folder creat pe receiver (numele folderului trimis):
+/// în plus, nu poate începe cu "." - folderele ascunse sunt cele temporare (".tmp_*") și indexul.
+pub fn check_folder_name(name: &str) -> Result<(), String> {
+    check_name(name)?;
+    if name.starts_with('.') {
+        return Err(format!("Nume nepermis {:?}: folder ascuns", name));
+    }
+    Ok(())
+}
+
+/// Calea relativă a unui fișier, separată cu "/" sau "\" (senderi Windows). Căile absolute,
+/// prefixele de disc/UNC și componentele "." / ".." sunt refuzate, nu corectate.
+/// Rezultatul e construit din componente, deci are separatorul sistemului local.
+pub fn safe_relative_path(path: &str) -> Result<PathBuf, String> {
+    let components: Vec<&str> = path.split(['/', '\\']).collect();
+    if components.len() > MAX_DEPTH {
+        return Err(format!("Cale nepermisă {:?}: prea multe subfoldere", path));
+    }
+
+    let mut relative = PathBuf::new();
+    for component in components {
+        // O componentă goală înseamnă cale absolută ("/x", "\\server") sau separatori dubli
+        check_name(component).map_err(|e| format!("Cale nepermisă {:?}: {}", path, e))?;
+        relative.push(component);
+    }
+    Ok(relative)
+}
+
+/// Un nume liber (al fotografului) pregătit pentru numele unui folder, în loc să fie refuzat:
+/// separatorii și caracterele nepermise devin "_", punctele și spațiile de la capete dispar,
+/// iar un nume gol sau rezervat primește "_" în față. Rezultatul trece de `check_folder_name`.
+pub fn sanitize_name(name: &str) -> String {
+    let mut cleaned = String::new();
+    for c in name.chars() {
+        if cleaned.len() + c.len_utf8() > MAX_SANITIZED_LEN {
+            break;
+        }
+        let unsafe_char = c.is_control() || c == '/' || c == '\\' || FORBIDDEN_CHARS.contains(&c);
+        cleaned.push(if unsafe_char { '_' } else { c });
+    }
+    let trimmed = cleaned.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if trimmed.is_empty() || is_reserved(trimmed) || is_part_file_name(trimmed) {
+        format!("_{}", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Numele sub care receiver-ul scrie un fișier până la verificare: ascuns și cu sufixul ".part",
+/// ca un fișier trimis chiar cu numele "X.part" să nu fie luat drept neterminat
+pub fn part_file_name(name: &str) -> String {
+    format!(".{}.part", name)
+}
+
+/// Numele e al unui fișier în curs de primire (vezi `part_file_name`); `check_name` îl refuză
+pub fn is_part_file_name(name: &str) -> bool {
+    name.starts_with('.') && name.ends_with(".part")
+}
+
+/// Prima cale liberă lângă `path` pentru "păstrează ambele": "IMG_0001.jpg" → "IMG_0001_2.jpg",
+/// apoi "_3" și așa mai departe
+pub fn keep_both_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (2..)
+        .map(|n| path.with_file_name(format!("{}_{}{}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+impl FileMetadata {
+    /// Calea fișierului relativ la folderul transferului (vezi `safe_relative_path`)
+    pub fn local_path(&self) -> Result<PathBuf, String> {
+        safe_relative_path(self.key())
+    }
+}
+
+impl TransferHeader {
+    /// Verifică toate numele din header care ajung în căi pe disc, înainte de orice acces la fișiere.
+    /// Numele fotografului nu e verificat - e text liber ("Ion Pop Jr."), curățat cu `sanitize_name`.
+    pub fn check_paths(&self) -> Result<(), String> {
+        if let Some(ref folder_name) = self.folder_name {
+            check_folder_name(folder_name)?;
+        }
+        for file in &self.files {
+            check_name(&file.name)?;
+            if !file.relative_path.is_empty() {
+                safe_relative_path(&file.relative_path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::FileAttributes;
+
+    fn file(name: &str, relative_path: &str) -> FileMetadata {
+        FileMetadata {
+            name: name.to_string(),
+            relative_path: relative_path.to_string(),
+            size: 1,
+            checksum: String::new(),
+            attributes: FileAttributes::default(),
+        }
+    }
+
+    fn header(photographer: &str, folder_name: Option<&str>, files: Vec<FileMetadata>) -> TransferHeader {
+        TransferHeader {
+            photographer: photographer.to_string(),
+            files,
+            is_folder_transfer: folder_name.is_some(),
+            folder_name: folder_name.map(str::to_string),
+            sender_role: None,
+            session: None,
+            event_key: None,
+        }
+    }
+
+    #[test]
+    fn ordinary_names_are_accepted() {
+        for name in ["IMG_0001.CR3", "DSC 0042.jpg", "ședință foto.NEF", ".DS_Store", "a..b.jpg", "COMPUTER.jpg"] {
+            assert!(check_name(name).is_ok(), "{}", name);
+        }
+        assert!(check_name("con1").is_ok());
+        assert_eq!(safe_relative_path("web/photo.jpg").unwrap(), Path::new("web").join("photo.jpg"));
+        assert_eq!(safe_relative_path("web\\raw\\a.CR3").unwrap(), Path::new("web").join("raw").join("a.CR3"));
+        assert!(header("Ana Pop", Some("01 - Ana Pop"), vec![file("a.jpg", "web/a.jpg")]).check_paths().is_ok());
+    }
+
+    #[test]
+    fn part_file_names_are_reserved() {
+        assert!(check_name("a.part").is_ok());
+        assert!(check_name(&part_file_name("a.jpg")).is_err());
+        assert!(check_name(".DS_Store").is_ok());
+        assert!(is_part_file_name(&part_file_name("a.part")));
+        assert!(!is_part_file_name("a.jpg.part"));
+    }
+
+    #[test]
+    fn parent_references_are_rejected() {
+        for path in ["..", "../a.jpg", "../../etc/passwd", "web/../../a.jpg", "web/..", "..\\..\\a.jpg", "web\\..\\a"] {
+            assert!(safe_relative_path(path).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn absolute_and_prefixed_paths_are_rejected() {
+        for path in [
+            "/etc/passwd",
+            "\\Windows\\System32\\a.dll",
+            "C:\\Users\\a.jpg",
+            "C:/Users/a.jpg",
+            "C:a.jpg",
+            "\\\\server\\share\\a.jpg",
+            "//server/share/a.jpg",
+            "\\\\?\\C:\\a.jpg",
+            "\\\\.\\pipe\\x",
+        ] {
+            assert!(safe_relative_path(path).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn empty_and_dot_components_are_rejected() {
+        for path in ["", "a//b.jpg", "a/./b.jpg", "./a.jpg", "a.jpg/", "web/"] {
+            assert!(safe_relative_path(path).is_err(), "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn reserved_windows_names_are_rejected() {
+        for name in ["CON", "con.jpg", "Aux.tar.gz", "NUL", "nul .txt", "COM1.jpg", "lpt9", "COM¹", "CONIN$"] {
+            assert!(check_name(name).is_err(), "{}", name);
+        }
+        assert!(safe_relative_path("web/PRN/a.jpg").is_err());
+    }
+
+    #[test]
+    fn control_and_forbidden_characters_are_rejected() {
+        for name in ["a\0.jpg", "a\n.jpg", "a\u{7f}.jpg", "a:stream", "a.jpg:$DATA", "a?.jpg", "a*.jpg", "a|b", "<a>"] {
+            assert!(check_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn trailing_dots_and_spaces_are_rejected() {
+        for name in ["a.jpg.", "a.jpg ", " a.jpg", "...", ". ."] {
+            assert!(check_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn overlong_names_and_paths_are_rejected() {
+        assert!(check_name(&"a".repeat(MAX_NAME_LEN)).is_ok());
+        assert!(check_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+        // Limita e în bytes: 128 de caractere de câte doi bytes depășesc 255
+        assert!(check_name(&"ș".repeat(128)).is_err());
+        assert!(safe_relative_path(&vec!["d"; MAX_DEPTH + 1].join("/")).is_err());
+        assert!(safe_relative_path(&vec!["d"; MAX_DEPTH].join("/")).is_ok());
+    }
+
+    #[test]
+    fn hostile_headers_are_rejected() {
+        let hostile = [
+            header("Ana", Some(".."), vec![file("a.jpg", "")]),
+            header("Ana", Some("../Editor/01 - Ion"), vec![file("a.jpg", "")]),
+            header("Ana", Some("C:\\Users"), vec![file("a.jpg", "")]),
+            header("Ana", Some("01 - Ana\0"), vec![file("a.jpg", "")]),
+            header("Ana", None, vec![file("../a.jpg", "")]),
+            header("Ana", None, vec![file("a.jpg", "../../a.jpg")]),
+            header("Ana", None, vec![file("a.jpg", "/tmp/a.jpg")]),
+            header("Ana", None, vec![file("a.jpg", "web\\..\\..\\a.jpg")]),
+            header("Ana", None, vec![file("ok.jpg", ""), file("CON", "")]),
+            header("Ana", None, vec![file("a.jpg", "web/aux/a.jpg")]),
+        ];
+        for header in hostile {
+            assert!(header.check_paths().is_err(), "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn photographer_names_are_sanitized_not_rejected() {
+        for name in ["Ion Pop Jr.", "Ana: Studio", "Who?", "../../Library", "Ana/../x", ".tmp_ana_1", "CON", ""] {
+            assert!(header(name, None, vec![file("a.jpg", "")]).check_paths().is_ok(), "{:?}", name);
+            let sanitized = sanitize_name(name);
+            assert!(check_folder_name(&sanitized).is_ok(), "{:?} → {:?}", name, sanitized);
+        }
+        assert_eq!(sanitize_name("Ion Pop Jr."), "Ion Pop Jr");
+        assert_eq!(sanitize_name("Ana: Studio"), "Ana_ Studio");
+        assert_eq!(sanitize_name("../../Library"), "_.._Library");
+        assert!(sanitize_name(&"ș".repeat(200)).len() <= MAX_SANITIZED_LEN);
+    }
+
+    #[test]
+    fn hostile_json_header_is_rejected() {
+        // Header exact cum ar ajunge de pe rețea, cu secvențe escape JSON
+        let json = r#"{"photographer":"Ion","files":[{"name":"a.jpg","relative_path":"..\u002f..\u002fa.jpg","size":3}],
+                       "is_folder_transfer":false,"folder_name":null}"#;
+        let header: TransferHeader = serde_json::from_str(json).unwrap();
+        assert!(header.check_paths().is_err());
+        assert!(header.files[0].local_path().is_err());
+    }
+
+    #[test]
+    fn local_path_uses_name_without_relative_path() {
+        assert_eq!(file("a.jpg", "").local_path().unwrap(), Path::new("a.jpg"));
+        assert_eq!(file("a.jpg", "web/a.jpg").local_path().unwrap(), Path::new("web").join("a.jpg"));
+        assert!(file("..", "").local_path().is_err());
+    }
+
+    #[test]
+    fn keep_both_path_picks_next_free_suffix() {
+        let folder = std::env::temp_dir().join(format!("tp_keep_both_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let original = folder.join("IMG_0001.jpg");
+        std::fs::write(&original, b"a").unwrap();
+
+        assert_eq!(keep_both_path(&original), folder.join("IMG_0001_2.jpg"));
+        std::fs::write(folder.join("IMG_0001_2.jpg"), b"b").unwrap();
+        assert_eq!(keep_both_path(&original), folder.join("IMG_0001_3.jpg"));
+        assert_eq!(keep_both_path(&folder.join("README")), folder.join("README_2"));
+
+        let _ = std::fs::remove_dir_all(folder);
+    }
+}
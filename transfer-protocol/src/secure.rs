@@ -0,0 +1,739 @@
+use crate::frame::{read_json, write_json};
+use crate::handshake::{Negotiated, CAP_ENCRYPTION};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity as _;
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::Path;
+
+// Canal criptat (Noise) negociat prin capabilitatea "encryption", pornit imediat după HelloReply.
+// Fiecare aplicație are o cheie X25519 permanentă. Prima conexiune între două dispozitive
+// folosește PIN-ul afișat pe receiver; după asociere, cheile se verifică direct (XX).
+//
+// PIN-ul are doar 10^6 valori, deci nu poate fi folosit direct ca PSK: cine interceptează un
+// mesaj criptat cu el îl găsește prin încercări offline. Asocierea începe cu un schimb CPace
+// (PAKE pe ristretto255) cheiat cu PIN-ul, iar cheia rezultată devine PSK-ul pentru XXpsk3.
+// Un atacator activ poate verifica un singur PIN per conexiune, iar PIN-ul se schimbă după fiecare încercare.
+const NOISE_PAIRED: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+const NOISE_PIN: &str = "Noise_XXpsk3_25519_ChaChaPoly_BLAKE2s";
+const PIN_CONTEXT: &str = "porn-transfer 2025 pairing CPace generator v2";
+const PAKE_KEY_CONTEXT: &str = "porn-transfer 2025 pairing CPace key v2";
+
+const MAX_NOISE_MESSAGE: usize = 65535;
+const TAG_LEN: usize = 16;
+const MAX_PLAINTEXT: usize = MAX_NOISE_MESSAGE - TAG_LEN;
+const MAX_AUTH_REPLY: usize = 64 * 1024;
+
+/// Răspunsul receiver-ului după handshake-ul criptat (primul mesaj criptat)
+pub const AUTH_OK: &str = "ok";
+pub const AUTH_PAIRED: &str = "paired";
+pub const AUTH_PAIRING_REQUIRED: &str = "pairing_required";
+
+/// Prefixul erorii pentru un receiver fără criptare care nu a fost permis; UI-ul cere confirmarea
+pub const LEGACY_RECEIVER: &str = "legacy_receiver";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthReply {
+    pub status: String,
+    #[serde(default)]
+    pub name: String, // Numele receiver-ului (salvat la asociere)
+}
+
+/// Cum se autentifică cele două părți
+#[derive(Debug, Clone, Copy)]
+pub enum SecureMode<'a> {
+    Paired,       // Cheile trebuie să fie deja asociate
+    Pin(&'a str), // Asociere nouă cu PIN-ul afișat pe receiver
+}
+
+/// Cheia permanentă a aplicației
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identity {
+    pub public_key: String, // hex
+    private_key: String,    // hex
+}
+
+impl Identity {
+    pub fn generate() -> Result<Self, String> {
+        let keypair = snow::Builder::new(noise_params(NOISE_PAIRED))
+            .generate_keypair()
+            .map_err(|e| format!("Eroare generare cheie: {}", e))?;
+        Ok(Self {
+            public_key: hex::encode(keypair.public),
+            private_key: hex::encode(keypair.private),
+        })
+    }
+
+    /// Amprenta scurtă afișată în UI pentru verificare vizuală
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.public_key)
+    }
+
+    fn private_bytes(&self) -> Result<Vec<u8>, String> {
+        hex::decode(&self.private_key).map_err(|e| format!("Cheie privată invalidă: {}", e))
+    }
+}
+
+/// Primele 8 caractere ale hash-ului cheii publice, grupate câte 4
+pub fn fingerprint(public_key: &str) -> String {
+    let hash = blake3::hash(public_key.as_bytes()).to_hex();
+    format!("{}-{}", &hash[..4], &hash[4..8]).to_uppercase()
+}
+
+/// Un dispozitiv asociat prin PIN
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrustedPeer {
+    pub public_key: String,
+    pub name: String,
+    pub paired_at: u64, // Unix timestamp
+}
+
+/// Cheia proprie + dispozitivele asociate, salvate într-un fișier JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyStore {
+    pub identity: Identity,
+    #[serde(default)]
+    pub peers: Vec<TrustedPeer>,
+}
+
+impl KeyStore {
+    /// Încarcă fișierul sau generează o cheie nouă la prima pornire
+    pub fn load_or_create(path: &Path) -> Result<Self, String> {
+        if path.exists() {
+            let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+            return serde_json::from_str(&content).map_err(|e| format!("Fișier chei invalid: {}", e));
+        }
+
+        let store = Self::generate()?;
+        store.save(path)?;
+        Ok(store)
+    }
+
+    /// Cheie nouă, fără dispozitive asociate (nesalvată)
+    pub fn generate() -> Result<Self, String> {
+        Ok(Self {
+            identity: Identity::generate()?,
+            peers: Vec::new(),
+        })
+    }
+
+    /// Scrie întâi un fișier temporar, apoi îl redenumește: o întrerupere nu lasă cheile pe jumătate scrise.
+    /// Cheia privată nu trebuie să fie citibilă de alți utilizatori, deci fișierul e creat direct cu 0600.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let mut part = path.as_os_str().to_owned();
+        part.push(".part");
+        let part = std::path::PathBuf::from(part);
+
+        let written = (|| -> std::io::Result<()> {
+            // Un .part rămas de la o întrerupere ar păstra drepturile lui
+            let _ = std::fs::remove_file(&part);
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            let mut file = options.open(&part)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+            drop(file);
+            std::fs::rename(&part, path)?;
+            #[cfg(unix)]
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                std::fs::File::open(dir)?.sync_all()?;
+            }
+            Ok(())
+        })();
+        if written.is_err() {
+            let _ = std::fs::remove_file(&part);
+        }
+        written.map_err(|e| format!("Eroare salvare chei {}: {}", path.display(), e))
+    }
+
+    pub fn is_trusted(&self, public_key: &str) -> bool {
+        self.peers.iter().any(|p| p.public_key == public_key)
+    }
+
+    pub fn peer_name(&self, public_key: &str) -> Option<&str> {
+        self.peers
+            .iter()
+            .find(|p| p.public_key == public_key)
+            .map(|p| p.name.as_str())
+    }
+
+    /// Adaugă (sau actualizează numele) unui dispozitiv asociat
+    pub fn trust(&mut self, public_key: &str, name: &str) {
+        let paired_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        match self.peers.iter_mut().find(|p| p.public_key == public_key) {
+            Some(peer) => {
+                peer.name = name.to_string();
+                peer.paired_at = paired_at;
+            }
+            None => self.peers.push(TrustedPeer {
+                public_key: public_key.to_string(),
+                name: name.to_string(),
+                paired_at,
+            }),
+        }
+    }
+
+    pub fn forget(&mut self, public_key: &str) {
+        self.peers.retain(|p| p.public_key != public_key);
+    }
+}
+
+/// Stream criptat: fiecare mesaj Noise e precedat de lungimea lui (u16 big endian)
+pub struct SecureStream<S> {
+    inner: S,
+    transport: snow::TransportState,
+    remote_key: String,
+    read_buf: Vec<u8>,
+    read_pos: usize,
+    message: Vec<u8>,
+}
+
+impl<S> SecureStream<S> {
+    /// Cheia publică (hex) a celeilalte părți, verificată în handshake
+    pub fn remote_key(&self) -> &str {
+        &self.remote_key
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: Read> Read for SecureStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.read_pos == self.read_buf.len() {
+            let ciphertext = match read_noise_message(&mut self.inner) {
+                Ok(c) => c,
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(0),
+                Err(e) => return Err(e),
+            };
+            self.read_buf.resize(MAX_NOISE_MESSAGE, 0);
+            let len = self
+                .transport
+                .read_message(&ciphertext, &mut self.read_buf)
+                .map_err(|e| invalid_data(format!("Mesaj criptat invalid: {}", e)))?;
+            self.read_buf.truncate(len);
+            self.read_pos = 0;
+        }
+
+        let n = buf.len().min(self.read_buf.len() - self.read_pos);
+        buf[..n].copy_from_slice(&self.read_buf[self.read_pos..self.read_pos + n]);
+        self.read_pos += n;
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for SecureStream<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(MAX_PLAINTEXT);
+        self.message.resize(MAX_NOISE_MESSAGE, 0);
+        let len = self
+            .transport
+            .write_message(&buf[..n], &mut self.message)
+            .map_err(|e| invalid_data(format!("Eroare criptare: {}", e)))?;
+        write_noise_message(&mut self.inner, &self.message[..len])?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Conexiunea după handshake: criptată sau (cu receiver-e vechi) în clar
+pub enum Channel<S> {
+    Plain(S),
+    Secure(Box<SecureStream<S>>),
+}
+
+impl<S> Channel<S> {
+    pub fn get_ref(&self) -> &S {
+        match self {
+            Channel::Plain(s) => s,
+            Channel::Secure(s) => s.get_ref(),
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        matches!(self, Channel::Secure(_))
+    }
+
+    /// Cheia publică a celeilalte părți (doar pe canalul criptat)
+    pub fn remote_key(&self) -> Option<&str> {
+        match self {
+            Channel::Plain(_) => None,
+            Channel::Secure(s) => Some(s.remote_key()),
+        }
+    }
+}
+
+impl<S: Read> Read for Channel<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Channel::Plain(s) => s.read(buf),
+            Channel::Secure(s) => s.read(buf),
+        }
+    }
+}
+
+impl<S: Write> Write for Channel<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Channel::Plain(s) => s.write(buf),
+            Channel::Secure(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Channel::Plain(s) => s.flush(),
+            Channel::Secure(s) => s.flush(),
+        }
+    }
+}
+
+/// Handshake-ul criptat din partea clientului (sender). În modul `Paired` receiver-ul
+/// trebuie să fie deja în `keys`; cu PIN, cheia lui e salvată după asociere.
+pub fn secure_connect<S: Read + Write>(
+    stream: S,
+    keys: &mut KeyStore,
+    mode: SecureMode,
+    negotiated: &Negotiated,
+) -> Result<(SecureStream<S>, AuthReply), String> {
+    let mut secure = noise_handshake(stream, &keys.identity, mode, negotiated, true)?;
+
+    if matches!(mode, SecureMode::Paired) && !keys.is_trusted(secure.remote_key()) {
+        return Err(pairing_required_error(&fingerprint(secure.remote_key())));
+    }
+
+    let reply: AuthReply = match read_json(&mut secure, MAX_AUTH_REPLY) {
+        Ok(reply) => reply,
+        // La PIN greșit receiver-ul nu poate decripta ultimul mesaj și închide conexiunea
+        Err(_) if matches!(mode, SecureMode::Pin(_)) => {
+            return Err("PIN greșit sau expirat. Verificați PIN-ul afișat pe receiver.".to_string())
+        }
+        Err(e) => return Err(format!("Eroare citire autentificare: {}", e)),
+    };
+
+    match reply.status.as_str() {
+        AUTH_OK => {}
+        AUTH_PAIRED => keys.trust(secure.remote_key(), &reply.name),
+        AUTH_PAIRING_REQUIRED => return Err(pairing_required_error(&fingerprint(secure.remote_key()))),
+        other => return Err(format!("Receiver-ul a refuzat conexiunea: {}", other)),
+    }
+
+    Ok((secure, reply))
+}
+
+/// Canalul de transfer al clientului după handshake: criptat cu un receiver asociat,
+/// în clar doar către un receiver vechi permis explicit (vezi `check_plaintext`)
+pub fn open_channel<S: Read + Write>(
+    stream: S,
+    keys: &mut KeyStore,
+    negotiated: &Negotiated,
+    target_name: &str,
+    allow_legacy: bool,
+) -> Result<Channel<S>, String> {
+    if !negotiated.has(CAP_ENCRYPTION) {
+        check_plaintext(keys, target_name, allow_legacy)?;
+        return Ok(Channel::Plain(stream));
+    }
+    let (secure, _) = secure_connect(stream, keys, SecureMode::Paired, negotiated)?;
+    Ok(Channel::Secure(Box::new(secure)))
+}
+
+/// Handshake-ul criptat din partea receiver-ului. Verificarea cheii clientului (și
+/// trimiterea `AuthReply`) rămâne în sarcina apelantului.
+pub fn secure_accept<S: Read + Write>(
+    stream: S,
+    identity: &Identity,
+    mode: SecureMode,
+    negotiated: &Negotiated,
+) -> Result<SecureStream<S>, String> {
+    noise_handshake(stream, identity, mode, negotiated, false)
+}
+
+pub fn send_auth_reply<S: Read + Write>(stream: &mut SecureStream<S>, status: &str, name: &str) -> Result<(), String> {
+    let reply = AuthReply {
+        status: status.to_string(),
+        name: name.to_string(),
+    };
+    write_json(stream, &reply).map_err(|e| format!("Eroare trimitere autentificare: {}", e))
+}
+
+/// Un receiver fără criptare (protocolul vechi sau fără capabilitatea "encryption") primește fișiere
+/// în clar doar dacă utilizatorul l-a permis explicit. Unul asociat suportă sigur criptarea, deci
+/// lipsa ei înseamnă că cineva din rețea a modificat handshake-ul - refuzat chiar dacă e permis.
+pub fn check_plaintext(keys: &KeyStore, target_name: &str, allow_legacy: bool) -> Result<(), String> {
+    if keys.peers.iter().any(|p| p.name == target_name) {
+        return Err(format!(
+            "Receiver-ul {} este asociat, dar conexiunea nu este criptată. Transfer oprit (posibilă interceptare).",
+            target_name
+        ));
+    }
+    if !allow_legacy {
+        return Err(format!(
+            "{}: receiver-ul {} nu suportă criptarea. Fișierele pot fi trimise doar dacă permiteți \
+             transferul necriptat către el.",
+            LEGACY_RECEIVER, target_name
+        ));
+    }
+    Ok(())
+}
+
+/// Mesajul de eroare când receiver-ul nu e asociat; UI-ul îl recunoaște după `AUTH_PAIRING_REQUIRED`
+pub fn pairing_required_error(fingerprint: &str) -> String {
+    format!(
+        "{}: dispozitivul nu este asociat (cheie {}). Introduceți PIN-ul afișat pe receiver.",
+        AUTH_PAIRING_REQUIRED, fingerprint
+    )
+}
+
+fn noise_params(pattern: &str) -> snow::params::NoiseParams {
+    // Numele de pattern sunt constante valide
+    pattern.parse().expect("pattern Noise invalid")
+}
+
+fn noise_handshake<S: Read + Write>(
+    mut stream: S,
+    identity: &Identity,
+    mode: SecureMode,
+    negotiated: &Negotiated,
+    initiator: bool,
+) -> Result<SecureStream<S>, String> {
+    let private_key = identity.private_bytes()?;
+    // Prologul leagă handshake-ul de versiunea și capabilitățile negociate în clar
+    let prologue = format!("PTXF v{} {}", negotiated.version, negotiated.capabilities.join(","));
+
+    let (pattern, psk) = match mode {
+        SecureMode::Paired => (NOISE_PAIRED, None),
+        SecureMode::Pin(pin) => (NOISE_PIN, Some(pake_exchange(&mut stream, pin, prologue.as_bytes(), initiator)?)),
+    };
+
+    let mut builder = snow::Builder::new(noise_params(pattern))
+        .local_private_key(&private_key)
+        .prologue(prologue.as_bytes());
+    if let Some(ref psk) = psk {
+        builder = builder.psk(3, psk);
+    }
+    let mut state = if initiator {
+        builder.build_initiator()
+    } else {
+        builder.build_responder()
+    }
+    .map_err(|e| format!("Eroare inițializare criptare: {}", e))?;
+
+    let mut buf = vec![0u8; MAX_NOISE_MESSAGE];
+    while !state.is_handshake_finished() {
+        if state.is_my_turn() {
+            let len = state
+                .write_message(&[], &mut buf)
+                .map_err(|e| format!("Eroare handshake criptat: {}", e))?;
+            write_noise_message(&mut stream, &buf[..len])
+                .map_err(|e| format!("Eroare trimitere handshake criptat: {}", e))?;
+        } else {
+            let message =
+                read_noise_message(&mut stream).map_err(|e| format!("Eroare citire handshake criptat: {}", e))?;
+            state
+                .read_message(&message, &mut buf)
+                .map_err(|_| "Autentificare eșuată (cheie sau PIN greșit)".to_string())?;
+        }
+    }
+
+    let remote_key = state
+        .get_remote_static()
+        .map(hex::encode)
+        .ok_or("Cheia celeilalte părți lipsește")?;
+    let transport = state
+        .into_transport_mode()
+        .map_err(|e| format!("Eroare handshake criptat: {}", e))?;
+
+    Ok(SecureStream {
+        inner: stream,
+        transport,
+        remote_key,
+        read_buf: Vec::new(),
+        read_pos: 0,
+        message: Vec::new(),
+    })
+}
+
+/// Generatorul CPace: punct pe curbă derivat din PIN și din prologul negociat
+fn pake_generator(pin: &str, prologue: &[u8]) -> RistrettoPoint {
+    let pin = pin.trim().as_bytes();
+    let mut hasher = blake3::Hasher::new_derive_key(PIN_CONTEXT);
+    hasher.update(&(pin.len() as u64).to_be_bytes());
+    hasher.update(pin);
+    hasher.update(prologue);
+    let mut uniform = [0u8; 64];
+    hasher.finalize_xof().fill(&mut uniform);
+    RistrettoPoint::from_uniform_bytes(&uniform)
+}
+
+/// Schimbul CPace dinaintea handshake-ului Noise. Fiecare parte trimite y·G, unde G depinde de PIN;
+/// cheia comună y_a·y_b·G se potrivește doar dacă ambele părți au folosit același PIN, iar mesajele
+/// interceptate nu permit verificarea altor PIN-uri.
+fn pake_exchange<S: Read + Write>(
+    stream: &mut S,
+    pin: &str,
+    prologue: &[u8],
+    initiator: bool,
+) -> Result<[u8; 32], String> {
+    let secret = Scalar::random(&mut OsRng);
+    let own = (pake_generator(pin, prologue) * secret).compress();
+
+    let send = |stream: &mut S| {
+        write_noise_message(stream, own.as_bytes()).map_err(|e| format!("Eroare trimitere asociere: {}", e))
+    };
+    let receive = |stream: &mut S| {
+        let message = read_noise_message(stream).map_err(|e| format!("Eroare citire asociere: {}", e))?;
+        CompressedRistretto::from_slice(&message)
+            .ok()
+            .and_then(|point| point.decompress())
+            .filter(|point| *point != RistrettoPoint::identity())
+            .ok_or_else(|| "Mesaj de asociere invalid".to_string())
+    };
+
+    let (initiator_point, responder_point, remote) = if initiator {
+        send(stream)?;
+        let remote = receive(stream)?;
+        (own, remote.compress(), remote)
+    } else {
+        let remote = receive(stream)?;
+        send(stream)?;
+        (remote.compress(), own, remote)
+    };
+
+    let shared = (remote * secret).compress();
+    let mut hasher = blake3::Hasher::new_derive_key(PAKE_KEY_CONTEXT);
+    hasher.update(shared.as_bytes());
+    hasher.update(initiator_point.as_bytes());
+    hasher.update(responder_point.as_bytes());
+    hasher.update(prologue);
+    Ok(*hasher.finalize().as_bytes())
+}
+
+fn write_noise_message<W: Write>(writer: &mut W, message: &[u8]) -> std::io::Result<()> {
+    let len = u16::try_from(message.len()).map_err(|_| invalid_data("Mesaj criptat prea mare".to_string()))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(message)
+}
+
+fn read_noise_message<R: Read>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let mut len_buf = [0u8; 2];
+    reader.read_exact(&mut len_buf)?;
+    let mut message = vec![0u8; u16::from_be_bytes(len_buf) as usize];
+    reader.read_exact(&mut message)?;
+    Ok(message)
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{read_frame, write_frame};
+    use std::net::{TcpListener, TcpStream};
+
+    fn negotiated() -> Negotiated {
+        Negotiated {
+            version: 1,
+            capabilities: vec!["encryption".to_string()],
+        }
+    }
+
+    fn store() -> KeyStore {
+        KeyStore {
+            identity: Identity::generate().unwrap(),
+            peers: Vec::new(),
+        }
+    }
+
+    /// Rulează `server` pe o conexiune locală și `client` pe cealaltă
+    fn with_pair<T: Send + 'static>(
+        server: impl FnOnce(TcpStream) -> T + Send + 'static,
+        client: impl FnOnce(TcpStream),
+    ) -> T {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = std::thread::spawn(move || server(listener.accept().unwrap().0));
+        client(TcpStream::connect(addr).unwrap());
+        handle.join().unwrap()
+    }
+
+    #[test]
+    fn pin_pairing_then_paired_transfer() {
+        let mut sender = store();
+        let receiver = store();
+        let receiver_identity = receiver.identity.clone();
+
+        // Asociere cu PIN: receiver-ul acceptă orice cheie care știe PIN-ul
+        let sender_key = with_pair(
+            move |stream| {
+                let mut secure =
+                    secure_accept(stream, &receiver_identity, SecureMode::Pin("482913"), &negotiated()).unwrap();
+                send_auth_reply(&mut secure, AUTH_PAIRED, "Tagger 1").unwrap();
+                secure.remote_key().to_string()
+            },
+            |stream| {
+                let (_, reply) = secure_connect(stream, &mut sender, SecureMode::Pin("482913"), &negotiated()).unwrap();
+                assert_eq!(reply.name, "Tagger 1");
+            },
+        );
+        assert_eq!(sender_key, sender.identity.public_key);
+        assert_eq!(sender.peer_name(&receiver.identity.public_key), Some("Tagger 1"));
+
+        // După asociere: date mari prin canalul criptat, în ambele direcții
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let expected = data.clone();
+        let receiver_identity = receiver.identity.clone();
+        let received = with_pair(
+            move |stream| {
+                let mut secure = secure_accept(stream, &receiver_identity, SecureMode::Paired, &negotiated()).unwrap();
+                send_auth_reply(&mut secure, AUTH_OK, "").unwrap();
+                let frame = read_frame(&mut secure, 1024).unwrap();
+                let mut body = vec![0u8; 300_000];
+                secure.read_exact(&mut body).unwrap();
+                write_frame(&mut secure, b"OK").unwrap();
+                (frame, body)
+            },
+            |stream| {
+                let (mut secure, _) = secure_connect(stream, &mut sender, SecureMode::Paired, &negotiated()).unwrap();
+                write_frame(&mut secure, b"header").unwrap();
+                secure.write_all(&data).unwrap();
+                assert_eq!(read_frame(&mut secure, 16).unwrap(), b"OK");
+            },
+        );
+        assert_eq!(received.0, b"header");
+        assert_eq!(received.1, expected);
+    }
+
+    #[test]
+    fn wrong_pin_fails_on_both_sides() {
+        let mut sender = store();
+        let receiver_identity = store().identity;
+
+        let server_result = with_pair(
+            move |stream| {
+                secure_accept(stream, &receiver_identity, SecureMode::Pin("111111"), &negotiated()).map(|_| ())
+            },
+            |stream| {
+                let err = secure_connect(stream, &mut sender, SecureMode::Pin("222222"), &negotiated())
+                    .err()
+                    .unwrap();
+                assert!(err.contains("PIN"), "{}", err);
+            },
+        );
+        assert!(server_result.is_err());
+        assert!(sender.peers.is_empty());
+    }
+
+    #[test]
+    fn pake_agrees_only_on_the_same_pin() {
+        let exchange = |server_pin: &'static str, client_pin: &str| {
+            let mut client_key = [0u8; 32];
+            let server_key = with_pair(
+                move |mut stream| pake_exchange(&mut stream, server_pin, b"PTXF v1", false).unwrap(),
+                |mut stream| client_key = pake_exchange(&mut stream, client_pin, b"PTXF v1", true).unwrap(),
+            );
+            (server_key, client_key)
+        };
+
+        let (server, client) = exchange("482913", " 482913 ");
+        assert_eq!(server, client);
+        // Cheie nouă la fiecare asociere, chiar cu același PIN
+        assert_ne!(exchange("482913", "482913").0, server);
+
+        let (server, client) = exchange("482913", "482914");
+        assert_ne!(server, client);
+    }
+
+    #[test]
+    fn unknown_receiver_requires_pairing() {
+        let mut sender = store();
+        let receiver_identity = store().identity;
+
+        with_pair(
+            move |stream| {
+                // Receiver-ul nu mai primește nimic - clientul renunță după handshake
+                let _ = secure_accept(stream, &receiver_identity, SecureMode::Paired, &negotiated());
+            },
+            |stream| {
+                let err = secure_connect(stream, &mut sender, SecureMode::Paired, &negotiated())
+                    .err()
+                    .unwrap();
+                assert!(err.starts_with(AUTH_PAIRING_REQUIRED), "{}", err);
+            },
+        );
+    }
+
+    #[test]
+    fn plaintext_only_for_allowed_unpaired_receivers() {
+        let mut keys = store();
+        keys.trust("abcd", "Editor");
+
+        assert!(check_plaintext(&keys, "Tagger vechi", true).is_ok());
+        let err = check_plaintext(&keys, "Tagger vechi", false).unwrap_err();
+        assert!(err.starts_with(LEGACY_RECEIVER), "{}", err);
+        // Un receiver asociat care apare fără criptare nu e o versiune veche
+        let err = check_plaintext(&keys, "Editor", true).unwrap_err();
+        assert!(!err.starts_with(LEGACY_RECEIVER), "{}", err);
+    }
+
+    #[test]
+    fn mismatched_negotiation_breaks_handshake() {
+        let mut sender = store();
+        let receiver_identity = store().identity;
+
+        let server_result = with_pair(
+            move |stream| {
+                let downgraded = Negotiated {
+                    version: 1,
+                    capabilities: Vec::new(),
+                };
+                secure_accept(stream, &receiver_identity, SecureMode::Pin("1234"), &downgraded).map(|_| ())
+            },
+            |stream| {
+                assert!(secure_connect(stream, &mut sender, SecureMode::Pin("1234"), &negotiated()).is_err());
+            },
+        );
+        assert!(server_result.is_err());
+    }
+
+    #[test]
+    fn key_store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("ptxf-keys-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("keys.json");
+        let _ = std::fs::remove_file(&path);
+
+        let mut created = KeyStore::load_or_create(&path).unwrap();
+        created.trust("abcd", "Editor");
+        created.save(&path).unwrap();
+
+        let loaded = KeyStore::load_or_create(&path).unwrap();
+        assert_eq!(loaded.identity.public_key, created.identity.public_key);
+        assert!(loaded.is_trusted("abcd"));
+        assert_eq!(loaded.identity.fingerprint().len(), 9);
+        assert!(!dir.join("keys.json.part").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+const MB: f64 = 1024.0 * 1024.0;
+
+/// Cum s-a încheiat un transfer, pentru statistici
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Complete,
+    Failed,
+    Interrupted, // Parțial pe receiver, anulat pe sender
+}
+
+/// Volumul, viteza și rata de eșec ale unui grup de transferuri
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub files: usize,
+    pub bytes: u64,
+    pub average_mbps: Option<f64>, // MB / timpul total, doar transferurile cu viteza salvată
+    pub peak_mbps: Option<f64>,    // Cel mai rapid transfer
+    pub failed: usize,
+    pub interrupted: usize,
+    pub failure_rate: f64, // 0..1
+    pub interrupted_rate: f64,
+}
+
+/// Adună transferurile unui grup. Viteza medie se calculează din timpul estimat al fiecărui
+/// transfer (bytes / viteză), ca un transfer mic și rapid să nu cântărească cât unul de 50 GB.
+#[derive(Debug, Default)]
+pub struct Accumulator {
+    summary: Summary,
+    measured_mb: f64,
+    measured_secs: f64,
+}
+
+impl Accumulator {
+    pub fn add(&mut self, files: usize, bytes: u64, outcome: Outcome, speed_mbps: Option<f64>) {
+        let s = &mut self.summary;
+        s.count += 1;
+        s.files += files;
+        s.bytes += bytes;
+        match outcome {
+            Outcome::Complete => {}
+            Outcome::Failed => s.failed += 1,
+            Outcome::Interrupted => s.interrupted += 1,
+        }
+        if let Some(speed) = speed_mbps.filter(|s| s.is_finite() && *s > 0.0) {
+            let mb = bytes as f64 / MB;
+            self.measured_mb += mb;
+            self.measured_secs += mb / speed;
+            s.peak_mbps = Some(s.peak_mbps.map_or(speed, |peak| peak.max(speed)));
+        }
+    }
+
+    pub fn finish(mut self) -> Summary {
+        let s = &mut self.summary;
+        if self.measured_secs > 0.0 {
+            s.average_mbps = Some(self.measured_mb / self.measured_secs);
+        }
+        if s.count > 0 {
+            s.failure_rate = s.failed as f64 / s.count as f64;
+            s.interrupted_rate = s.interrupted as f64 / s.count as f64;
+        }
+        self.summary
+    }
+}
+
+/// Transferurile dintr-o oră din zi
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HourLoad {
+    pub hour: u32,
+    pub count: usize,
+    pub bytes: u64,
+}
+
+/// Încărcarea pe ore a unei liste de transferuri
+#[derive(Debug, Default)]
+pub struct HourLoads {
+    hours: HashMap<u32, HourLoad>,
+}
+
+impl HourLoads {
+    pub fn add(&mut self, hour: u32, bytes: u64) {
+        let load = self.hours.entry(hour).or_insert(HourLoad { hour, count: 0, bytes: 0 });
+        load.count += 1;
+        load.bytes += bytes;
+    }
+
+    /// Cele mai încărcate ore primele (după bytes), doar orele cu transferuri
+    pub fn busiest(self) -> Vec<HourLoad> {
+        let mut hours: Vec<HourLoad> = self.hours.into_values().collect();
+        hours.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.hour.cmp(&b.hour)));
+        hours
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn average_is_weighted_by_size() {
+        let mut acc = Accumulator::default();
+        // 1 GB la 100 MB/s + 1 GB la 50 MB/s = 2048 MB în 30.72 s
+        acc.add(10, GB, Outcome::Complete, Some(100.0));
+        acc.add(10, GB, Outcome::Complete, Some(50.0));
+        // Un transfer mic și rapid abia mișcă media
+        acc.add(1, 1024 * 1024, Outcome::Complete, Some(1000.0));
+
+        let summary = acc.finish();
+        let average = summary.average_mbps.unwrap();
+        assert!((average - 66.7).abs() < 0.1, "{}", average);
+        assert_eq!(summary.peak_mbps, Some(1000.0));
+        assert_eq!(summary.files, 21);
+        assert_eq!(summary.bytes, 2 * GB + 1024 * 1024);
+    }
+
+    #[test]
+    fn transfers_without_speed_count_but_dont_skew_speed() {
+        let mut acc = Accumulator::default();
+        acc.add(3, GB, Outcome::Complete, Some(80.0));
+        acc.add(3, GB, Outcome::Complete, None);
+        acc.add(3, GB, Outcome::Complete, Some(f64::NAN));
+        acc.add(3, GB, Outcome::Complete, Some(0.0));
+
+        let summary = acc.finish();
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.average_mbps, Some(80.0));
+        assert_eq!(summary.peak_mbps, Some(80.0));
+    }
+
+    #[test]
+    fn rates() {
+        let mut acc = Accumulator::default();
+        acc.add(1, 10, Outcome::Complete, None);
+        acc.add(1, 10, Outcome::Failed, None);
+        acc.add(1, 10, Outcome::Interrupted, None);
+        acc.add(1, 10, Outcome::Interrupted, None);
+
+        let summary = acc.finish();
+        assert_eq!((summary.failed, summary.interrupted), (1, 2));
+        assert_eq!(summary.failure_rate, 0.25);
+        assert_eq!(summary.interrupted_rate, 0.5);
+        assert_eq!(summary.average_mbps, None);
+    }
+
+    #[test]
+    fn empty_group() {
+        assert_eq!(Accumulator::default().finish(), Summary::default());
+    }
+
+    #[test]
+    fn busiest_hours_by_bytes() {
+        let mut loads = HourLoads::default();
+        loads.add(14, 100);
+        loads.add(9, 300);
+        loads.add(14, 200);
+        loads.add(22, 300);
+
+        assert_eq!(
+            loads.busiest(),
+            vec![
+                HourLoad { hour: 9, count: 1, bytes: 300 },
+                HourLoad { hour: 14, count: 2, bytes: 300 },
+                HourLoad { hour: 22, count: 1, bytes: 300 },
+            ]
+        );
+    }
+}
@@ -27,13 +27,13 @@ VERSION=$(grep '"version"' "$PROJECT_DIR/src-tauri/tauri.conf.json" | head -1 |
 echo "📦 Version: $VERSION"
 echo ""
 
-# Check if builds exist
-ARM64_APP="$PROJECT_DIR/src-tauri/target/aarch64-apple-darwin/release/bundle/macos/Porn Receiver.app"
-X64_APP="$PROJECT_DIR/src-tauri/target/x86_64-apple-darwin/release/bundle/macos/Porn Receiver.app"
+# Check if builds exist (the Cargo workspace at the repo root builds into its target/)
+ARM64_APP="$SCRIPT_DIR/../target/aarch64-apple-darwin/release/bundle/macos/Porn Receiver.app"
+X64_APP="$SCRIPT_DIR/../target/x86_64-apple-darwin/release/bundle/macos/Porn Receiver.app"
 
 if [ "$APP_TYPE" == "sender" ]; then
-    ARM64_APP="$PROJECT_DIR/src-tauri/target/aarch64-apple-darwin/release/bundle/macos/Porn Sender.app"
-    X64_APP="$PROJECT_DIR/src-tauri/target/x86_64-apple-darwin/release/bundle/macos/Porn Sender.app"
+    ARM64_APP="$SCRIPT_DIR/../target/aarch64-apple-darwin/release/bundle/macos/Porn Sender.app"
+    X64_APP="$SCRIPT_DIR/../target/x86_64-apple-darwin/release/bundle/macos/Porn Sender.app"
 fi
 
 # Create tar.gz files